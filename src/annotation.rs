@@ -0,0 +1,215 @@
+// Every `Node` stores an `Annotation` inline that is empty until a value is stored in it.  The tree
+// installs a reference to the annotation of the node a `GameDynamics` callback is evaluated for in
+// a scoped thread local for the duration of the call so that the callback signatures don't have to
+// change; `with_annotation` reads the thread local.  `GameDynamics::score_leaves` is evaluated for
+// several new leaves at once, so the annotations of all of them are installed in a second scoped
+// thread local, which `with_leaf_annotation` reads.
+
+use scoped_tls_hkt::scoped_thread_local;
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::OnceLock;
+
+scoped_thread_local!(static CURRENT: Annotation);
+scoped_thread_local!(static LEAVES: for<'a> &'a [&'a Annotation]);
+
+/// A slot to attach arbitrary user data to a `Node` (e.g. a cached list of moves, features of an
+/// evaluator, or the policy output of a neural network) that is not part of the `Node`'s score.
+///
+/// The slot is empty when the `Node` is created and can be set once; the value is dropped together
+/// with the `Node`.  The annotation is available to [`GameDynamics`](crate::GameDynamics)
+/// callbacks via [`with_annotation`] and to other code via
+/// [`Node::annotation`](crate::Node::annotation) and
+/// [`Cursor::with_annotation`](crate::Cursor::with_annotation).
+#[derive(Default)]
+pub struct Annotation {
+    value: OnceLock<Box<dyn Any + Send + Sync>>,
+}
+
+impl Annotation {
+    /// Construct a new, empty `Annotation`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value of the annotation if it is set and of type `T`.
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.value.get()?.downcast_ref()
+    }
+
+    /// Returns the value of the annotation, calling `f` to set it if the annotation is empty.  If
+    /// several threads initialize the annotation at the same time, only one of them calls `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the annotation is already set to a value of a type other than `T`.
+    pub fn get_or_init<T, F>(&self, f: F) -> &T
+    where
+        T: Any + Send + Sync,
+        F: FnOnce() -> T,
+    {
+        self.value
+            .get_or_init(|| Box::new(f()))
+            .downcast_ref()
+            .expect("the annotation is set to a value of a different type")
+    }
+
+    /// Sets the value of the annotation.  Returns `value` if the annotation was already set.
+    pub fn set<T: Any + Send + Sync>(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.value.get_or_init(|| Box::new(value.take().unwrap()));
+        match value {
+            None => Ok(()),
+            Some(value) => Err(value),
+        }
+    }
+
+    /// Returns `true` if the annotation is set.
+    pub fn is_set(&self) -> bool {
+        self.value.get().is_some()
+    }
+}
+
+impl Debug for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Annotation")
+            .field("is_set", &self.is_set())
+            .finish()
+    }
+}
+
+/// Calls `f` with the annotation of the `Node` that the [`GameDynamics`](crate::GameDynamics)
+/// callback running on the current thread is evaluated for, or with `None` outside of a callback.
+///
+/// The `Node` is the one whose player and state are passed to the callback, i.e. the parent in
+/// [`GameDynamics::available_actions`](crate::GameDynamics::available_actions),
+/// [`GameDynamics::action_prior`](crate::GameDynamics::action_prior),
+/// [`GameDynamics::select_node`](crate::GameDynamics::select_node) and
+/// [`GameDynamics::backprop_scores`](crate::GameDynamics::backprop_scores), and the new leaf in
+/// [`GameDynamics::score_leaf`](crate::GameDynamics::score_leaf) and
+/// [`GameDynamics::score_repetition`](crate::GameDynamics::score_repetition).  Within
+/// [`GameDynamics::score_leaves`](crate::GameDynamics::score_leaves), the annotations of the new
+/// leaves are available via [`with_leaf_annotation`] instead.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+///
+/// // e.g. in `GameDynamics::score_leaf`
+/// fn features(state: &u64) -> u32 {
+///     with_annotation(|annotation| match annotation {
+///         Some(annotation) => *annotation.get_or_init(|| state.count_ones()),
+///         None => state.count_ones(),
+///     })
+/// }
+/// ```
+pub fn with_annotation<T>(f: impl FnOnce(Option<&Annotation>) -> T) -> T {
+    if CURRENT.is_set() {
+        CURRENT.with(|current| f(Some(current)))
+    } else {
+        f(None)
+    }
+}
+
+/// Calls `f` with the annotation of the `index`-th state passed to the
+/// [`GameDynamics::score_leaves`](crate::GameDynamics::score_leaves) call running on the current
+/// thread, or with `None` outside of `score_leaves` (or if `index` is out of bounds).
+///
+/// The default implementation of `score_leaves` installs the annotation of each state while it
+/// calls [`GameDynamics::score_leaf`](crate::GameDynamics::score_leaf) for it, so that
+/// [`with_annotation`] behaves the same whether or not the leaves are scored in batches.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+///
+/// // e.g. in `GameDynamics::score_leaves`, storing the policy output of a network per leaf
+/// fn store_policies(policies: Vec<Vec<f32>>) {
+///     for (i, policy) in policies.into_iter().enumerate() {
+///         with_leaf_annotation(i, |annotation| {
+///             if let Some(annotation) = annotation {
+///                 let _ = annotation.set(policy);
+///             }
+///         });
+///     }
+/// }
+/// ```
+pub fn with_leaf_annotation<T>(index: usize, f: impl FnOnce(Option<&Annotation>) -> T) -> T {
+    if LEAVES.is_set() {
+        LEAVES.with(|leaves| f(leaves.get(index).copied()))
+    } else {
+        f(None)
+    }
+}
+
+// Calls `f` with the annotation of the `index`-th leaf (see `with_leaf_annotation`) installed as
+// the current annotation of the thread
+pub(crate) fn with_leaf_installed<T>(index: usize, f: impl FnOnce() -> T) -> T {
+    with_leaf_annotation(index, |leaf| match leaf {
+        Some(leaf) => install(leaf, f),
+        None => f(),
+    })
+}
+
+// Calls `f` with `annotation` installed as the current annotation of the thread; the previous one
+// is restored when `f` returns (or panics)
+pub(crate) fn install<T>(annotation: &Annotation, f: impl FnOnce() -> T) -> T {
+    CURRENT.set(annotation, f)
+}
+
+// Calls `f` with the annotations of the leaves scored by `GameDynamics::score_leaves` installed
+pub(crate) fn install_leaves<T>(annotations: &[&Annotation], f: impl FnOnce() -> T) -> T {
+    LEAVES.set(annotations, f)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_annotation() {
+        let a = Annotation::new();
+        assert!(!a.is_set());
+        assert_eq!(a.get::<u32>(), None);
+        assert_eq!(*a.get_or_init(|| 1_u32), 1);
+        assert_eq!(*a.get_or_init(|| 2_u32), 1);
+        assert_eq!(a.set(3_u32), Err(3));
+        assert_eq!(a.get::<u32>(), Some(&1));
+        assert_eq!(a.get::<u64>(), None);
+    }
+
+    #[test]
+    fn test_install() {
+        let outer = Annotation::new();
+        let inner = Annotation::new();
+        outer.set("outer").unwrap();
+        inner.set("inner").unwrap();
+        assert!(with_annotation(|a| a.is_none()));
+        install(&outer, || {
+            install(&inner, || {
+                assert_eq!(
+                    with_annotation(|a| *a.unwrap().get::<&str>().unwrap()),
+                    "inner"
+                );
+            });
+            assert_eq!(
+                with_annotation(|a| *a.unwrap().get::<&str>().unwrap()),
+                "outer"
+            );
+        });
+        assert!(with_annotation(|a| a.is_none()));
+
+        install_leaves(&[&outer, &inner], || {
+            with_leaf_installed(1, || {
+                assert_eq!(
+                    with_annotation(|a| *a.unwrap().get::<&str>().unwrap()),
+                    "inner"
+                );
+            });
+            assert!(with_leaf_annotation(2, |a| a.is_none()));
+        });
+        assert!(with_leaf_annotation(0, |a| a.is_none()));
+    }
+}
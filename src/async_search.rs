@@ -0,0 +1,610 @@
+// A `SearchTask` runs the search on dedicated threads so that an async executor is never blocked by
+// `SearchTree::step`.  A reporter thread owns the workers (via `std::thread::scope`), publishes a
+// `ProgressSnapshot` every `progress_interval` and the `SearchOutcome` once the workers are done;
+// futures waiting on either are woken via the `Waker`s registered in `State`.  The futures only
+// rely on `std::future` so that they can be awaited on any executor.
+
+use crate::game_dynamics::GameDynamics;
+use crate::tree::{SearchTree, Status};
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// The amount of search performed by a [`SearchTask`] (or per move by a
+/// [`SelfPlay`](crate::SelfPlay) driver).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchBudget {
+    /// Search until the tree has been stepped this many times (in total across all threads).
+    Steps(usize),
+    /// Search for this long.
+    Time(Duration),
+}
+
+/// The parameters of a [`SearchTask`].
+#[derive(Debug, Clone)]
+pub struct AsyncSearchConfig {
+    /// When to stop searching.
+    pub budget: SearchBudget,
+    /// The number of threads stepping the tree.
+    pub n_threads: usize,
+    /// The interval at which [`ProgressSnapshot`]s are published.
+    pub progress_interval: Duration,
+}
+
+impl Default for AsyncSearchConfig {
+    fn default() -> Self {
+        Self {
+            budget: SearchBudget::Time(Duration::from_secs(1)),
+            n_threads: 1,
+            progress_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// The state of a search published periodically by a [`SearchTask`] (see
+/// [`SearchTask::progress`]) or passed to the callback of [`search_with_progress`].
+#[derive(Debug, Clone)]
+pub struct ProgressSnapshot<A, Q> {
+    /// The currently anticipated best action at the root.
+    pub best_action: Status<A>,
+    /// The principal variation (see [`SearchTree::principal_variation`]); its length is the depth
+    /// of the principal variation.
+    pub pv: Vec<A>,
+    /// The score of the root.
+    pub root_score: Option<Q>,
+    /// The number of nodes in the tree.
+    pub n_nodes: usize,
+    /// The number of steps performed by the search so far.
+    pub n_steps: usize,
+    /// The number of steps performed per second since the search was started.
+    pub steps_per_sec: f64,
+    /// The time elapsed since the search was started.
+    pub elapsed: Duration,
+}
+
+impl<A, Q> ProgressSnapshot<A, Q> {
+    fn capture<T>(tree: &T, n_steps: usize, elapsed: Duration) -> Self
+    where
+        T: ?Sized + SearchTree,
+        T::GD: GameDynamics<Action = A, Score = Q>,
+        <T::GD as GameDynamics>::Player: Clone,
+        Q: Clone,
+    {
+        let secs = elapsed.as_secs_f64();
+        Self {
+            best_action: tree.best_action(),
+            pv: tree.principal_variation(),
+            root_score: tree.get_root_info().score,
+            n_nodes: tree.get_registry_info().snapshot().len,
+            n_steps,
+            steps_per_sec: if secs > 0.0 {
+                n_steps as f64 / secs
+            } else {
+                0.0
+            },
+            elapsed,
+        }
+    }
+}
+
+/// When [`search_with_progress`] reports the progress of the search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressInterval {
+    /// Every time this many steps have been performed (in total across all threads).
+    Steps(usize),
+    /// Every time this much time has elapsed.
+    Time(Duration),
+}
+
+/// The result of a [`SearchTask`].
+#[derive(Debug, Clone)]
+pub struct SearchOutcome<A> {
+    /// The best action at the root once the search stopped.
+    pub best_action: Status<A>,
+    /// The number of steps performed by the search.
+    pub n_steps: usize,
+    /// The time the search took.
+    pub elapsed: Duration,
+    /// `true` if the search was stopped via [`SearchTask::cancel`] before the budget was used.
+    pub cancelled: bool,
+}
+
+struct State<A, Q> {
+    snapshot: Option<ProgressSnapshot<A, Q>>,
+    // incremented whenever `snapshot` is replaced
+    generation: usize,
+    outcome: Option<SearchOutcome<A>>,
+    finished: bool,
+    wakers: Vec<Waker>,
+}
+
+impl<A, Q> State<A, Q> {
+    fn register(&mut self, waker: &Waker) {
+        if !self.wakers.iter().any(|w| w.will_wake(waker)) {
+            self.wakers.push(waker.clone());
+        }
+    }
+
+    fn wake_all(&mut self) {
+        self.wakers.drain(..).for_each(Waker::wake);
+    }
+}
+
+struct Shared<A, Q> {
+    cancelled: AtomicBool,
+    n_steps: AtomicUsize,
+    state: Mutex<State<A, Q>>,
+}
+
+// Marks the search as finished when the reporter thread exits, including when a step panicked (in
+// which case there is no outcome)
+struct Finished<'a, A, Q>(&'a Shared<A, Q>);
+
+impl<A, Q> Drop for Finished<'_, A, Q> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.finished = true;
+        state.wake_all();
+    }
+}
+
+// Decrements the number of active workers when a worker exits (including when a step panics)
+struct Active<'a>(&'a Mutex<usize>, &'a Condvar);
+
+impl Drop for Active<'_> {
+    fn drop(&mut self) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+        self.1.notify_all();
+    }
+}
+
+/// A search running on dedicated threads that can be awaited from async code, e.g. from the
+/// handler of a web server, without blocking the executor.
+///
+/// A `SearchTask` is a [`Future`] that resolves to the [`SearchOutcome`] once the
+/// [`SearchBudget`] is used up.  Progress can be observed via [`SearchTask::progress`].  Dropping
+/// the `SearchTask` cancels the search (the threads exit after completing their current step).
+/// Polling a `SearchTask` panics if a step of the search panicked.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// async fn think<T>(tree: Arc<T>) -> Status<<T::GD as GameDynamics>::Action>
+/// where
+///     T: SearchTree + Send + Sync + 'static,
+///     <T::GD as GameDynamics>::Action: Clone + Send + 'static,
+///     <T::GD as GameDynamics>::Player: Clone,
+///     <T::GD as GameDynamics>::Score: Clone + Send + 'static,
+/// {
+///     let config = AsyncSearchConfig {
+///         budget: SearchBudget::Time(Duration::from_millis(500)),
+///         ..AsyncSearchConfig::default()
+///     };
+///     let task = SearchTask::spawn(tree, config);
+///     let mut progress = task.progress();
+///     while let Some(snapshot) = progress.next_snapshot().await {
+///         println!("{} nodes after {:?}", snapshot.n_nodes, snapshot.elapsed);
+///     }
+///     task.await.best_action
+/// }
+/// ```
+pub struct SearchTask<A, Q> {
+    shared: Arc<Shared<A, Q>>,
+}
+
+impl<A, Q> SearchTask<A, Q>
+where
+    A: Clone + Send + 'static,
+    Q: Clone + Send + 'static,
+{
+    /// Starts searching `tree` on `config.n_threads` new threads.
+    ///
+    /// Each `SearchTask` starts its own OS threads (the workers and a thread publishing the
+    /// progress) rather than drawing from a shared pool; they exit once the search has finished or
+    /// been cancelled.  A server running many searches concurrently should therefore bound the
+    /// number of live `SearchTask`s itself, e.g. with a semaphore around `spawn`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.n_threads` is zero.
+    pub fn spawn<T>(tree: Arc<T>, config: AsyncSearchConfig) -> Self
+    where
+        T: ?Sized + SearchTree + Send + Sync + 'static,
+        T::GD: GameDynamics<Action = A, Score = Q>,
+        <T::GD as GameDynamics>::Player: Clone,
+    {
+        assert!(config.n_threads > 0, "at least one thread is required");
+        let shared = Arc::new(Shared {
+            cancelled: AtomicBool::new(false),
+            n_steps: AtomicUsize::new(0),
+            state: Mutex::new(State {
+                snapshot: None,
+                generation: 0,
+                outcome: None,
+                finished: false,
+                wakers: Vec::new(),
+            }),
+        });
+        std::thread::spawn({
+            let shared = Arc::clone(&shared);
+            move || Self::run(&*tree, &config, &shared)
+        });
+        Self { shared }
+    }
+
+    fn run<T>(tree: &T, config: &AsyncSearchConfig, shared: &Shared<A, Q>)
+    where
+        T: ?Sized + SearchTree + Sync,
+        T::GD: GameDynamics<Action = A, Score = Q>,
+        <T::GD as GameDynamics>::Player: Clone,
+    {
+        let _finished = Finished(shared);
+        let start = Instant::now();
+        let snapshot = || {
+            let n_steps = shared.n_steps.load(Ordering::Relaxed);
+            ProgressSnapshot::capture(tree, n_steps, start.elapsed())
+        };
+        let publish = |snapshot| {
+            let mut state = shared.state.lock().unwrap();
+            state.snapshot = Some(snapshot);
+            state.generation += 1;
+            state.wake_all();
+        };
+
+        run_workers(
+            tree,
+            config.budget,
+            config.n_threads,
+            &shared.n_steps,
+            Some(&shared.cancelled),
+            Some(ProgressInterval::Time(config.progress_interval)),
+            |_| publish(snapshot()),
+        );
+
+        let last = snapshot();
+        let outcome = SearchOutcome {
+            best_action: last.best_action.clone(),
+            n_steps: last.n_steps,
+            elapsed: last.elapsed,
+            cancelled: shared.cancelled.load(Ordering::Relaxed),
+        };
+        publish(last);
+        shared.state.lock().unwrap().outcome = Some(outcome);
+    }
+}
+
+impl<A, Q> SearchTask<A, Q> {
+    /// Returns a [`Progress`] receiving the snapshots published by the search.
+    pub fn progress(&self) -> Progress<A, Q> {
+        Progress {
+            shared: Arc::clone(&self.shared),
+            seen: 0,
+        }
+    }
+
+    /// Returns the number of steps performed by the search so far.
+    pub fn n_steps(&self) -> usize {
+        self.shared.n_steps.load(Ordering::Relaxed)
+    }
+
+    /// Stops the search early; the `SearchTask` then resolves to the outcome of the search so
+    /// far.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<A, Q> Future for SearchTask<A, Q> {
+    type Output = SearchOutcome<A>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(outcome) = state.outcome.take() {
+            Poll::Ready(outcome)
+        } else if state.finished {
+            panic!("search thread panicked");
+        } else {
+            state.register(cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<A, Q> Drop for SearchTask<A, Q> {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl<A, Q> Debug for SearchTask<A, Q> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SearchTask")
+            .field("n_steps", &self.n_steps())
+            .field("cancelled", &self.shared.cancelled.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// Receives the [`ProgressSnapshot`]s published by a [`SearchTask`].  Snapshots that are
+/// published while the receiver is not waiting are skipped, i.e. [`Progress::next_snapshot`] always
+/// returns the most recent snapshot.
+///
+/// `Progress` is a poll-based stand-in for a `Stream` (this crate does not depend on `futures`):
+/// await [`Progress::next_snapshot`] in a loop until it returns `None`.  To use it where a
+/// `Stream` is expected (e.g. for a streaming actix-web response), wrap it with
+/// `futures::stream::unfold`.
+pub struct Progress<A, Q> {
+    shared: Arc<Shared<A, Q>>,
+    seen: usize,
+}
+
+impl<A, Q> Progress<A, Q> {
+    /// Returns a future resolving to the next snapshot, or to `None` once the search has finished
+    /// and the final snapshot has been received.
+    pub fn next_snapshot(&mut self) -> NextProgress<'_, A, Q> {
+        NextProgress(self)
+    }
+}
+
+impl<A, Q> Debug for Progress<A, Q> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Progress")
+            .field("seen", &self.seen)
+            .finish()
+    }
+}
+
+/// The future returned by [`Progress::next_snapshot`].
+#[derive(Debug)]
+pub struct NextProgress<'a, A, Q>(&'a mut Progress<A, Q>);
+
+impl<A: Clone, Q: Clone> Future for NextProgress<'_, A, Q> {
+    type Output = Option<ProgressSnapshot<A, Q>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let progress = &mut *self.0;
+        let mut state = progress.shared.state.lock().unwrap();
+        if state.generation > progress.seen {
+            progress.seen = state.generation;
+            Poll::Ready(state.snapshot.clone())
+        } else if state.finished {
+            Poll::Ready(None)
+        } else {
+            state.register(cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+/// Searches `tree` on `n_threads` threads until `budget` is used up and calls `callback` on the
+/// calling thread with a [`ProgressSnapshot`] at every `interval` and once more when the search
+/// has finished (unless the previous snapshot already included all steps), e.g. to print UCI
+/// `info` lines or to update a progress bar.  Returns the final snapshot.
+///
+/// This is the blocking counterpart of [`SearchTask`]; the callback does not need to be `Send`.
+///
+/// # Panics
+///
+/// Panics if `n_threads` is zero or `interval` is `ProgressInterval::Steps(0)`.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use std::sync::Mutex;
+///
+/// let game = nim::Nim {
+///     max_move: 3,
+///     rng: Mutex::new(rand::SeedableRng::seed_from_u64(0)),
+/// };
+/// let tree = Tree::new(game, GetState, nim::Player::P1, 20);
+/// let last = search_with_progress(
+///     &tree,
+///     SearchBudget::Steps(200),
+///     2,
+///     ProgressInterval::Steps(50),
+///     |p| println!("info nodes {} nps {:.0} depth {}", p.n_nodes, p.steps_per_sec, p.pv.len()),
+/// );
+/// assert_eq!(last.n_steps, 200);
+/// ```
+pub fn search_with_progress<T, F>(
+    tree: &T,
+    budget: SearchBudget,
+    n_threads: usize,
+    interval: ProgressInterval,
+    mut callback: F,
+) -> ProgressSnapshot<<T::GD as GameDynamics>::Action, <T::GD as GameDynamics>::Score>
+where
+    T: ?Sized + SearchTree + Sync,
+    <T::GD as GameDynamics>::Player: Clone,
+    <T::GD as GameDynamics>::Score: Clone,
+    F: FnMut(&ProgressSnapshot<<T::GD as GameDynamics>::Action, <T::GD as GameDynamics>::Score>),
+{
+    assert!(n_threads > 0, "at least one thread is required");
+    assert!(
+        interval != ProgressInterval::Steps(0),
+        "the progress interval must not be zero steps"
+    );
+    let start = Instant::now();
+    let n_steps = AtomicUsize::new(0);
+    let mut last_reported = None;
+    run_workers(
+        tree,
+        budget,
+        n_threads,
+        &n_steps,
+        None,
+        Some(interval),
+        |n| {
+            callback(&ProgressSnapshot::capture(tree, n, start.elapsed()));
+            last_reported = Some(n);
+        },
+    );
+
+    let n = n_steps.into_inner();
+    let last = ProgressSnapshot::capture(tree, n, start.elapsed());
+    if last_reported != Some(n) {
+        callback(&last);
+    }
+    last
+}
+
+// Searches the root of `tree` for `budget` on `n_threads` threads and returns the number of steps
+// performed
+pub(crate) fn search<T>(tree: &T, budget: SearchBudget, n_threads: usize) -> usize
+where
+    T: ?Sized + SearchTree + Sync,
+{
+    let n_steps = AtomicUsize::new(0);
+    run_workers(tree, budget, n_threads, &n_steps, None, None, |_| {});
+    n_steps.into_inner()
+}
+
+// The longest a worker waits after a step that did not expand the tree, see `Backoff`
+const MAX_BACKOFF: Duration = Duration::from_millis(10);
+
+// The increasing time a worker waits between steps that do not expand the tree (used by
+// `run_workers` and `Ponder::run`): it starts at 50µs and doubles after each wait up to
+// `MAX_BACKOFF`, and is reset once a step expands the tree again
+pub(crate) struct Backoff(Duration);
+
+impl Backoff {
+    const INITIAL: Duration = Duration::from_micros(50);
+
+    pub(crate) fn new() -> Self {
+        Self(Self::INITIAL)
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.0 = Self::INITIAL;
+    }
+
+    // Returns the time to wait now and doubles the next one
+    pub(crate) fn next_wait(&mut self) -> Duration {
+        let wait = self.0;
+        self.0 = (wait * 2).min(MAX_BACKOFF);
+        wait
+    }
+}
+
+// Steps `tree` on `n_threads` threads until `budget` is used up or `cancelled` is set, counting the
+// steps in `n_steps`.  The workers stop early once the root is terminal; with a time budget, a
+// worker whose steps keep reaching terminal nodes (e.g. if the tree is fully expanded) sleeps for
+// an increasing time up to `MAX_BACKOFF` between steps instead of spinning.  Meanwhile, the
+// calling thread waits for the workers and calls `progress` with the number of steps so far at
+// every `interval` (never if `interval` is `None`); with a step interval, the workers wake the
+// calling thread whenever a report is due.  Panics once all workers have exited if a step
+// panicked.
+fn run_workers<T, F>(
+    tree: &T,
+    budget: SearchBudget,
+    n_threads: usize,
+    n_steps: &AtomicUsize,
+    cancelled: Option<&AtomicBool>,
+    interval: Option<ProgressInterval>,
+    mut progress: F,
+) where
+    T: ?Sized + SearchTree + Sync,
+    F: FnMut(usize),
+{
+    let start = Instant::now();
+    let (max_steps, deadline) = match budget {
+        SearchBudget::Steps(n) => (n, None),
+        SearchBudget::Time(d) => (usize::MAX, Some(start + d)),
+    };
+    let (every_steps, timeout) = match interval {
+        Some(ProgressInterval::Steps(n)) => (Some(n), None),
+        Some(ProgressInterval::Time(d)) => (None, Some(d)),
+        None => (None, None),
+    };
+    let n_active = Mutex::new(n_threads);
+    let cv = Condvar::new();
+    std::thread::scope(|s| {
+        for _ in 0..n_threads {
+            s.spawn(|| {
+                let _active = Active(&n_active, &cv);
+                let mut backoff = Backoff::new();
+                while !cancelled.into_iter().any(|c| c.load(Ordering::Relaxed))
+                    && deadline.into_iter().all(|d| Instant::now() < d)
+                {
+                    let n = n_steps.fetch_add(1, Ordering::Relaxed);
+                    if n >= max_steps {
+                        n_steps.fetch_sub(1, Ordering::Relaxed);
+                        break;
+                    }
+                    if tree.step().is_some() {
+                        backoff.reset();
+                    } else if let Status::Terminal = tree.best_action() {
+                        break;
+                    } else if let Some(deadline) = deadline {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        std::thread::sleep(backoff.next_wait().min(remaining));
+                    }
+                    if every_steps.into_iter().any(|e| (n + 1) % e == 0) {
+                        // lock so that the notification cannot be missed between the calling
+                        // thread checking `n_steps` and waiting
+                        let _lk = n_active.lock().unwrap();
+                        cv.notify_all();
+                    }
+                }
+            });
+        }
+
+        let mut next_report = every_steps.unwrap_or(0);
+        let mut n_active_lk = n_active.lock().unwrap();
+        while *n_active_lk > 0 {
+            let due = |n| every_steps.is_some() && n >= next_report;
+            if !due(n_steps.load(Ordering::Relaxed)) {
+                let timed_out = match timeout {
+                    Some(timeout) => {
+                        let (lk, result) = cv.wait_timeout(n_active_lk, timeout).unwrap();
+                        n_active_lk = lk;
+                        result.timed_out()
+                    }
+                    None => {
+                        n_active_lk = cv.wait(n_active_lk).unwrap();
+                        false
+                    }
+                };
+                if *n_active_lk == 0 {
+                    break;
+                }
+                if !due(n_steps.load(Ordering::Relaxed)) && !timed_out {
+                    continue;
+                }
+            }
+            drop(n_active_lk);
+            let n = n_steps.load(Ordering::Relaxed);
+            if let Some(e) = every_steps {
+                next_report = (n / e + 1) * e;
+            }
+            progress(n);
+            n_active_lk = n_active.lock().unwrap();
+        }
+    });
+}
+
+// Steps `tree` until its root has been expanded (e.g. if the budget was zero) so that an action
+// can be applied, and returns the best action at the root
+pub(crate) fn expand_root<T>(tree: &T) -> Status<<T::GD as GameDynamics>::Action>
+where
+    T: ?Sized + SearchTree,
+{
+    loop {
+        match tree.best_action() {
+            Status::Pending => {
+                tree.step();
+            }
+            status => return status,
+        }
+    }
+}
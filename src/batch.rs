@@ -0,0 +1,551 @@
+// A `Batcher` owns a single service thread.  Callers of `Batcher::evaluate` push their input and a
+// `Slot` onto a shared queue and then block on the `Slot` until the service thread has evaluated
+// the batch containing their input.  The service thread waits for the first input, then keeps
+// collecting inputs until either `max_batch_size` inputs are queued or `max_latency` has elapsed
+// since the first input of the batch arrived.
+
+use std::fmt::Debug;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// An evaluator that scores inputs in groups, e.g. a neural network that evaluates a batch of
+/// positions in a single forward pass.  Used by [`Batcher`].
+pub trait BatchEvaluator: Send + Sync + 'static {
+    /// The type of a single input, e.g. a `GameDynamics::State` or a tensor encoding it.
+    type Input: Send + 'static;
+    /// The type of the evaluation of a single input.
+    type Output: Send + 'static;
+
+    /// Evaluate all `inputs`.  The returned vector must have the same length as `inputs` and
+    /// `outputs[i]` must be the evaluation of `inputs[i]`.
+    fn evaluate(&self, inputs: Vec<Self::Input>) -> Vec<Self::Output>;
+}
+
+/// Thresholds that determine when a [`Batcher`] evaluates the queued inputs.
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    /// A batch is evaluated as soon as this many inputs are queued.
+    pub max_batch_size: usize,
+    /// A batch is evaluated once the first queued input has waited this long, even if the batch
+    /// is not full.
+    pub max_latency: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_size: 16,
+            max_latency: Duration::from_millis(1),
+        }
+    }
+}
+
+struct Slot<T> {
+    // `None` while pending, `Some(Err(()))` if the evaluator panicked
+    value: Mutex<Option<Result<T, ()>>>,
+    cv: Condvar,
+}
+
+impl<T> Slot<T> {
+    fn new() -> Self {
+        Self {
+            value: Mutex::new(None),
+            cv: Condvar::new(),
+        }
+    }
+
+    fn set(&self, value: Result<T, ()>) {
+        *self.value.lock().unwrap() = Some(value);
+        self.cv.notify_one();
+    }
+
+    fn wait(&self) -> Result<T, ()> {
+        let lk = self.value.lock().unwrap();
+        let mut lk = self.cv.wait_while(lk, |v| v.is_none()).unwrap();
+        lk.take().unwrap()
+    }
+}
+
+struct Queue<In, Out> {
+    pending: Vec<(In, Arc<Slot<Out>>)>,
+    first_arrival: Option<Instant>,
+    shutdown: bool,
+}
+
+struct Shared<E: BatchEvaluator> {
+    evaluator: E,
+    config: BatchConfig,
+    queue: Mutex<Queue<E::Input, E::Output>>,
+    cv: Condvar,
+    n_batches: AtomicUsize,
+    n_inputs: AtomicUsize,
+}
+
+/// A service that collects inputs submitted concurrently by the threads expanding a
+/// [`Tree`](crate::Tree) and evaluates them in batches using a [`BatchEvaluator`].
+///
+/// A `Batcher` is intended to be stored in a type implementing
+/// [`GameDynamics`](crate::GameDynamics) and called from
+/// [`GameDynamics::score_leaf`](crate::GameDynamics::score_leaf) and
+/// [`GameDynamics::score_leaves`](crate::GameDynamics::score_leaves); each thread calling
+/// [`Batcher::evaluate`] or [`Batcher::evaluate_all`] blocks until the batches containing its
+/// inputs have been evaluated.  With `score_leaf` alone, a thread only submits one leaf at a time,
+/// so batches are only filled if the `Tree` is expanded by at least `max_batch_size` threads and
+/// are otherwise evaluated once [`BatchConfig::max_latency`] has elapsed.  A `Tree` configured
+/// with [`Tree::with_leaf_batch_size`](crate::Tree::with_leaf_batch_size) submits up to that many
+/// children of a leaf through `score_leaves` at once, which fills batches even when searching
+/// with a single thread.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use std::sync::Arc;
+///
+/// struct Doubler;
+///
+/// impl BatchEvaluator for Doubler {
+///     type Input = u32;
+///     type Output = u32;
+///     fn evaluate(&self, inputs: Vec<u32>) -> Vec<u32> {
+///         inputs.into_iter().map(|x| 2 * x).collect()
+///     }
+/// }
+///
+/// let batcher = Arc::new(Batcher::new(Doubler, BatchConfig::default()));
+/// let handles = (0..4)
+///     .map(|x| {
+///         let batcher = Arc::clone(&batcher);
+///         std::thread::spawn(move || batcher.evaluate(x))
+///     })
+///     .collect::<Vec<_>>();
+/// let out = handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>();
+/// assert_eq!(out, vec![0, 2, 4, 6]);
+/// ```
+pub struct Batcher<E: BatchEvaluator> {
+    shared: Arc<Shared<E>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<E: BatchEvaluator> Batcher<E> {
+    /// Construct a new `Batcher` and start its service thread.
+    pub fn new(evaluator: E, config: BatchConfig) -> Self {
+        assert!(
+            config.max_batch_size > 0,
+            "`max_batch_size` must be positive"
+        );
+        let shared = Arc::new(Shared {
+            evaluator,
+            config,
+            queue: Mutex::new(Queue {
+                pending: Vec::new(),
+                first_arrival: None,
+                shutdown: false,
+            }),
+            cv: Condvar::new(),
+            n_batches: AtomicUsize::new(0),
+            n_inputs: AtomicUsize::new(0),
+        });
+        let worker = std::thread::spawn({
+            let shared = Arc::clone(&shared);
+            move || Self::run(&shared)
+        });
+        Self {
+            shared,
+            worker: Some(worker),
+        }
+    }
+
+    /// Submit `input` for evaluation and block until the result is available.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`BatchEvaluator::evaluate`] panicked while evaluating the batch containing
+    /// `input` or returned the wrong number of outputs.
+    pub fn evaluate(&self, input: E::Input) -> E::Output {
+        let slot = Arc::new(Slot::new());
+        {
+            let mut q = self.shared.queue.lock().unwrap();
+            if q.pending.is_empty() {
+                q.first_arrival = Some(Instant::now());
+            }
+            q.pending.push((input, Arc::clone(&slot)));
+        }
+        self.shared.cv.notify_one();
+        slot.wait().expect("batch evaluation failed")
+    }
+
+    /// Submit all `inputs` for evaluation and block until all results are available.  The inputs
+    /// are queued together, so they end up in as few batches as `max_batch_size` allows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`BatchEvaluator::evaluate`] panicked while evaluating a batch containing one
+    /// of `inputs` or returned the wrong number of outputs.
+    pub fn evaluate_all(&self, inputs: Vec<E::Input>) -> Vec<E::Output> {
+        let slots = inputs
+            .iter()
+            .map(|_| Arc::new(Slot::new()))
+            .collect::<Vec<_>>();
+        if slots.is_empty() {
+            return Vec::new();
+        }
+        {
+            let mut q = self.shared.queue.lock().unwrap();
+            if q.pending.is_empty() {
+                q.first_arrival = Some(Instant::now());
+            }
+            q.pending
+                .extend(inputs.into_iter().zip(slots.iter().cloned()));
+        }
+        self.shared.cv.notify_one();
+        slots
+            .iter()
+            .map(|slot| slot.wait().expect("batch evaluation failed"))
+            .collect()
+    }
+
+    /// Returns the number of batches evaluated so far.
+    pub fn n_batches(&self) -> usize {
+        self.shared.n_batches.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of inputs evaluated so far.
+    pub fn n_inputs(&self) -> usize {
+        self.shared.n_inputs.load(Ordering::Relaxed)
+    }
+
+    fn run(shared: &Shared<E>) {
+        let BatchConfig {
+            max_batch_size,
+            max_latency,
+        } = shared.config;
+
+        loop {
+            let q = shared.queue.lock().unwrap();
+            let mut q = shared
+                .cv
+                .wait_while(q, |q| q.pending.is_empty() && !q.shutdown)
+                .unwrap();
+            if q.pending.is_empty() {
+                // shutting down and nothing left to evaluate
+                return;
+            }
+
+            let deadline = q.first_arrival.expect("queue is not empty") + max_latency;
+            while q.pending.len() < max_batch_size && !q.shutdown {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                q = shared.cv.wait_timeout(q, deadline - now).unwrap().0;
+            }
+
+            let n = std::cmp::min(q.pending.len(), max_batch_size);
+            let batch = q.pending.drain(..n).collect::<Vec<_>>();
+            // inputs left over from a full batch start a new latency window
+            q.first_arrival = if q.pending.is_empty() {
+                None
+            } else {
+                Some(Instant::now())
+            };
+            drop(q);
+
+            let (inputs, slots): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+            let outputs =
+                panic::catch_unwind(AssertUnwindSafe(|| shared.evaluator.evaluate(inputs)));
+            shared.n_batches.fetch_add(1, Ordering::Relaxed);
+            shared.n_inputs.fetch_add(slots.len(), Ordering::Relaxed);
+
+            match outputs {
+                Ok(outputs) if outputs.len() == slots.len() => {
+                    slots
+                        .iter()
+                        .zip(outputs)
+                        .for_each(|(slot, out)| slot.set(Ok(out)));
+                }
+                _ => slots.iter().for_each(|slot| slot.set(Err(()))),
+            }
+        }
+    }
+}
+
+impl<E: BatchEvaluator> Drop for Batcher<E> {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().shutdown = true;
+        self.shared.cv.notify_one();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<E: BatchEvaluator> Debug for Batcher<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Batcher")
+            .field("config", &self.shared.config)
+            .field("n_batches", &self.n_batches())
+            .field("n_inputs", &self.n_inputs())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_dynamics::{EdgeStats, GameDynamics, SelectNodeState};
+    use crate::tree::state_memory::GetState;
+    use crate::tree::{SearchTree, Tree};
+
+    use std::ops::Deref;
+
+    // deterministic stand-in for a neural network: records the size of each batch it receives
+    struct Recorder(Mutex<Vec<usize>>);
+
+    impl BatchEvaluator for Recorder {
+        type Input = u64;
+        type Output = u64;
+        fn evaluate(&self, inputs: Vec<u64>) -> Vec<u64> {
+            assert!(!inputs.contains(&13), "unlucky input");
+            self.0.lock().unwrap().push(inputs.len());
+            inputs.into_iter().map(|x| x * x).collect()
+        }
+    }
+
+    #[test]
+    fn test_full_batches() {
+        let config = BatchConfig {
+            max_batch_size: 4,
+            max_latency: Duration::from_secs(60),
+        };
+        let batcher = Arc::new(Batcher::new(Recorder(Mutex::new(Vec::new())), config));
+        let handles = (0..8)
+            .map(|x| {
+                let batcher = Arc::clone(&batcher);
+                std::thread::spawn(move || (x, batcher.evaluate(x)))
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            let (x, y) = h.join().unwrap();
+            assert_eq!(x * x, y);
+        }
+        assert_eq!(*batcher.shared.evaluator.0.lock().unwrap(), vec![4, 4]);
+        assert_eq!(batcher.n_inputs(), 8);
+    }
+
+    #[test]
+    fn test_evaluate_all() {
+        let config = BatchConfig {
+            max_batch_size: 4,
+            max_latency: Duration::from_secs(60),
+        };
+        let batcher = Batcher::new(Recorder(Mutex::new(Vec::new())), config);
+        assert_eq!(
+            batcher.evaluate_all((0..8).collect()),
+            (0..8).map(|x| x * x).collect::<Vec<_>>()
+        );
+        assert!(batcher.evaluate_all(Vec::new()).is_empty());
+        assert_eq!(*batcher.shared.evaluator.0.lock().unwrap(), vec![4, 4]);
+    }
+
+    #[test]
+    fn test_latency() {
+        let config = BatchConfig {
+            max_batch_size: 100,
+            max_latency: Duration::from_millis(5),
+        };
+        let batcher = Batcher::new(Recorder(Mutex::new(Vec::new())), config);
+        assert_eq!(batcher.evaluate(3), 9);
+        assert_eq!(batcher.evaluate(4), 16);
+        assert_eq!(batcher.n_batches(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "batch evaluation failed")]
+    fn test_evaluator_panic() {
+        let batcher = Batcher::new(Recorder(Mutex::new(Vec::new())), BatchConfig::default());
+        batcher.evaluate(13);
+    }
+
+    // a one player game on a tree of depth three where every node has four children; the leaves
+    // are scored by a `Batcher` (the state `s` is scored `(s + 100)^2`) and the score of a branch
+    // is the sum of the scores of its children
+    struct Squares(Batcher<Recorder>);
+
+    impl GameDynamics for Squares {
+        type Player = ();
+        type State = u64;
+        type Action = u64;
+        type Score = u64;
+        type ActionIter = Vec<((), u64)>;
+
+        fn available_actions(&self, _: &(), state: &u64) -> Option<Self::ActionIter> {
+            if *state < 21 {
+                Some((1..=4).map(|a| ((), a)).collect())
+            } else {
+                None
+            }
+        }
+
+        fn apply_action(&self, state: u64, action: &u64) -> Option<u64> {
+            Some(4 * state + action)
+        }
+
+        fn select_node<II, Q, A>(
+            &self,
+            _: Option<&u64>,
+            _: &(),
+            _: &u64,
+            _: SelectNodeState,
+            scores_and_actions: II,
+        ) -> u64
+        where
+            II: Clone + IntoIterator<Item = (Q, A)>,
+            Q: Deref<Target = Option<u64>>,
+            A: Deref<Target = u64>,
+        {
+            *scores_and_actions.into_iter().next().unwrap().1
+        }
+
+        // explores the least visited child so that the tree is eventually expanded completely
+        fn select_node_with_edges<II, Q, A>(
+            &self,
+            _: Option<&u64>,
+            _: &(),
+            _: &u64,
+            _: SelectNodeState,
+            scores_actions_and_edges: II,
+        ) -> u64
+        where
+            II: Clone + IntoIterator<Item = (Q, A, EdgeStats)>,
+            Q: Deref<Target = Option<u64>>,
+            A: Deref<Target = u64>,
+        {
+            let children = scores_actions_and_edges.into_iter();
+            children.map(|(_, a, e)| (e.visits, *a)).min().unwrap().1
+        }
+
+        fn backprop_scores<II, Q>(&self, _: &(), _: Option<&u64>, child_scores: II) -> Option<u64>
+        where
+            II: Clone + IntoIterator<Item = Q>,
+            Q: Deref<Target = u64>,
+        {
+            Some(child_scores.into_iter().map(|q| *q).sum())
+        }
+
+        fn score_leaf(&self, _: Option<&u64>, _: &(), state: &u64) -> Option<u64> {
+            // `Recorder` rejects the input 13
+            Some(self.0.evaluate(*state + 100))
+        }
+
+        fn score_leaves(&self, _: Option<&u64>, _: &(), states: &[&u64]) -> Vec<Option<u64>> {
+            let inputs = states.iter().map(|&state| *state + 100).collect();
+            self.0.evaluate_all(inputs).into_iter().map(Some).collect()
+        }
+    }
+
+    // expands the complete tree from `n_threads` threads and returns the sizes of the batches
+    fn search_squares(config: BatchConfig, n_threads: usize, leaf_batch_size: usize) -> Vec<usize> {
+        let game = Squares(Batcher::new(Recorder(Mutex::new(Vec::new())), config));
+        let tree = Tree::new(game, GetState, (), 0).with_leaf_batch_size(leaf_batch_size);
+        std::thread::scope(|s| {
+            for _ in 0..n_threads {
+                s.spawn(|| {
+                    for _ in 0..160 / n_threads {
+                        tree.step();
+                    }
+                });
+            }
+        });
+
+        let mut stack = vec![tree.root_cursor()];
+        while let Some(cursor) = stack.pop() {
+            let children = cursor.children();
+            let state = cursor.state().unwrap();
+            if state >= 21 {
+                assert_eq!(cursor.info().unwrap().score, Some((state + 100).pow(2)));
+            } else {
+                assert_eq!(children.len(), 4);
+            }
+            stack.extend(children.into_iter().map(|(_, _, c)| c));
+        }
+        assert_eq!(
+            tree.get_root_info().score,
+            Some((121..185).map(|s| s * s).sum())
+        );
+
+        let game = tree.get_game_dynamics();
+        let batcher = &game.0;
+        let sizes = batcher.shared.evaluator.0.lock().unwrap().clone();
+        // every node but the root is scored exactly once
+        assert_eq!(batcher.n_inputs(), 84);
+        assert_eq!(sizes.iter().sum::<usize>(), 84);
+        assert_eq!(batcher.n_batches(), sizes.len());
+        sizes
+    }
+
+    #[test]
+    fn test_tree_full_batches() {
+        // with as many stepping threads as inputs per batch, batches are filled by the threads
+        // that concurrently create the children of a branch
+        let sizes = search_squares(
+            BatchConfig {
+                max_batch_size: 4,
+                max_latency: Duration::from_millis(50),
+            },
+            4,
+            1,
+        );
+        assert!(sizes.iter().all(|&n| n <= 4));
+        assert!(sizes.contains(&4));
+    }
+
+    #[test]
+    fn test_tree_latency() {
+        // batches can never be filled by four threads, so every batch is flushed by the timeout,
+        // i.e. no batch holds more inputs than there are threads
+        let sizes = search_squares(
+            BatchConfig {
+                max_batch_size: 64,
+                max_latency: Duration::from_millis(2),
+            },
+            4,
+            1,
+        );
+        assert!(!sizes.is_empty());
+        assert!(sizes.iter().all(|&n| (1..=4).contains(&n)));
+        // every node but the root has been scored via one of the flushed batches
+        assert_eq!(sizes.iter().sum::<usize>(), 84);
+    }
+
+    #[test]
+    fn test_tree_leaf_batches() {
+        // a single thread submits all four children of a leaf at once, so every batch is full
+        // and no batch waits for the (very long) latency window
+        let t0 = Instant::now();
+        let sizes = search_squares(
+            BatchConfig {
+                max_batch_size: 4,
+                max_latency: Duration::from_secs(60),
+            },
+            1,
+            4,
+        );
+        assert_eq!(sizes, vec![4; 21]);
+        assert!(t0.elapsed() < Duration::from_secs(30));
+
+        // threads that join the expansion of a leaf claim the remaining children
+        let sizes = search_squares(
+            BatchConfig {
+                max_batch_size: 4,
+                max_latency: Duration::from_millis(2),
+            },
+            4,
+            2,
+        );
+        assert!(sizes.iter().all(|&n| n <= 4));
+    }
+}
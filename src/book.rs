@@ -0,0 +1,484 @@
+// An opening book maps positions to the statistics of the actions searched from them.  Positions
+// are identified by the same hash of the player and state that `Node`s use (see `book_key`), so a
+// book works for any game whose players and states implement `Hash` and can be stored without the
+// states themselves.  Books are created by merging the statistics of saved search trees (or of
+// `SearchReport`s, e.g. received from root parallel workers) and are consulted at the root only:
+// either to choose the action directly while the root is in book or to seed the priors of the
+// root's edges before searching.  A hash collision can map a position to the moves of another
+// position; actions that are not available at the root are ignored when the book is applied.
+
+use crate::async_search::expand_root;
+use crate::game_dynamics::GameDynamics;
+use crate::root_parallel::SearchReport;
+use crate::tree::state_memory::StateMemory;
+use crate::tree::{hash_of, Node, SearchTree, Status, TreeAlias};
+
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+/// Returns the key of the position with `player` to move in `state`, i.e. the hash used by
+/// [`Node`]s and [`SearchReport::root_hash`].  Keys are computed with the standard library's
+/// `DefaultHasher`, so they may differ between Rust releases (see [`OpeningBook`]).
+pub fn book_key<P: Hash, S: Hash>(player: &P, state: &S) -> u64 {
+    hash_of(&(player, state))
+}
+
+// Bumped whenever `book_key` changes
+const KEY_VERSION: u32 = 1;
+
+// The tag stored by an `OpeningBook`: a fingerprint of `book_key` that also changes if the
+// `DefaultHasher` of the toolchain changes
+fn key_format() -> u64 {
+    book_key(&KEY_VERSION, &"recon_mcts::OpeningBook")
+}
+
+/// The statistics of an action in an [`OpeningBook`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookMove<A, Q> {
+    /// The action.
+    pub action: A,
+    /// The number of times the edge of the action was traversed, summed over the trees the book
+    /// was built from.
+    pub visits: usize,
+    /// The score of the child reached by the action.
+    pub score: Option<Q>,
+}
+
+/// A precomputed map from positions (see [`book_key`]) to the statistics of the actions available
+/// in them, created with a [`BookBuilder`].  Books implement `Serialize` and `Deserialize` so they
+/// can be computed once and loaded before playing.
+///
+/// The keys depend on the standard library's `DefaultHasher` (and on the `Hash` implementations
+/// of the players and states), whose algorithm is not specified and may change between Rust
+/// releases; a book saved by a binary built with a different toolchain may then find no (or,
+/// rarely, the wrong) positions.  Books store a tag identifying how their keys were computed:
+/// check [`OpeningBook::is_compatible`] after loading a book and rebuild it if it is `false`.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use std::sync::Mutex;
+///
+/// let make_tree = || {
+///     let game = nim::Nim {
+///         max_move: 3,
+///         rng: Mutex::new(rand::SeedableRng::seed_from_u64(0)),
+///     };
+///     Tree::new(game, GetState, nim::Player::P1, 10)
+/// };
+///
+/// let tree = make_tree();
+/// for _ in 0..500 {
+///     tree.step();
+/// }
+/// let book = BookBuilder::new().with_max_depth(2).add_tree(&tree).build();
+/// let book: OpeningBook<usize, nim::Score> =
+///     serde_json::from_str(&serde_json::to_string(&book).unwrap()).unwrap();
+///
+/// // a fresh tree plays from the book without searching
+/// let tree = make_tree();
+/// assert!(matches!(book.apply_best_action(&tree), Status::Action(2)));
+/// assert_eq!(tree.get_root_info().state, Some(8));
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpeningBook<A, Q> {
+    // see `key_format`; books saved without a tag are not compatible
+    #[serde(default)]
+    key_format: u64,
+    // the moves of each position, sorted by decreasing visits
+    positions: HashMap<u64, Vec<BookMove<A, Q>>>,
+}
+
+impl<A, Q> Default for OpeningBook<A, Q> {
+    fn default() -> Self {
+        Self {
+            key_format: key_format(),
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl<A, Q> OpeningBook<A, Q> {
+    /// Returns `true` if the keys of the book were computed the same way as [`book_key`] computes
+    /// them in this binary, i.e. if the positions of the book can be probed.
+    pub fn is_compatible(&self) -> bool {
+        self.key_format == key_format()
+    }
+
+    /// Returns the number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns `true` if the book contains no positions.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Returns the moves of the position with the given [`book_key`], sorted by decreasing
+    /// visits.
+    pub fn probe_key(&self, key: u64) -> Option<&[BookMove<A, Q>]> {
+        self.positions.get(&key).map(|moves| moves.as_slice())
+    }
+
+    /// Returns the moves of the position with `player` to move in `state`, sorted by decreasing
+    /// visits.
+    pub fn probe<P: Hash, S: Hash>(&self, player: &P, state: &S) -> Option<&[BookMove<A, Q>]> {
+        self.probe_key(book_key(player, state))
+    }
+
+    // Returns the moves of the `tree`'s root
+    fn probe_root<T>(&self, tree: &T) -> Option<&[BookMove<A, Q>]>
+    where
+        T: ?Sized + SearchTree,
+        <T::GD as GameDynamics>::Player: Hash + Clone,
+        <T::GD as GameDynamics>::State: Hash,
+        <T::GD as GameDynamics>::Score: Clone,
+    {
+        let root = tree.get_root_info();
+        let state = root.state.expect("the root has a state");
+        self.probe(&root.player, &state)
+    }
+
+    /// Returns the most visited book move of the `tree`'s root if the root is in book and
+    /// [`SearchTree::best_action`] otherwise.  The book move is not checked against the actions
+    /// available at the root (which may not have been created yet); see
+    /// [`OpeningBook::apply_best_action`].
+    pub fn best_action<T>(&self, tree: &T) -> Status<A>
+    where
+        T: ?Sized + SearchTree,
+        T::GD: GameDynamics<Action = A>,
+        <T::GD as GameDynamics>::Player: Hash + Clone,
+        <T::GD as GameDynamics>::State: Hash,
+        <T::GD as GameDynamics>::Score: Clone,
+        A: Clone,
+    {
+        match self.probe_root(tree).and_then(|moves| moves.first()) {
+            Some(m) => Status::Action(m.action.clone()),
+            None => tree.best_action(),
+        }
+    }
+
+    /// Applies the most visited book move that is available at the `tree`'s root and returns it.
+    /// The root is expanded first if necessary.  Falls back to [`SearchTree::apply_best_action`]
+    /// if the root is not in book or none of its book moves are available.
+    pub fn apply_best_action<T>(&self, tree: &T) -> Status<A>
+    where
+        T: ?Sized + SearchTree,
+        T::GD: GameDynamics<Action = A>,
+        <T::GD as GameDynamics>::Player: Hash + Clone,
+        <T::GD as GameDynamics>::State: Hash,
+        <T::GD as GameDynamics>::Score: Clone,
+        A: Clone + PartialEq,
+    {
+        let moves = match self.probe_root(tree) {
+            Some(moves) => moves,
+            None => return tree.apply_best_action(),
+        };
+        let edges = loop {
+            if let Status::Terminal = expand_root(tree) {
+                return Status::Terminal;
+            }
+            match tree.get_next_move_edges() {
+                Some(edges) => break edges,
+                // another thread is creating the root's children
+                None => {
+                    tree.step();
+                }
+            }
+        };
+        match moves
+            .iter()
+            .find(|m| edges.iter().any(|(a, _)| *a == m.action))
+        {
+            Some(m) => {
+                tree.apply_action(&m.action);
+                Status::Action(m.action.clone())
+            }
+            None => tree.apply_best_action(),
+        }
+    }
+
+    /// Mixes the visit distribution of the book moves into the priors of the edges from the
+    /// `tree`'s root to its children, i.e. each prior `p` is replaced by
+    /// `(1 - weight) * p + weight * n / total` where `n` is the number of visits of the edge's
+    /// action in the book and `total` the sum over the actions available at the root.  Edges
+    /// without a prior are treated as having a uniform prior (see
+    /// [`SearchTree::set_root_priors`]).
+    ///
+    /// Returns `false` (and leaves the priors unchanged) if the root is not in book, none of its
+    /// book moves have been visited or not all of the root's children have been created yet.
+    pub fn seed_priors<T>(&self, tree: &T, weight: f64) -> bool
+    where
+        T: ?Sized + SearchTree,
+        T::GD: GameDynamics<Action = A>,
+        <T::GD as GameDynamics>::Player: Hash + Clone,
+        <T::GD as GameDynamics>::State: Hash,
+        <T::GD as GameDynamics>::Score: Clone,
+        A: Clone + PartialEq,
+    {
+        let (moves, edges) = match (self.probe_root(tree), tree.get_next_move_edges()) {
+            (Some(moves), Some(edges)) => (moves, edges),
+            _ => return false,
+        };
+        let book_visits = |a: &A| {
+            moves
+                .iter()
+                .find(|m| m.action == *a)
+                .map_or(0, |m| m.visits)
+        };
+        let total = edges.iter().map(|(a, _)| book_visits(a)).sum::<usize>();
+        if total == 0 {
+            return false;
+        }
+
+        let n = edges.len() as f64;
+        let priors = edges
+            .iter()
+            .map(|(a, e)| {
+                let p = e.prior.unwrap_or(1.0 / n);
+                let q = book_visits(a) as f64 / total as f64;
+                (a.clone(), (1.0 - weight) * p + weight * q)
+            })
+            .collect::<Vec<_>>();
+        tree.set_root_priors(&priors)
+    }
+}
+
+/// Creates an [`OpeningBook`] by merging the statistics of search trees: the visits of an action
+/// in a position are summed over all trees containing the position and the score is taken from
+/// the most recently added tree that has one.
+#[derive(Debug, Clone)]
+pub struct BookBuilder<A, Q> {
+    max_depth: usize,
+    min_visits: usize,
+    positions: HashMap<u64, Vec<BookMove<A, Q>>>,
+}
+
+impl<A, Q> Default for BookBuilder<A, Q> {
+    fn default() -> Self {
+        Self {
+            max_depth: 1,
+            min_visits: 1,
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl<A, Q> BookBuilder<A, Q>
+where
+    A: PartialEq,
+{
+    /// Construct a new `BookBuilder` that only records the root of each tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the `Node`s whose smallest number of actions from the root of a tree added via
+    /// [`BookBuilder::add_tree`] is smaller than `max_depth`.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Only records the actions of `Node`s that were visited at least `min_visits` times, and only
+    /// the actions whose edges were visited at least `min_visits` times.
+    pub fn with_min_visits(mut self, min_visits: usize) -> Self {
+        self.min_visits = min_visits;
+        self
+    }
+
+    fn insert(&mut self, key: u64, action: A, visits: usize, score: Option<Q>) {
+        if visits < self.min_visits {
+            return;
+        }
+        let moves = self.positions.entry(key).or_default();
+        match moves.iter_mut().find(|m| m.action == action) {
+            Some(m) => {
+                m.visits += visits;
+                if score.is_some() {
+                    m.score = score;
+                }
+            }
+            None => moves.push(BookMove {
+                action,
+                visits,
+                score,
+            }),
+        }
+    }
+
+    /// Adds the root of a [`SearchReport`] (e.g. a report saved by a root parallel search, see
+    /// [`search_root_parallel`](crate::search_root_parallel)).
+    pub fn add_report(mut self, report: &SearchReport<A, Q>) -> Self
+    where
+        A: Clone,
+        Q: Clone,
+    {
+        if report.children.iter().map(|c| c.visits).sum::<usize>() < self.min_visits {
+            return self;
+        }
+        for c in report.children.iter() {
+            self.insert(
+                report.root_hash,
+                c.action.clone(),
+                c.visits,
+                c.score.clone(),
+            );
+        }
+        self
+    }
+
+    /// Adds the `Node`s of `tree` up to the maximum depth (see [`BookBuilder::with_max_depth`]).
+    /// `Node`s reachable via several paths are added once.
+    pub fn add_tree<GD, S, P, I, M, II>(mut self, tree: &TreeAlias<GD, M>) -> Self
+    where
+        Node<GD, S, P, A, Q, I, M>: StateMemory<State = S>,
+        GD: GameDynamics<Player = P, State = S, Action = A, Score = Q, ActionIter = II>,
+        II: IntoIterator<IntoIter = I, Item = (P, A)>,
+        I: Iterator<Item = (P, A)>,
+        A: Clone + Hash + Eq,
+        S: Clone + Hash + PartialEq<S>,
+        P: Clone + Hash + PartialEq<P>,
+        Q: Clone,
+    {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back((tree.root_cursor(), 0));
+        while let Some((cursor, depth)) = queue.pop_front() {
+            if depth >= self.max_depth || cursor.visits() < self.min_visits {
+                continue;
+            }
+            let (info, state) = match (cursor.info(), cursor.state()) {
+                (Some(info), Some(state)) => (info, state),
+                // the node was pruned by another thread
+                _ => continue,
+            };
+            let key = book_key(&info.player, &state);
+            if !seen.insert(key) {
+                continue;
+            }
+            for (a, e, child) in cursor.children() {
+                let score = child.info().and_then(|info| info.score);
+                self.insert(key, a, e.visits, score);
+                queue.push_back((child, depth + 1));
+            }
+        }
+        self
+    }
+
+    /// Returns the book.
+    pub fn build(mut self) -> OpeningBook<A, Q> {
+        self.positions.retain(|_, moves| !moves.is_empty());
+        for moves in self.positions.values_mut() {
+            moves.sort_by_key(|m| Reverse(m.visits));
+        }
+        OpeningBook {
+            key_format: key_format(),
+            positions: self.positions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nim::{Nim, Player};
+    use crate::tree::state_memory::GetState;
+    use crate::tree::Tree;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use std::sync::Mutex;
+
+    fn make_tree(player: Player, state: usize) -> TreeAlias<Nim, GetState> {
+        let game = Nim {
+            max_move: 3,
+            rng: Mutex::new(StdRng::seed_from_u64(0)),
+        };
+        Tree::new(game, GetState, player, state)
+    }
+
+    #[test]
+    fn test_book() {
+        let tree = make_tree(Player::P1, 10);
+        for _ in 0..300 {
+            tree.step();
+        }
+        let report = tree.search_report(false);
+        assert_eq!(report.root_hash, book_key(&Player::P1, &10usize));
+
+        let book = BookBuilder::new()
+            .with_max_depth(3)
+            .with_min_visits(2)
+            .add_tree(&tree)
+            .build();
+        assert!(book.len() > 1);
+        assert!(book.is_compatible());
+        let moves = book.probe(&Player::P1, &10usize).unwrap();
+        assert_eq!(moves.len(), 3);
+        assert!(moves.windows(2).all(|m| m[0].visits >= m[1].visits));
+        let total = report.children.iter().map(|c| c.visits).sum::<usize>();
+        assert_eq!(moves.iter().map(|m| m.visits).sum::<usize>(), total);
+        assert!(book.probe(&Player::P2, &7usize).is_some());
+        assert!(book.probe(&Player::P2, &10usize).is_none());
+        assert!(moves.iter().all(|m| m.visits >= 2 && m.score.is_some()));
+
+        // reports are merged into the same positions
+        let book = BookBuilder::new()
+            .add_report(&report)
+            .add_report(&report)
+            .build();
+        assert_eq!(book.len(), 1);
+        let merged = book.probe_key(report.root_hash).unwrap();
+        assert_eq!(merged.iter().map(|m| m.visits).sum::<usize>(), 2 * total);
+        assert_eq!(merged[0].action, moves[0].action);
+
+        // out of book
+        let tree = make_tree(Player::P1, 9);
+        assert!(matches!(book.best_action(&tree), Status::Pending));
+        assert!(!book.seed_priors(&tree, 0.5));
+
+        let tree = make_tree(Player::P1, 10);
+        assert!(matches!(book.best_action(&tree), Status::Action(2)));
+        assert!(!book.seed_priors(&tree, 0.5));
+        tree.step();
+        assert!(book.seed_priors(&tree, 1.0));
+        for (a, e) in tree.get_next_move_edges().unwrap() {
+            let m = merged.iter().find(|m| m.action == a).unwrap();
+            let expected = m.visits as f64 / (2 * total) as f64;
+            assert!((e.prior.unwrap() - expected).abs() < 1e-12);
+        }
+        assert!(matches!(book.apply_best_action(&tree), Status::Action(2)));
+        assert_eq!(tree.get_root_info().state, Some(8));
+        // the new root is not in book and has not been expanded
+        assert!(matches!(book.apply_best_action(&tree), Status::Pending));
+    }
+
+    #[test]
+    fn test_key_format() {
+        let book = BookBuilder::<usize, f64>::new().build();
+        let json = serde_json::to_value(&book).unwrap();
+        let loaded: OpeningBook<usize, f64> = serde_json::from_value(json.clone()).unwrap();
+        assert!(loaded.is_compatible());
+        assert_eq!(loaded, book);
+
+        // a book saved by a binary with another hasher (or without a tag)
+        for tag in &[Some(serde_json::json!(key_format() ^ 1)), None] {
+            let mut json = json.clone();
+            match tag {
+                Some(tag) => json["key_format"] = tag.clone(),
+                None => {
+                    json.as_object_mut().unwrap().remove("key_format");
+                }
+            }
+            let loaded: OpeningBook<usize, f64> = serde_json::from_value(json).unwrap();
+            assert!(!loaded.is_compatible());
+        }
+    }
+}
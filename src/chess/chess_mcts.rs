@@ -14,15 +14,16 @@ use rand::rngs::StdRng;
 use rand::Rng;
 
 use rand::SeedableRng;
+use serde::Serialize;
 use std::sync::Arc;
 
-#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Clone, Debug, Hash, PartialEq, Serialize)]
 pub enum Player {
     White,
     Black,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Score {
     player1: f64,
     player2: f64,
@@ -0,0 +1,390 @@
+// `SearchTree` can only be used as a trait object if its associated types are named, which fixes
+// the game.  `DynTree` erases the game instead: players, states, actions and scores are converted
+// to and from JSON values at the boundary, so trees of different games can be stored side by side
+// (e.g. as `Box<dyn DynTree>` in a server that selects the game at runtime).  `ErasedTree`
+// implements `DynTree` for any `SearchTree` whose associated types implement `Serialize` (and
+// `Deserialize` for actions).
+
+use crate::async_search::expand_root;
+use crate::game_dynamics::GameDynamics;
+use crate::root_parallel::{ChildReport, NodeReport, SearchReport};
+use crate::stats::RegistryInfo;
+use crate::tree::{NodeInfo, SearchTree, Status};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use std::fmt;
+
+/// A [`NodeInfo`] with the state, player and score encoded as JSON values.
+pub type DynNodeInfo = NodeInfo<Value, Value, Value>;
+
+/// The error returned by [`DynTree::apply_action`].
+#[derive(Debug)]
+pub enum DynActionError {
+    /// The value could not be decoded as an action of the game.
+    Decode(serde_json::Error),
+    /// The action is not available at the root (or the root is terminal).
+    Illegal(Value),
+}
+
+impl fmt::Display for DynActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DynActionError::Decode(e) => write!(f, "invalid action: {}", e),
+            DynActionError::Illegal(action) => write!(f, "illegal action: {}", action),
+        }
+    }
+}
+
+impl std::error::Error for DynActionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DynActionError::Decode(e) => Some(e),
+            DynActionError::Illegal(_) => None,
+        }
+    }
+}
+
+/// An object-safe interface to a [`SearchTree`] of any game, with players, states, actions and
+/// scores encoded as JSON values (see [`ErasedTree`]).
+///
+/// # Panics
+///
+/// The methods panic if a player, state or score of the game cannot be encoded as JSON (e.g. a
+/// map with keys that are not strings).
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use std::collections::HashMap;
+/// use std::sync::Mutex;
+///
+/// // a server could select the game by name
+/// let mut games: HashMap<&str, fn() -> Box<dyn DynTree>> = HashMap::new();
+/// games.insert("nim", || {
+///     let game = nim::Nim {
+///         max_move: 3,
+///         rng: Mutex::new(rand::SeedableRng::seed_from_u64(0)),
+///     };
+///     ErasedTree::boxed(Tree::new(game, GetState, nim::Player::P1, 10))
+/// });
+///
+/// let tree = games["nim"]();
+/// for _ in 0..100 {
+///     tree.step();
+/// }
+/// assert!(tree.apply_action(&serde_json::json!(5)).is_err());
+/// tree.apply_action(&serde_json::json!(2)).unwrap();
+/// assert_eq!(tree.root_info().state, Some(serde_json::json!(8)));
+/// assert_eq!(tree.root_info().player, serde_json::json!("P2"));
+/// ```
+pub trait DynTree: Send + Sync {
+    /// Expands the tree (see [`SearchTree::step`]).  Returns `false` if no progress was made.
+    fn step(&self) -> bool;
+
+    /// See [`SearchTree::best_action`].
+    fn best_action(&self) -> Status<Value>;
+
+    /// Moves the root based on the action (see [`SearchTree::apply_action`]).  The root is
+    /// expanded first if necessary so that the action can be validated.
+    fn apply_action(&self, action: &Value) -> Result<(), DynActionError>;
+
+    /// See [`SearchTree::apply_best_action`].
+    fn apply_best_action(&self) -> Status<Value>;
+
+    /// See [`SearchTree::undo`].
+    fn undo(&self) -> bool;
+
+    /// See [`SearchTree::history_len`].
+    fn history_len(&self) -> usize;
+
+    /// See [`SearchTree::get_root_info`].
+    fn root_info(&self) -> DynNodeInfo;
+
+    /// See [`SearchTree::get_next_move_info`].
+    fn next_move_info(&self) -> Option<Vec<(Value, DynNodeInfo)>>;
+
+    /// See [`SearchTree::search_report`].
+    fn search_report(&self, include_nodes: bool) -> SearchReport<Value, Value>;
+
+    /// See [`SearchTree::get_registry_info`].
+    fn registry_info(&self) -> &RegistryInfo;
+}
+
+/// Wraps a [`SearchTree`] to implement [`DynTree`].
+#[derive(Debug)]
+pub struct ErasedTree<T> {
+    tree: T,
+}
+
+impl<T> ErasedTree<T> {
+    /// Construct a new `ErasedTree`.
+    pub fn new(tree: T) -> Self {
+        Self { tree }
+    }
+
+    /// Returns the wrapped tree.
+    pub fn get_ref(&self) -> &T {
+        &self.tree
+    }
+
+    /// Returns the wrapped tree, consuming the `ErasedTree`.
+    pub fn into_inner(self) -> T {
+        self.tree
+    }
+}
+
+impl<T: 'static> ErasedTree<T>
+where
+    Self: DynTree,
+{
+    /// Wraps the tree and returns it as a boxed [`DynTree`].
+    pub fn boxed(tree: T) -> Box<dyn DynTree> {
+        Box::new(Self::new(tree))
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).expect("the value can be encoded as JSON")
+}
+
+fn encode_info<S, P, Q>(info: NodeInfo<S, P, Q>) -> DynNodeInfo
+where
+    S: Serialize,
+    P: Serialize,
+    Q: Serialize,
+{
+    NodeInfo {
+        depth: info.depth,
+        state: info.state.as_ref().map(encode),
+        player: encode(&info.player),
+        score: info.score.as_ref().map(encode),
+        n_parents: info.n_parents,
+        n_children: info.n_children,
+    }
+}
+
+fn encode_status<A: Serialize>(status: Status<A>) -> Status<Value> {
+    match status {
+        Status::Action(a) => Status::Action(encode(&a)),
+        Status::ActionWip(a) => Status::ActionWip(encode(&a)),
+        Status::Pending => Status::Pending,
+        Status::Terminal => Status::Terminal,
+    }
+}
+
+impl<T, S, P, A, Q> DynTree for ErasedTree<T>
+where
+    T: SearchTree + Send + Sync,
+    T::GD: GameDynamics<State = S, Player = P, Action = A, Score = Q>,
+    S: Serialize,
+    P: Clone + Serialize,
+    A: PartialEq + Serialize + DeserializeOwned,
+    Q: Clone + Serialize,
+{
+    fn step(&self) -> bool {
+        self.tree.step().is_some()
+    }
+
+    fn best_action(&self) -> Status<Value> {
+        encode_status(self.tree.best_action())
+    }
+
+    fn apply_action(&self, action: &Value) -> Result<(), DynActionError> {
+        let decoded: A = serde_json::from_value(action.clone()).map_err(DynActionError::Decode)?;
+        let edges = loop {
+            if let Status::Terminal = expand_root(&self.tree) {
+                return Err(DynActionError::Illegal(action.clone()));
+            }
+            match self.tree.get_next_move_edges() {
+                Some(edges) => break edges,
+                // another thread is creating the root's children
+                None => {
+                    self.tree.step();
+                }
+            }
+        };
+        if !edges.iter().any(|(a, _)| *a == decoded) {
+            return Err(DynActionError::Illegal(action.clone()));
+        }
+        self.tree.apply_action(&decoded);
+        Ok(())
+    }
+
+    fn apply_best_action(&self) -> Status<Value> {
+        encode_status(self.tree.apply_best_action())
+    }
+
+    fn undo(&self) -> bool {
+        self.tree.undo()
+    }
+
+    fn history_len(&self) -> usize {
+        self.tree.history_len()
+    }
+
+    fn root_info(&self) -> DynNodeInfo {
+        encode_info(self.tree.get_root_info())
+    }
+
+    fn next_move_info(&self) -> Option<Vec<(Value, DynNodeInfo)>> {
+        let info = self.tree.get_next_move_info()?;
+        Some(
+            info.into_iter()
+                .map(|(a, info)| (encode(&a), encode_info(info)))
+                .collect(),
+        )
+    }
+
+    fn search_report(&self, include_nodes: bool) -> SearchReport<Value, Value> {
+        let report = self.tree.search_report(include_nodes);
+        SearchReport {
+            root_hash: report.root_hash,
+            n_trees: report.n_trees,
+            children: report
+                .children
+                .into_iter()
+                .map(|c| ChildReport {
+                    action: encode(&c.action),
+                    hash: c.hash,
+                    visits: c.visits,
+                    prior: c.prior,
+                    score: c.score.as_ref().map(encode),
+                })
+                .collect(),
+            nodes: report
+                .nodes
+                .into_iter()
+                .map(|n| NodeReport {
+                    hash: n.hash,
+                    depth: n.depth,
+                    visits: n.visits,
+                    score: n.score.as_ref().map(encode),
+                })
+                .collect(),
+        }
+    }
+
+    fn registry_info(&self) -> &RegistryInfo {
+        self.tree.get_registry_info()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chess::chess_mcts::{self, Chess};
+    use crate::nim::{Nim, Player};
+    use crate::simple_game::{SimpleGame, Uct};
+    use crate::tree::state_memory::{GetState, StoreState};
+    use crate::tree::Tree;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    use std::sync::Mutex;
+
+    // a one player game: count to 5 in steps of 1 or 2, ending exactly on 5 is rewarded
+    struct Count;
+
+    impl SimpleGame for Count {
+        type Player = ();
+        type State = u8;
+        type Action = u8;
+
+        fn legal_actions(&self, _player: &(), state: &u8) -> Vec<u8> {
+            if *state < 5 {
+                vec![1, 2]
+            } else {
+                Vec::new()
+            }
+        }
+
+        fn apply(&self, state: u8, action: &u8) -> u8 {
+            state + action
+        }
+
+        fn next_player(&self, _player: &()) {}
+
+        fn reward(&self, _player: &(), state: &u8) -> Vec<((), f64)> {
+            vec![((), if *state == 5 { 1.0 } else { 0.0 })]
+        }
+    }
+
+    #[test]
+    fn test_dyn_tree() {
+        let nim = Nim {
+            max_move: 3,
+            rng: Mutex::new(StdRng::seed_from_u64(0)),
+        };
+        let trees: Vec<Box<dyn DynTree>> = vec![
+            ErasedTree::boxed(Tree::new(nim, GetState, Player::P1, 2).with_history(2, true)),
+            ErasedTree::boxed(Tree::new(Uct::new(Count), StoreState, (), 0)),
+        ];
+        for tree in trees.iter() {
+            for _ in 0..50 {
+                tree.step();
+            }
+            assert!(matches!(tree.best_action(), Status::Action(_)));
+            assert!(!tree.search_report(false).children.is_empty());
+        }
+
+        let nim = &trees[0];
+        assert!(matches!(
+            nim.apply_action(&json!("x")),
+            Err(DynActionError::Decode(_))
+        ));
+        assert!(matches!(
+            nim.apply_action(&json!(3)),
+            Err(DynActionError::Illegal(_))
+        ));
+        nim.apply_action(&json!(1)).unwrap();
+        assert_eq!(nim.root_info().state, Some(json!(1)));
+        assert_eq!(nim.root_info().player, json!("P2"));
+        assert_eq!(nim.history_len(), 1);
+        nim.step();
+        assert!(matches!(nim.apply_best_action(), Status::Action(a) if a == json!(1)));
+        assert!(matches!(
+            nim.apply_action(&json!(1)),
+            Err(DynActionError::Illegal(_))
+        ));
+        assert!(nim.undo());
+        assert_eq!(nim.root_info().state, Some(json!(1)));
+
+        let count = &trees[1];
+        let info = count.next_move_info().unwrap();
+        assert_eq!(info.len(), 2);
+        let score = count.root_info().score.unwrap();
+        assert!(score["playouts"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_dyn_chess() {
+        // the chess dynamics are not implemented yet, so the tree is only created and inspected
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let chess = Chess {
+            fen_state: fen.to_string(),
+            rng: Mutex::new(StdRng::seed_from_u64(0)),
+        };
+        let tree: Box<dyn DynTree> = ErasedTree::boxed(Tree::new(
+            chess,
+            StoreState,
+            chess_mcts::Player::White,
+            fen.to_string(),
+        ));
+        let info = tree.root_info();
+        assert_eq!(info.player, json!("White"));
+        assert_eq!(info.state, Some(json!(fen)));
+        assert_eq!(info.score, None);
+        assert!(matches!(
+            tree.apply_action(&json!("e2e4")),
+            Err(DynActionError::Decode(_))
+        ));
+        assert_eq!(tree.history_len(), 0);
+        assert!(tree.search_report(false).children.is_empty());
+    }
+}
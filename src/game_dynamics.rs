@@ -1,10 +1,11 @@
 use std::cell::{Ref, RefCell};
 use std::ops::Deref;
 
+use crate::annotation;
 use crate::ref_iter::RefIterator;
 
 /// A flag indicating whether an action is being evaluated for exploration or exploitation.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SelectNodeState {
     /// `Explore` indicates that the node has been selected in [`GameDynamics::select_node`] for
     /// exploration purposes.
@@ -14,6 +15,35 @@ pub enum SelectNodeState {
     Exploit,
 }
 
+/// Statistics of the edge connecting a parent `Node` to one of its children, passed to
+/// [`GameDynamics::select_node_with_edges`] alongside the child's score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeStats {
+    /// The prior probability of selecting the edge's action as returned by
+    /// [`GameDynamics::action_prior`] when the edge was created (possibly mixed with noise via
+    /// [`SearchTree::add_root_noise`](crate::SearchTree::add_root_noise)).
+    pub prior: Option<f64>,
+    /// The number of times the edge was traversed while expanding the `Tree`.
+    pub visits: usize,
+}
+
+// Drops the `EdgeStats` so that the default implementation of
+// `GameDynamics::select_node_with_edges` can forward to `GameDynamics::select_node`
+#[derive(Clone)]
+struct WithoutEdges<II>(II);
+
+impl<II, Q, A> IntoIterator for WithoutEdges<II>
+where
+    II: IntoIterator<Item = (Q, A, EdgeStats)>,
+{
+    type Item = (Q, A);
+    type IntoIter = std::iter::Map<II::IntoIter, fn((Q, A, EdgeStats)) -> (Q, A)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter().map(|(q, a, _)| (q, a))
+    }
+}
+
 // It's currently not possible to turn `GameDynamics` into a trait object because `select_node` and
 // `backprop_scores` use generic parameters.  In particular, the underlying generic type has an
 // iterator over `Node<GD, State, Player, Action, Score, I, M>` where `M` is a true generic
@@ -77,14 +107,35 @@ pub trait GameDynamics {
     ///
     /// Note that the user must ensure that for any `State` the same state can never be reached
     /// again by applying any number of `Action`s obtained via `available_actions` (i.e. the user
-    /// must ensure the tree graph is acyclic)
+    /// must ensure the tree graph is acyclic), unless the `Tree` is configured to detect cycles via
+    /// [`Tree::with_cycle_handling`](crate::Tree::with_cycle_handling).
     fn apply_action(&self, state: Self::State, action: &Self::Action) -> Option<Self::State>;
 
+    /// Returns the prior probability of selecting `action` at a node with state
+    /// `parent_node_state` (e.g. the output of a policy network).  The prior is stored on the edge
+    /// connecting the parent to the resulting child and passed to
+    /// [`GameDynamics::select_node_with_edges`].  The method is called once for every action
+    /// yielded by [`GameDynamics::available_actions`] (after the action was successfully
+    /// applied).
+    ///
+    /// The default implementation returns `None`, i.e. no prior.
+    #[allow(unused_variables)]
+    fn action_prior(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        parent_node_state: &Self::State,
+        action: &Self::Action,
+    ) -> Option<f64> {
+        None
+    }
+
     /// Select the action to take based on the scores.
     ///
-    /// Note that because `scores_and_actions` are received in non-deterministic order, the result
-    /// of the MCTS algorithm may also be non-deterministic if there is a tie with respect to the
-    /// selected score for two different actions.
+    /// Note that because `scores_and_actions` are received in non-deterministic order when the
+    /// `Tree` is expanded by multiple threads, the result of the MCTS algorithm may also be
+    /// non-deterministic if there is a tie with respect to the selected score for two different
+    /// actions.  See [`ReproducibleSearch`](crate::ReproducibleSearch) for a deterministic mode.
     ///
     /// The `SelectNodeState` maybe be used to add an additional exploration parameter when
     /// searching for the best action.
@@ -113,6 +164,42 @@ pub trait GameDynamics {
         Q: Deref<Target = Option<Self::Score>>,
         A: Deref<Target = Self::Action>;
 
+    /// Select the action to take based on the scores and the [`EdgeStats`] of the edges leading
+    /// to the children.  The `Tree` always calls this method rather than
+    /// [`GameDynamics::select_node`]; the default implementation discards the `EdgeStats` and
+    /// forwards to `select_node`.
+    ///
+    /// Overriding this method allows selection rules that use the prior and visit count of each
+    /// edge, e.g. PUCT as used by AlphaZero:
+    ///
+    /// `Q(a) + c * P(a) * sqrt(N) / (1 + N(a))`
+    ///
+    /// where `P(a)` is [`EdgeStats::prior`], `N(a)` is [`EdgeStats::visits`], and `N` is the sum
+    /// of `N(a)` over all children.  Note that `N(a)` only counts traversals of the edge; a child
+    /// reached via a different parent (i.e. a transposition) may have been visited more often.
+    fn select_node_with_edges<II, Q, A>(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        parent_node_state: &Self::State,
+        purpose: SelectNodeState,
+        scores_actions_and_edges: II,
+    ) -> Self::Action
+    where
+        Self: Sized,
+        II: Clone + IntoIterator<Item = (Q, A, EdgeStats)>,
+        Q: Deref<Target = Option<Self::Score>>,
+        A: Deref<Target = Self::Action>,
+    {
+        self.select_node(
+            parent_score,
+            parent_player,
+            parent_node_state,
+            purpose,
+            WithoutEdges(scores_actions_and_edges),
+        )
+    }
+
     /// Score a parent node based on its child nodes.
     ///
     /// `score_current` is passed to enable comparison with the calculated score so that if the
@@ -145,6 +232,10 @@ pub trait GameDynamics {
     /// be considered immutable.  Changes to the score of such a terminal node on subsequent
     /// simulation runs will *not* result in the updates being backpropagated through the tree.
     ///
+    /// Evaluators that are more efficient when scoring several states at once (e.g. neural
+    /// networks) can implement [`GameDynamics::score_leaves`] and collect the states submitted
+    /// by concurrent calls via a [`Batcher`](crate::Batcher).
+    ///
     /// # Implementation Note:
     ///
     /// The library currently expands all branches of a leaf, though this can effectively be
@@ -177,6 +268,67 @@ pub trait GameDynamics {
         parent_player: &Self::Player,
         state: &Self::State,
     ) -> Option<Self::Score>;
+
+    /// Score the states of several new children of the same parent at once; `outputs[i]` must be
+    /// the score of `states[i]`.  Only called for a `Tree` configured with
+    /// [`Tree::with_leaf_batch_size`](crate::Tree::with_leaf_batch_size) greater than one, which
+    /// scores up to that many children of a leaf with a single call (a single new child is still
+    /// scored via [`GameDynamics::score_leaf`]).  The annotation of the node of `states[i]` is
+    /// available via [`with_leaf_annotation(i, ..)`](crate::with_leaf_annotation).
+    ///
+    /// The default implementation calls [`GameDynamics::score_leaf`] for each state, with the
+    /// annotation of the state's node installed (see [`with_annotation`](crate::with_annotation)).
+    fn score_leaves(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        states: &[&Self::State],
+    ) -> Vec<Option<Self::Score>> {
+        states
+            .iter()
+            .enumerate()
+            .map(|(i, state)| {
+                annotation::with_leaf_installed(i, || {
+                    self.score_leaf(parent_score, parent_player, state)
+                })
+            })
+            .collect()
+    }
+
+    /// Score a state that repeats the state of one of the parent's (grand)*parents.  Only called
+    /// for a `Tree` configured with [`CycleHandling::Draw`](crate::CycleHandling::Draw), in which
+    /// case the repeated state becomes a terminal node, e.g. scored as a draw.
+    ///
+    /// The default implementation calls [`GameDynamics::score_leaf`].
+    fn score_repetition(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        state: &Self::State,
+    ) -> Option<Self::Score> {
+        self.score_leaf(parent_score, parent_player, state)
+    }
+
+    /// Returns a lower and an upper bound on the value of a child with score `score` from the
+    /// perspective of `parent_player` (i.e. the player choosing among the children), e.g. derived
+    /// from proven values or from an evaluator whose output is known to be bounded.  The bounds
+    /// must be sound: they must hold for every score the child can be assigned later on (and a
+    /// greater value must be preferred by [`SelectNodeState::Exploit`]).
+    ///
+    /// A `Tree` configured with
+    /// [`Tree::with_dominance_pruning`](crate::Tree::with_dominance_pruning) skips children whose
+    /// upper bound is below the lower bound of a sibling when selecting a node and releases their
+    /// subtrees.
+    ///
+    /// The default implementation returns `None`, i.e. no bounds.
+    #[allow(unused_variables)]
+    fn score_bounds(
+        &self,
+        parent_player: &Self::Player,
+        score: &Self::Score,
+    ) -> Option<(f64, f64)> {
+        None
+    }
 }
 
 /// A trait that can be used to implemented [`DynGD`] without implementing [`GameDynamics`].
@@ -207,6 +359,18 @@ pub trait BaseGD {
     /// See [`GameDynamics::apply_action`] for a description of this associated function.
     fn apply_action(&self, state: Self::State, action: &Self::Action) -> Option<Self::State>;
 
+    /// See [`GameDynamics::action_prior`] for a description of this associated function.
+    #[allow(unused_variables)]
+    fn action_prior(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        parent_node_state: &Self::State,
+        action: &Self::Action,
+    ) -> Option<f64> {
+        None
+    }
+
     /// See [`GameDynamics::score_leaf`] for a description of this associated function.
     fn score_leaf(
         &self,
@@ -214,6 +378,44 @@ pub trait BaseGD {
         parent_player: &Self::Player,
         state: &Self::State,
     ) -> Option<Self::Score>;
+
+    /// See [`GameDynamics::score_leaves`] for a description of this associated function.
+    fn score_leaves(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        states: &[&Self::State],
+    ) -> Vec<Option<Self::Score>> {
+        states
+            .iter()
+            .enumerate()
+            .map(|(i, state)| {
+                annotation::with_leaf_installed(i, || {
+                    self.score_leaf(parent_score, parent_player, state)
+                })
+            })
+            .collect()
+    }
+
+    /// See [`GameDynamics::score_repetition`] for a description of this associated function.
+    fn score_repetition(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        state: &Self::State,
+    ) -> Option<Self::Score> {
+        self.score_leaf(parent_score, parent_player, state)
+    }
+
+    /// See [`GameDynamics::score_bounds`] for a description of this associated function.
+    #[allow(unused_variables)]
+    fn score_bounds(
+        &self,
+        parent_player: &Self::Player,
+        score: &Self::Score,
+    ) -> Option<(f64, f64)> {
+        None
+    }
 }
 
 impl<T> BaseGD for T
@@ -240,6 +442,23 @@ where
         <T as GameDynamics>::apply_action(&self, state, action)
     }
 
+    #[inline(always)]
+    fn action_prior(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        parent_node_state: &Self::State,
+        action: &Self::Action,
+    ) -> Option<f64> {
+        <T as GameDynamics>::action_prior(
+            self,
+            parent_score,
+            parent_player,
+            parent_node_state,
+            action,
+        )
+    }
+
     #[inline(always)]
     fn score_leaf(
         &self,
@@ -249,6 +468,35 @@ where
     ) -> Option<Self::Score> {
         <T as GameDynamics>::score_leaf(&self, parent_score, parent_player, state)
     }
+
+    #[inline(always)]
+    fn score_leaves(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        states: &[&Self::State],
+    ) -> Vec<Option<Self::Score>> {
+        <T as GameDynamics>::score_leaves(self, parent_score, parent_player, states)
+    }
+
+    #[inline(always)]
+    fn score_repetition(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        state: &Self::State,
+    ) -> Option<Self::Score> {
+        <T as GameDynamics>::score_repetition(self, parent_score, parent_player, state)
+    }
+
+    #[inline(always)]
+    fn score_bounds(
+        &self,
+        parent_player: &Self::Player,
+        score: &Self::Score,
+    ) -> Option<(f64, f64)> {
+        <T as GameDynamics>::score_bounds(self, parent_player, score)
+    }
 }
 
 /// A supertrait of [`BaseGD`].  Its purpose is to implement `GameDynamics` for trait objects.
@@ -292,6 +540,32 @@ pub trait DynGD: BaseGD {
         >,
     ) -> Self::Action;
 
+    /// See [`GameDynamics::select_node_with_edges`] for a description of this associated
+    /// function.
+    fn select_node_with_edges(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        parent_node_state: &Self::State,
+        purpose: SelectNodeState,
+        scores_actions_and_edges: &mut dyn Iterator<
+            Item = (
+                Ref<'_, Option<Self::Score>>,
+                Ref<'_, Self::Action>,
+                EdgeStats,
+            ),
+        >,
+    ) -> Self::Action {
+        <Self as DynGD>::select_node(
+            self,
+            parent_score,
+            parent_player,
+            parent_node_state,
+            purpose,
+            &mut scores_actions_and_edges.map(|(q, a, _)| (q, a)),
+        )
+    }
+
     /// See [`GameDynamics::backprop_scores`] for a description of this associated function.
     fn backprop_scores(
         &self,
@@ -322,6 +596,17 @@ where
         <T as BaseGD>::apply_action(self, state, action)
     }
 
+    #[inline(always)]
+    fn action_prior(
+        &self,
+        parent_score: Option<&T::Score>,
+        parent_player: &T::Player,
+        parent_node_state: &T::State,
+        action: &T::Action,
+    ) -> Option<f64> {
+        <T as BaseGD>::action_prior(self, parent_score, parent_player, parent_node_state, action)
+    }
+
     fn select_node<II, Q, A>(
         &self,
         parent_score: Option<&T::Score>,
@@ -365,6 +650,51 @@ where
         )
     }
 
+    fn select_node_with_edges<II, Q, A>(
+        &self,
+        parent_score: Option<&T::Score>,
+        parent_player: &T::Player,
+        parent_node_state: &T::State,
+        purpose: SelectNodeState,
+        scores_actions_and_edges: II,
+    ) -> T::Action
+    where
+        Self: Sized,
+        II: Clone + IntoIterator<Item = (Q, A, EdgeStats)>,
+        Q: Deref<Target = Option<T::Score>>,
+        A: Deref<Target = T::Action>,
+    {
+        // see `select_node` above; `EdgeStats` is `Copy` and can be passed by value
+        let reserved_space = RefCell::new(None);
+        let scores = scores_actions_and_edges
+            .clone()
+            .into_iter()
+            .map(|qae| qae.0)
+            .ref_iter(&reserved_space)
+            .map(|q| Ref::map(q, Deref::deref));
+
+        let reserved_space = RefCell::new(None);
+        let actions = scores_actions_and_edges
+            .clone()
+            .into_iter()
+            .map(|qae| qae.1)
+            .ref_iter(&reserved_space)
+            .map(|a| Ref::map(a, Deref::deref));
+
+        let edges = scores_actions_and_edges.into_iter().map(|qae| qae.2);
+
+        let mut qae = scores.zip(actions).zip(edges).map(|((q, a), e)| (q, a, e));
+
+        <T as DynGD>::select_node_with_edges(
+            self,
+            parent_score,
+            parent_player,
+            parent_node_state,
+            purpose,
+            &mut qae,
+        )
+    }
+
     fn backprop_scores<II, Q>(
         &self,
         player: &T::Player,
@@ -394,6 +724,31 @@ where
     ) -> Option<T::Score> {
         <T as BaseGD>::score_leaf(self, parent_score, parent_player, state)
     }
+
+    #[inline(always)]
+    fn score_leaves(
+        &self,
+        parent_score: Option<&T::Score>,
+        parent_player: &T::Player,
+        states: &[&T::State],
+    ) -> Vec<Option<T::Score>> {
+        <T as BaseGD>::score_leaves(self, parent_score, parent_player, states)
+    }
+
+    #[inline(always)]
+    fn score_repetition(
+        &self,
+        parent_score: Option<&T::Score>,
+        parent_player: &T::Player,
+        state: &T::State,
+    ) -> Option<T::Score> {
+        <T as BaseGD>::score_repetition(self, parent_score, parent_player, state)
+    }
+
+    #[inline(always)]
+    fn score_bounds(&self, parent_player: &T::Player, score: &T::Score) -> Option<(f64, f64)> {
+        <T as BaseGD>::score_bounds(self, parent_player, score)
+    }
 }
 
 // An interface for two player games that wraps the generic `GameDynamics` interface above; Not yet
@@ -169,11 +169,45 @@
     broken_intra_doc_links
 )]
 
+// Spans and events of the search internals, which expand to nothing unless the `tracing` feature is
+// enabled; a span is entered until the end of the enclosing block
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!($($arg)*).entered();
+    };
+}
+
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(tracing::Level::$level, $($arg)*);
+    };
+}
+
+mod annotation;
+mod async_search;
+mod batch;
+mod book;
 pub mod chess;
+mod dyn_tree;
 mod game_dynamics;
 mod lockref;
 pub mod nim;
+mod observer;
+mod ponder;
 mod ref_iter;
+mod reproducible;
+mod root_parallel;
+mod self_play;
+mod simple_game;
+mod stats;
+#[cfg(feature = "testing")]
+mod synthetic;
+#[cfg(feature = "testing")]
+mod testing;
+mod tournament;
+mod trace;
 mod tree;
 mod unique_heap;
 
@@ -185,11 +219,33 @@ pub use prelude::*;
 
 #[doc(hidden)]
 pub mod prelude {
-    pub use crate::game_dynamics::{BaseGD, DynGD, GameDynamics, SelectNodeState};
+    pub use crate::annotation::{with_annotation, with_leaf_annotation, Annotation};
+    pub use crate::async_search::{
+        search_with_progress, AsyncSearchConfig, NextProgress, Progress, ProgressInterval,
+        ProgressSnapshot, SearchBudget, SearchOutcome, SearchTask,
+    };
+    pub use crate::batch::{BatchConfig, BatchEvaluator, Batcher};
+    pub use crate::book::{book_key, BookBuilder, BookMove, OpeningBook};
+    pub use crate::dyn_tree::{DynActionError, DynNodeInfo, DynTree, ErasedTree};
+    pub use crate::game_dynamics::{BaseGD, DynGD, EdgeStats, GameDynamics, SelectNodeState};
+    pub use crate::observer::TreeObserver;
+    pub use crate::ponder::Ponder;
+    pub use crate::reproducible::{with_search_rng, ReproducibleSearch};
+    pub use crate::root_parallel::{
+        search_root_parallel, ChildReport, LocalTransport, NodeReport, RootParallelConfig,
+        SearchReport, Transport,
+    };
+    pub use crate::self_play::{GameRecord, MoveRecord, SelfPlay, SelfPlayConfig};
+    pub use crate::simple_game::{SimpleGame, Uct, UctScore};
+    pub use crate::stats::{MemoryHints, RegistryInfo, RegistryStats};
+    pub use crate::tournament::{
+        run_match, MatchConfig, MatchReport, MatchStats, Outcome, Sprt, SprtDecision,
+    };
+    pub use crate::trace::{TraceChild, TraceRecord, TraceRecorder};
     pub use crate::tree::state_memory::{self, GetState, HashOnly, StateMemory, StoreState};
     pub use crate::tree::{
-        ArcNode, ArcWrap, Node, NodeInfo, OnDrop, RegistryInfo, SearchTree, Status, Tree, WeakNode,
-        WeakWrap,
+        ArcNode, ArcWrap, Cursor, CycleHandling, Node, NodeFilter, NodeInfo, OnDrop, SearchTree,
+        Status, Tree, WeakNode, WeakWrap,
     };
 
     pub use crate::nim;
@@ -197,6 +253,14 @@ pub mod prelude {
     pub use crate::tree::TreeAlias;
     // pub use crate::chess_mcts::{Chess, Player};
 
+    #[cfg(feature = "testing")]
+    pub use crate::synthetic::{Side, SyntheticConfig, SyntheticGame, SyntheticState};
+    #[cfg(feature = "testing")]
+    pub use crate::testing::{
+        assert_game_dynamics, check_game_dynamics, ConformanceConfig, ConformanceReport, Violation,
+        ViolationKind,
+    };
+
     #[cfg(feature = "test_internals")]
     pub use crate::tree::test::*;
 }
@@ -34,13 +34,14 @@ use rand::rngs::StdRng;
 use rand::Rng;
 
 use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 const INIT: usize = 500;
 const MAX_MOVE: usize = 10;
 
 #[doc(hidden)]
-#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Clone, Debug, Hash, PartialEq, Serialize, Deserialize)]
 pub enum Player {
     P1,
     P2,
@@ -48,7 +49,7 @@ pub enum Player {
 
 // Each player keeps their own score ... just for fun
 #[doc(hidden)]
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Score {
     player1: f64,
     player2: f64,
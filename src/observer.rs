@@ -0,0 +1,164 @@
+// Observers are stored type-erased in a list shared by the `Tree` and all of its `Node`s (node drop
+// and score update events are raised by the `Node` itself, which has no reference to its `Tree`).
+// The public `TreeObserver` trait receives `NodeInfo`s, which require `Clone` on the player and
+// score types; rather than adding those bounds to every `Tree` method, the `NodeInfo` is built by
+// `Adapter`, which is only constructed when an observer is registered (i.e. where the `Clone`
+// bounds are known to hold).
+
+use crate::game_dynamics::{EdgeStats, GameDynamics};
+use crate::tree::state_memory::StateMemory;
+use crate::tree::{Node, NodeInfo};
+
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Receives notifications about changes to a [`Tree`](crate::Tree).  Register an observer via
+/// [`SearchTree::add_observer`](crate::SearchTree::add_observer).
+///
+/// All methods have a default implementation that does nothing, so only the events of interest
+/// need to be implemented.  The methods are called from the worker threads expanding the `Tree`
+/// (i.e. potentially concurrently) and should return quickly since they delay the search.  No locks
+/// on the reported `Node` are held while a method is called; however, the `Tree` may have been
+/// modified by other threads by the time the `NodeInfo` is received.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// #[derive(Default)]
+/// struct Transpositions(AtomicUsize);
+///
+/// impl<S, P, A, Q> TreeObserver<S, P, A, Q> for Transpositions {
+///     fn registry_hit(&self, _action: &A, _node: &NodeInfo<S, P, Q>) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+/// ```
+pub trait TreeObserver<S, P, A, Q>: Send + Sync {
+    /// A new `Node` was created and scored via
+    /// [`GameDynamics::score_leaf`](crate::GameDynamics::score_leaf); `action` is the action that
+    /// connects the `Node` to the parent it was created from.
+    fn node_created(&self, _action: &A, _node: &NodeInfo<S, P, Q>) {}
+
+    /// Applying `action` to a leaf resulted in a state that already existed in the `Tree` (i.e. a
+    /// transposition); the existing `Node` gained a parent.
+    fn registry_hit(&self, _action: &A, _node: &NodeInfo<S, P, Q>) {}
+
+    /// All children of the `Node` have been created and scored.
+    fn branch_complete(&self, _node: &NodeInfo<S, P, Q>) {}
+
+    /// The score of the `Node` changed as a result of backpropagation.
+    fn score_updated(&self, _node: &NodeInfo<S, P, Q>) {}
+
+    /// The root of the `Tree` is about to be moved by applying `action`; `root` is the current
+    /// root and `children` contains the root's children together with the actions and the
+    /// statistics of the edges leading to them.
+    fn root_moving(
+        &self,
+        _action: &A,
+        _root: &NodeInfo<S, P, Q>,
+        _children: &[(A, EdgeStats, NodeInfo<S, P, Q>)],
+    ) {
+    }
+
+    /// The root of the `Tree` was moved by applying `action`; `node` is the new root.
+    fn root_moved(&self, _action: &A, _node: &NodeInfo<S, P, Q>) {}
+
+    /// Applying `action` was taken back via [`SearchTree::undo`](crate::SearchTree::undo); `node`
+    /// is the restored root.
+    fn root_undone(&self, _action: &A, _node: &NodeInfo<S, P, Q>) {}
+
+    /// The `Node` is no longer reachable from the root and is about to be dropped.
+    fn node_dropped(&self, _node: &NodeInfo<S, P, Q>) {}
+}
+
+#[derive(Debug)]
+pub(crate) enum TreeEvent<'a, A> {
+    NodeCreated(&'a A),
+    RegistryHit(&'a A),
+    BranchComplete,
+    ScoreUpdated,
+    RootMoving(&'a A),
+    RootMoved(&'a A),
+    RootUndone(&'a A),
+    NodeDropped,
+}
+
+impl<'a, A> Clone for TreeEvent<'a, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, A> Copy for TreeEvent<'a, A> {}
+
+pub(crate) trait NodeObserver<N, A>: Send + Sync {
+    fn notify(&self, event: TreeEvent<'_, A>, node: &N);
+}
+
+pub(crate) struct Observers<N, A> {
+    // checked before acquiring the lock on `list` so that a `Tree` without observers does not pay
+    // for the lock on every event
+    active: AtomicBool,
+    list: RwLock<Vec<Box<dyn NodeObserver<N, A>>>>,
+}
+
+impl<N, A> Observers<N, A> {
+    pub fn new() -> Self {
+        Self {
+            active: AtomicBool::new(false),
+            list: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn push(&self, observer: Box<dyn NodeObserver<N, A>>) {
+        self.list.write().unwrap().push(observer);
+        self.active.store(true, Ordering::Release);
+    }
+
+    pub fn notify(&self, event: TreeEvent<'_, A>, node: &N) {
+        if self.active.load(Ordering::Acquire) {
+            for o in self.list.read().unwrap().iter() {
+                o.notify(event, node);
+            }
+        }
+    }
+}
+
+impl<N, A> Debug for Observers<N, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Observers")
+            .field("len", &self.list.read().unwrap().len())
+            .finish()
+    }
+}
+
+pub(crate) struct Adapter<S, P, A, Q>(pub Arc<dyn TreeObserver<S, P, A, Q>>);
+
+impl<GD, S, P, A, Q, I, M> NodeObserver<Node<GD, S, P, A, Q, I, M>, A> for Adapter<S, P, A, Q>
+where
+    Node<GD, S, P, A, Q, I, M>: StateMemory,
+    GD: GameDynamics<Player = P, State = S, Action = A>,
+    A: Hash + Eq + Clone,
+    S: Hash + PartialEq<S> + Clone,
+    P: Hash + PartialEq<P> + Clone,
+    Q: Clone,
+{
+    fn notify(&self, event: TreeEvent<'_, A>, node: &Node<GD, S, P, A, Q, I, M>) {
+        let info = node.get_node_info();
+        match event {
+            TreeEvent::NodeCreated(a) => self.0.node_created(a, &info),
+            TreeEvent::RegistryHit(a) => self.0.registry_hit(a, &info),
+            TreeEvent::BranchComplete => self.0.branch_complete(&info),
+            TreeEvent::ScoreUpdated => self.0.score_updated(&info),
+            TreeEvent::RootMoving(a) => self.0.root_moving(a, &info, &node.get_children_info()),
+            TreeEvent::RootMoved(a) => self.0.root_moved(a, &info),
+            TreeEvent::RootUndone(a) => self.0.root_undone(a, &info),
+            TreeEvent::NodeDropped => self.0.node_dropped(&info),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+// The workers of a `Ponder` call `SearchTree::step` in a loop.  Moving the root is coordinated by
+// the `Tree` itself (`apply_action` acquires the `prune_lock` exclusively, so it waits for steps
+// in progress and steps started afterwards begin at the new root), so the workers don't need to be
+// informed of root moves.  Pausing is cooperative: a worker checks the `paused` flag before each
+// step and parks on the `Condvar` until it is resumed or stopped.  A worker whose steps keep
+// reaching terminal nodes (e.g. once the tree is fully expanded) waits on the `Condvar` for an
+// increasing time (see `async_search::Backoff`) between steps, so that a root move is picked up
+// eventually without spinning.
+
+use crate::async_search::Backoff;
+use crate::game_dynamics::GameDynamics;
+use crate::tree::{SearchTree, Status};
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+struct Control {
+    paused: AtomicBool,
+    stopped: AtomicBool,
+    // the number of workers that are parked or have exited
+    idle: Mutex<usize>,
+    cv: Condvar,
+    n_steps: AtomicUsize,
+    // the payload of the first worker that panicked, e.g. in the evaluator (the other workers then
+    // typically panic on the poisoned locks of the tree)
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+// Counts a worker as idle once it exits (including when a step panics) so that `Ponder::pause`
+// does not wait for it
+struct Exited<'a>(&'a Control);
+
+impl Drop for Exited<'_> {
+    fn drop(&mut self) {
+        *self.0.idle.lock().unwrap() += 1;
+        self.0.cv.notify_all();
+    }
+}
+
+/// A handle to a background search that expands a [`SearchTree`] on worker threads until it is
+/// stopped, e.g. to keep searching ("pondering") while the opponent is thinking.
+///
+/// The search is not tied to a particular root: when the opponent's actual move is applied to the
+/// tree via [`SearchTree::apply_action`], the workers transparently continue searching from the
+/// new root (the statistics of the subtree below the new root are retained).  The search can be
+/// paused and resumed; dropping the handle stops the search.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use std::sync::Arc;
+///
+/// fn ponder<T>(tree: Arc<T>, opponent_move: <T::GD as GameDynamics>::Action)
+/// where
+///     T: SearchTree + Send + Sync + 'static,
+/// {
+///     let ponder = Ponder::start(tree, 4);
+///     // ... the opponent is thinking ...
+///     ponder.tree().apply_action(&opponent_move);
+///     // ... the search continues from the new root ...
+///     ponder.pause();
+///     let reply = ponder.best_action();
+///     let tree = ponder.stop();
+/// }
+/// ```
+pub struct Ponder<T: ?Sized + SearchTree> {
+    tree: Arc<T>,
+    control: Arc<Control>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<T> Ponder<T>
+where
+    T: ?Sized + SearchTree + Send + Sync + 'static,
+{
+    /// Starts searching `tree` on `n_threads` worker threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n_threads` is zero.
+    pub fn start(tree: Arc<T>, n_threads: usize) -> Self {
+        assert!(n_threads > 0, "at least one thread is required");
+        let control = Arc::new(Control {
+            paused: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            idle: Mutex::new(0),
+            cv: Condvar::new(),
+            n_steps: AtomicUsize::new(0),
+            panic: Mutex::new(None),
+        });
+        let workers = (0..n_threads)
+            .map(|_| {
+                let tree = Arc::clone(&tree);
+                let control = Arc::clone(&control);
+                std::thread::spawn(move || {
+                    let run = panic::catch_unwind(AssertUnwindSafe(|| Self::run(&*tree, &control)));
+                    if let Err(payload) = run {
+                        control.panic.lock().unwrap().get_or_insert(payload);
+                    }
+                })
+            })
+            .collect();
+        Self {
+            tree,
+            control,
+            workers,
+        }
+    }
+
+    /// Returns the tree being searched.  Actions applied to the tree (e.g. the opponent's move)
+    /// take effect for the background search as soon as the steps in progress have completed.
+    pub fn tree(&self) -> &Arc<T> {
+        &self.tree
+    }
+
+    /// Returns the currently anticipated best action at the root of the tree (see
+    /// [`SearchTree::best_action`]).
+    pub fn best_action(&self) -> Status<<T::GD as GameDynamics>::Action> {
+        self.tree.best_action()
+    }
+
+    /// Pauses the search.  Blocks until all workers have completed their current step, i.e. the
+    /// tree is not modified by the search once this method returns.
+    pub fn pause(&self) {
+        let idle = self.control.idle.lock().unwrap();
+        self.control.paused.store(true, Ordering::Release);
+        self.control.cv.notify_all();
+        let n_workers = self.workers.len();
+        let _idle = self
+            .control
+            .cv
+            .wait_while(idle, |idle| *idle < n_workers)
+            .unwrap();
+    }
+
+    /// Resumes a paused search.
+    pub fn resume(&self) {
+        let _idle = self.control.idle.lock().unwrap();
+        self.control.paused.store(false, Ordering::Release);
+        self.control.cv.notify_all();
+    }
+
+    /// Returns `true` if the search is paused.
+    pub fn is_paused(&self) -> bool {
+        self.control.paused.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of steps performed by the search so far; steps that did not expand the
+    /// tree (see [`SearchTree::step`]) are not counted.
+    pub fn n_steps(&self) -> usize {
+        self.control.n_steps.load(Ordering::Relaxed)
+    }
+
+    /// Stops the search, waits for the workers to exit, and returns the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a step of the search panicked (e.g. in the evaluator), with the payload of the
+    /// first worker that panicked.  Dropping the handle panics likewise unless the thread is
+    /// already panicking.
+    pub fn stop(mut self) -> Arc<T> {
+        if let Some(payload) = self.shutdown() {
+            panic::resume_unwind(payload);
+        }
+        Arc::clone(&self.tree)
+    }
+
+    fn run(tree: &T, control: &Control) {
+        let _exited = Exited(control);
+        let mut backoff = Backoff::new();
+        while !control.stopped.load(Ordering::Acquire) {
+            if control.paused.load(Ordering::Acquire) {
+                let mut idle = control.idle.lock().unwrap();
+                *idle += 1;
+                control.cv.notify_all();
+                idle = control
+                    .cv
+                    .wait_while(idle, |_| {
+                        control.paused.load(Ordering::Acquire)
+                            && !control.stopped.load(Ordering::Acquire)
+                    })
+                    .unwrap();
+                *idle -= 1;
+                continue;
+            }
+
+            if tree.step().is_some() {
+                control.n_steps.fetch_add(1, Ordering::Relaxed);
+                backoff.reset();
+            } else {
+                // the tree is fully expanded (or another worker is expanding the only available
+                // leaf), so wait for the root to move rather than spinning on the tree's locks;
+                // pausing or stopping the search ends the wait
+                let idle = control.idle.lock().unwrap();
+                let _idle = control
+                    .cv
+                    .wait_timeout_while(idle, backoff.next_wait(), |_| {
+                        !control.paused.load(Ordering::Acquire)
+                            && !control.stopped.load(Ordering::Acquire)
+                    })
+                    .unwrap();
+            }
+        }
+    }
+}
+
+impl<T: ?Sized + SearchTree> Ponder<T> {
+    // Stops the workers and waits for them to exit; returns the payload of the first worker that
+    // panicked
+    fn shutdown(&mut self) -> Option<Box<dyn Any + Send>> {
+        {
+            let _idle = self.control.idle.lock().unwrap();
+            self.control.stopped.store(true, Ordering::Release);
+            self.control.cv.notify_all();
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        self.control.panic.lock().unwrap().take()
+    }
+}
+
+impl<T: ?Sized + SearchTree> Drop for Ponder<T> {
+    fn drop(&mut self) {
+        // panicking while unwinding would abort
+        if let Some(payload) = self.shutdown() {
+            if !std::thread::panicking() {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized + SearchTree> Debug for Ponder<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ponder")
+            .field("n_threads", &self.workers.len())
+            .field("paused", &self.control.paused.load(Ordering::Relaxed))
+            .field("n_steps", &self.control.n_steps.load(Ordering::Relaxed))
+            .finish()
+    }
+}
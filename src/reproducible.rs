@@ -0,0 +1,244 @@
+// A search is reproducible if (1) the steps are executed in the same order, which
+// `ReproducibleSearch` ensures by passing a turn from worker thread to worker thread in order of
+// the workers' indices, (2) the children of a node are passed to `GameDynamics::select_node` in
+// the same order, which is ensured by the fixed hasher of `tree::ChildMap`, and (3) the
+// randomness used by the `GameDynamics` implementation is drawn from seeded streams, which are
+// provided by `with_search_rng`.
+
+use crate::tree::SearchTree;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+thread_local! {
+    static SEARCH_RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Calls `f` with the random number generator of the search worker running on the current thread.
+///
+/// Implementations of [`GameDynamics`](crate::GameDynamics) that require randomness (e.g. an
+/// exploration bonus in [`GameDynamics::select_node`](crate::GameDynamics::select_node) or random
+/// rollouts in [`GameDynamics::score_leaf`](crate::GameDynamics::score_leaf)) should draw it from
+/// this generator.  While a step is executed by [`ReproducibleSearch`], the generator is the seeded
+/// stream of the worker executing the step; otherwise each thread uses its own generator seeded
+/// from entropy.
+///
+/// # Panics
+///
+/// Panics if called from within `f`.
+pub fn with_search_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    SEARCH_RNG.with(|rng| f(rng.borrow_mut().get_or_insert_with(StdRng::from_entropy)))
+}
+
+// Installs a worker's generator as the search rng of the current thread and restores the previous
+// generator when dropped (including when the step panics)
+struct Installed<'a> {
+    worker_rng: &'a mut StdRng,
+    prior: Option<StdRng>,
+}
+
+impl<'a> Installed<'a> {
+    fn new(worker_rng: &'a mut StdRng) -> Self {
+        let prior = SEARCH_RNG.with(|rng| rng.replace(Some(worker_rng.clone())));
+        Self { worker_rng, prior }
+    }
+}
+
+impl Drop for Installed<'_> {
+    fn drop(&mut self) {
+        let prior = self.prior.take();
+        if let Some(rng) = SEARCH_RNG.with(|rng| rng.replace(prior)) {
+            *self.worker_rng = rng;
+        }
+    }
+}
+
+/// Expands a [`SearchTree`] such that the result only depends on the seed, the number of workers,
+/// and the number of rounds, which allows regression tests and bug reports to replay an exact
+/// search.
+///
+/// The search proceeds in rounds; in each round every worker performs one
+/// [`SearchTree::step`] in order of the worker's index.  Each worker runs on its own thread and
+/// has its own random number generator (derived from the seed) which is available to the
+/// [`GameDynamics`](crate::GameDynamics) implementation via [`with_search_rng`], so the search
+/// exercises the same threads, thread-local state, and random number streams as a parallel search.
+/// To guarantee a deterministic schedule, a worker only starts its step once the previous worker
+/// has completed its step, i.e. the steps never overlap and a reproducible search does not run
+/// faster on multiple cores.
+///
+/// Note that the `GameDynamics` implementation must itself be deterministic apart from the
+/// randomness drawn via `with_search_rng`.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+///
+/// fn replay<T>(tree: &T, seed: u64) -> Status<<T::GD as GameDynamics>::Action>
+/// where
+///     T: SearchTree + Sync,
+/// {
+///     let mut search = ReproducibleSearch::new(seed, 4);
+///     search.run(tree, 100);
+///     tree.best_action()
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ReproducibleSearch {
+    rngs: Vec<StdRng>,
+    rounds: usize,
+}
+
+impl ReproducibleSearch {
+    /// Construct a new `ReproducibleSearch` with `n_workers` workers whose random number
+    /// generators are derived from `seed`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n_workers` is zero.
+    pub fn new(seed: u64, n_workers: usize) -> Self {
+        assert!(n_workers > 0, "at least one worker is required");
+        let mut seeder = StdRng::seed_from_u64(seed);
+        let rngs = (0..n_workers)
+            .map(|_| StdRng::from_rng(&mut seeder).expect("`StdRng` is infallible"))
+            .collect();
+        Self { rngs, rounds: 0 }
+    }
+
+    /// Runs `n_rounds` rounds and returns the number of steps that expanded the `tree` (i.e. for
+    /// which `SearchTree::step` returned `Some(_)`).  Subsequent calls continue the workers'
+    /// random number streams, so running `a` and then `b` rounds is identical to running `a + b`
+    /// rounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a step panics; the remaining workers stop at their next turn.
+    pub fn run<T>(&mut self, tree: &T, n_rounds: usize) -> usize
+    where
+        T: ?Sized + SearchTree + Sync,
+    {
+        let n_workers = self.rngs.len();
+        let schedule = Schedule {
+            turn: Mutex::new(Some(0)),
+            cv: Condvar::new(),
+        };
+        let n_expanded = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for (i, rng) in self.rngs.iter_mut().enumerate() {
+                let (schedule, n_expanded) = (&schedule, &n_expanded);
+                s.spawn(move || {
+                    let _installed = Installed::new(rng);
+                    for round in 0..n_rounds {
+                        let _turn = match schedule.wait_for(round * n_workers + i) {
+                            Some(turn) => turn,
+                            None => return,
+                        };
+                        if tree.step().is_some() {
+                            n_expanded.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+        self.rounds += n_rounds;
+        n_expanded.into_inner()
+    }
+
+    /// Returns the number of workers.
+    pub fn n_workers(&self) -> usize {
+        self.rngs.len()
+    }
+
+    /// Returns the number of rounds run so far.
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+}
+
+// The index of the step whose turn it is; `None` once a step panicked
+struct Schedule {
+    turn: Mutex<Option<usize>>,
+    cv: Condvar,
+}
+
+impl Schedule {
+    // Blocks until it is the turn of step `step`; returns `None` if a previous step panicked
+    fn wait_for(&self, step: usize) -> Option<Turn<'_>> {
+        let turn = self.turn.lock().unwrap();
+        let turn = self
+            .cv
+            .wait_while(turn, |t| matches!(*t, Some(t) if t != step))
+            .unwrap();
+        turn.map(|_| Turn(self))
+    }
+}
+
+// Passes the turn on to the next step when dropped, or ends the schedule if the step panicked
+struct Turn<'a>(&'a Schedule);
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        let mut turn = self.0.turn.lock().unwrap_or_else(|e| e.into_inner());
+        *turn = match *turn {
+            Some(t) if !std::thread::panicking() => Some(t + 1),
+            _ => None,
+        };
+        self.0.cv.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::RngCore;
+
+    fn draw(search: &mut ReproducibleSearch, worker: usize) -> u64 {
+        let _installed = Installed::new(&mut search.rngs[worker]);
+        with_search_rng(|rng| rng.next_u64())
+    }
+
+    #[test]
+    fn test_worker_streams() {
+        let mut a = ReproducibleSearch::new(3, 2);
+        let mut b = ReproducibleSearch::new(3, 2);
+
+        let a0 = (0..3).map(|_| draw(&mut a, 0)).collect::<Vec<_>>();
+        let a1 = draw(&mut a, 1);
+        let b1 = draw(&mut b, 1);
+        let b0 = (0..3).map(|_| draw(&mut b, 0)).collect::<Vec<_>>();
+
+        // streams are independent of the interleaving of workers and continue across steps
+        assert_eq!(a0, b0);
+        assert_eq!(a1, b1);
+        assert_ne!(a0[0], a0[1]);
+        assert_ne!(a0[0], a1);
+
+        // the thread's own generator is restored after a step
+        assert!(SEARCH_RNG.with(|rng| rng.borrow().is_none()));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_panicking_step() {
+        use crate::synthetic::{SyntheticConfig, SyntheticGame};
+        use crate::tree::state_memory::GetState;
+        use crate::tree::Tree;
+        use std::panic::{self, AssertUnwindSafe};
+
+        let game = SyntheticGame::new(SyntheticConfig {
+            panic_rate: 1.0,
+            ..Default::default()
+        });
+        let (player, state) = game.initial();
+        let tree = Tree::new(game, GetState, player, state);
+        // the panic of the first step is propagated instead of leaving the other workers waiting
+        // for their turn
+        let mut search = ReproducibleSearch::new(0, 4);
+        let r = panic::catch_unwind(AssertUnwindSafe(|| search.run(&tree, 10)));
+        assert!(r.is_err());
+    }
+}
@@ -0,0 +1,410 @@
+// Root parallelization runs independent `Tree`s instead of sharing one: each tree is searched
+// without any synchronization with the others and only a `SearchReport` (a plain data summary of
+// the tree) is exchanged at the end.  Reports can be serialized, so the trees may live in other
+// processes or on other machines; `Transport` abstracts over how reports are delivered and
+// `LocalTransport` is an in-process stand-in used by `search_root_parallel`.
+
+use crate::game_dynamics::GameDynamics;
+use crate::tree::SearchTree;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::hash::Hash;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+
+/// The statistics of an edge from the root to one of its children in a [`SearchReport`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChildReport<A, Q> {
+    /// The action leading to the child.
+    pub action: A,
+    /// The hash of the child's player and state.
+    pub hash: u64,
+    /// The number of times the edge was traversed.
+    pub visits: usize,
+    /// The prior of the edge (see [`GameDynamics::action_prior`]).
+    pub prior: Option<f64>,
+    /// The score of the child.
+    pub score: Option<Q>,
+}
+
+/// The statistics of a node in a [`SearchReport`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeReport<Q> {
+    /// The hash of the node's player and state.
+    pub hash: u64,
+    /// The smallest number of actions from the root to the node.
+    pub depth: usize,
+    /// The number of times the edges leading to the node were traversed (for the root, the
+    /// number of times the edges leaving it were traversed).
+    pub visits: usize,
+    /// The score of the node.
+    pub score: Option<Q>,
+}
+
+/// A summary of a [`SearchTree`] (see [`SearchTree::search_report`]) that can be sent to another
+/// thread or process and merged with the reports of other trees that searched the same root.
+///
+/// Nodes are matched across trees by the hash of their player and state, which is computed with
+/// [`DefaultHasher::new`](std::collections::hash_map::DefaultHasher::new); the hashes are
+/// therefore only comparable between processes running the same build of the library.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchReport<A, Q> {
+    /// The hash of the root's player and state.
+    pub root_hash: u64,
+    /// The number of trees whose reports were merged into this report.
+    pub n_trees: usize,
+    /// The statistics of the root's children.
+    pub children: Vec<ChildReport<A, Q>>,
+    /// The statistics of all nodes reachable from the root (including the root itself); empty
+    /// unless requested when the report was created.
+    pub nodes: Vec<NodeReport<Q>>,
+}
+
+impl<A, Q> SearchReport<A, Q>
+where
+    A: Clone + Hash + Eq,
+    Q: Clone,
+{
+    /// Merges the reports of independently searched trees.  Visits of matching children (by
+    /// action) and nodes (by hash) are summed; since scores cannot be combined without knowledge
+    /// of the game, the merged score of a child or node is the score reported by the tree that
+    /// visited it most often.  Returns `None` if `reports` is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reports are for different roots.
+    pub fn merge<II>(reports: II) -> Option<Self>
+    where
+        II: IntoIterator<Item = Self>,
+    {
+        let mut reports = reports.into_iter();
+        let mut merged = reports.next()?;
+        // the largest number of visits of a single report that contributed the score of each
+        // child and node
+        let mut child_score_visits = merged.children.iter().map(|c| c.visits).collect::<Vec<_>>();
+        let mut node_score_visits = merged.nodes.iter().map(|n| n.visits).collect::<Vec<_>>();
+        let mut child_index = merged
+            .children
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.action.clone(), i))
+            .collect::<HashMap<_, _>>();
+        let mut node_index = merged
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.hash, i))
+            .collect::<HashMap<_, _>>();
+
+        for report in reports {
+            assert_eq!(
+                merged.root_hash, report.root_hash,
+                "cannot merge reports of different roots"
+            );
+            merged.n_trees += report.n_trees;
+
+            for child in report.children {
+                match child_index.entry(child.action.clone()) {
+                    Entry::Occupied(e) => {
+                        let i = *e.get();
+                        if child.visits > child_score_visits[i] {
+                            child_score_visits[i] = child.visits;
+                            merged.children[i].score = child.score;
+                        }
+                        merged.children[i].visits += child.visits;
+                    }
+                    Entry::Vacant(e) => {
+                        e.insert(merged.children.len());
+                        child_score_visits.push(child.visits);
+                        merged.children.push(child);
+                    }
+                }
+            }
+
+            for node in report.nodes {
+                match node_index.entry(node.hash) {
+                    Entry::Occupied(e) => {
+                        let i = *e.get();
+                        if node.visits > node_score_visits[i] {
+                            node_score_visits[i] = node.visits;
+                            merged.nodes[i].score = node.score;
+                        }
+                        let m = &mut merged.nodes[i];
+                        m.visits += node.visits;
+                        m.depth = std::cmp::min(m.depth, node.depth);
+                    }
+                    Entry::Vacant(e) => {
+                        e.insert(merged.nodes.len());
+                        node_score_visits.push(node.visits);
+                        merged.nodes.push(node);
+                    }
+                }
+            }
+        }
+
+        Some(merged)
+    }
+
+    /// Returns the most visited action at the root (ties are resolved in favor of the child that
+    /// appears first), or `None` if the root has no children.
+    pub fn best_action(&self) -> Option<&A> {
+        self.children
+            .iter()
+            .rev()
+            .max_by_key(|c| c.visits)
+            .map(|c| &c.action)
+    }
+}
+
+/// Delivers messages (e.g. [`SearchReport`]s) from the workers of a root-parallel search to the
+/// coordinator, e.g. over a network connection.  See [`LocalTransport`] for an in-process
+/// implementation.
+pub trait Transport<M>: Send + Sync {
+    /// Sends `message` to the coordinator.
+    fn send(&self, message: M);
+
+    /// Blocks until a message is available and returns it.
+    fn recv(&self) -> M;
+}
+
+/// An in-process [`Transport`] backed by a channel; a stand-in for a transport between processes.
+pub struct LocalTransport<M> {
+    tx: Mutex<Sender<M>>,
+    rx: Mutex<Receiver<M>>,
+}
+
+impl<M> LocalTransport<M> {
+    /// Construct a new `LocalTransport`.
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            tx: Mutex::new(tx),
+            rx: Mutex::new(rx),
+        }
+    }
+}
+
+impl<M> Default for LocalTransport<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: Send> Transport<M> for LocalTransport<M> {
+    fn send(&self, message: M) {
+        self.tx
+            .lock()
+            .unwrap()
+            .send(message)
+            .expect("receiver is owned by the transport");
+    }
+
+    fn recv(&self) -> M {
+        self.rx
+            .lock()
+            .unwrap()
+            .recv()
+            .expect("sender is owned by the transport")
+    }
+}
+
+impl<M> std::fmt::Debug for LocalTransport<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalTransport").finish()
+    }
+}
+
+/// The parameters of [`search_root_parallel`].
+#[derive(Debug, Clone)]
+pub struct RootParallelConfig {
+    /// The number of independent trees, each of which is searched on its own thread.
+    pub n_trees: usize,
+    /// The number of steps performed on each tree.
+    pub n_steps: usize,
+    /// Whether the reports include all nodes (see [`SearchReport::nodes`]) or only the root's
+    /// children.
+    pub include_nodes: bool,
+}
+
+/// Searches `config.n_trees` independent trees in parallel and returns their merged
+/// [`SearchReport`].  Tree `i` is constructed by `make_tree(i)`, which is typically used to seed
+/// the tree's `GameDynamics` differently for each tree.  Since the trees share no state, there is
+/// no contention on the trees' locks; on the other hand, work may be duplicated across trees.
+///
+/// Each tree's report is delivered via `transport`; to distribute the search across processes,
+/// run the same steps in each process (i.e. call [`SearchTree::search_report`] and send it via a
+/// `Transport` between the processes) and merge the received reports with
+/// [`SearchReport::merge`].
+///
+/// # Panics
+///
+/// Panics if `make_tree` or the search of a tree panics (once the other trees have been searched).
+/// The reports of the other trees may then remain queued in `transport`.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+///
+/// fn decide<T, F>(make_tree: F) -> Option<<T::GD as GameDynamics>::Action>
+/// where
+///     F: Fn(usize) -> T + Sync,
+///     T: SearchTree,
+///     <T::GD as GameDynamics>::Action: Send + Clone + std::hash::Hash + Eq,
+///     <T::GD as GameDynamics>::Score: Send + Clone,
+/// {
+///     let config = RootParallelConfig {
+///         n_trees: 4,
+///         n_steps: 1000,
+///         include_nodes: false,
+///     };
+///     let report = search_root_parallel(&config, make_tree, &LocalTransport::new())?;
+///     report.best_action().cloned()
+/// }
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn search_root_parallel<T, F, X>(
+    config: &RootParallelConfig,
+    make_tree: F,
+    transport: &X,
+) -> Option<SearchReport<<T::GD as GameDynamics>::Action, <T::GD as GameDynamics>::Score>>
+where
+    F: Fn(usize) -> T + Sync,
+    T: SearchTree,
+    X: Transport<SearchReport<<T::GD as GameDynamics>::Action, <T::GD as GameDynamics>::Score>>,
+    <T::GD as GameDynamics>::Action: Send + Clone + Hash + Eq,
+    <T::GD as GameDynamics>::Score: Send + Clone,
+{
+    // each worker announces whether its report is sent via `transport` or whether it panicked;
+    // waiting for the report of a worker that panicked would block forever
+    let (status_tx, status_rx) = mpsc::channel();
+    std::thread::scope(|s| {
+        for i in 0..config.n_trees {
+            let make_tree = &make_tree;
+            let status_tx = status_tx.clone();
+            s.spawn(move || {
+                let report = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let tree = make_tree(i);
+                    for _ in 0..config.n_steps {
+                        tree.step();
+                    }
+                    tree.search_report(config.include_nodes)
+                }));
+                match report {
+                    Ok(report) => {
+                        let _ = status_tx.send(Ok(()));
+                        transport.send(report);
+                    }
+                    Err(payload) => {
+                        let _ = status_tx.send(Err(payload));
+                    }
+                }
+            });
+        }
+
+        let mut reports = Vec::with_capacity(config.n_trees);
+        for status in status_rx.iter().take(config.n_trees) {
+            match status {
+                Ok(()) => reports.push(transport.recv()),
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+        SearchReport::merge(reports)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn report(visits: &[(u8, usize, f64)]) -> SearchReport<u8, f64> {
+        let children = visits
+            .iter()
+            .map(|&(action, visits, score)| ChildReport {
+                action,
+                hash: u64::from(action),
+                visits,
+                prior: None,
+                score: Some(score),
+            })
+            .collect::<Vec<_>>();
+        let nodes = std::iter::once(NodeReport {
+            hash: 0,
+            depth: 0,
+            visits: children.iter().map(|c| c.visits).sum(),
+            score: None,
+        })
+        .chain(children.iter().map(|c| NodeReport {
+            hash: c.hash,
+            depth: 1,
+            visits: c.visits,
+            score: c.score,
+        }))
+        .collect();
+        SearchReport {
+            root_hash: 0,
+            n_trees: 1,
+            children,
+            nodes,
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let a = report(&[(1, 10, 0.1), (2, 5, 0.2)]);
+        let b = report(&[(2, 20, 0.3), (3, 1, 0.4)]);
+        assert_eq!(a.best_action(), Some(&1));
+
+        let m = SearchReport::merge(vec![a, b]).unwrap();
+        assert_eq!(m.n_trees, 2);
+        let children = m
+            .children
+            .iter()
+            .map(|c| (c.action, c.visits, c.score.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(children, vec![(1, 10, 0.1), (2, 25, 0.3), (3, 1, 0.4)]);
+        assert_eq!(m.best_action(), Some(&2));
+        assert_eq!(m.nodes.len(), 4);
+        assert_eq!(m.nodes[0].visits, 36);
+
+        assert!(SearchReport::<u8, f64>::merge(vec![]).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "different roots")]
+    fn test_merge_different_roots() {
+        let a = report(&[(1, 10, 0.1)]);
+        let mut b = a.clone();
+        b.root_hash = 1;
+        SearchReport::merge(vec![a, b]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot make tree")]
+    fn test_panicking_tree() {
+        use crate::nim::{Nim, Player};
+        use crate::tree::state_memory::GetState;
+        use crate::tree::Tree;
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let config = RootParallelConfig {
+            n_trees: 4,
+            n_steps: 100,
+            include_nodes: false,
+        };
+        let make_tree = |i| {
+            assert!(i != 2, "cannot make tree");
+            let game = Nim {
+                max_move: 3,
+                rng: Mutex::new(StdRng::seed_from_u64(i as u64)),
+            };
+            Tree::new(game, GetState, Player::P1, 20)
+        };
+        // the panic is propagated instead of waiting for the missing report
+        search_root_parallel(&config, make_tree, &LocalTransport::new());
+    }
+}
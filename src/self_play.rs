@@ -0,0 +1,310 @@
+// `SelfPlay` plays complete games with a single `SearchTree` per game (or a fresh tree per move if
+// trees are not reused): it searches the root for the configured budget, chooses an action, and
+// applies it until the root is terminal.  Trees are created by a user supplied closure since the
+// `SearchTree` trait has no constructor.  Moves chosen with a temperature are sampled from the
+// visit counts of the root's edges using the driver's own seeded generator, so a game only depends
+// on the seed if the search itself is deterministic (see `ReproducibleSearch`).
+
+use crate::async_search::{expand_root, search, SearchBudget};
+use crate::game_dynamics::GameDynamics;
+use crate::tree::{SearchTree, Status};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use std::time::{Duration, Instant};
+
+/// The parameters of a [`SelfPlay`] driver.
+#[derive(Debug, Clone)]
+pub struct SelfPlayConfig {
+    /// The search performed before each move.
+    pub budget: SearchBudget,
+    /// The number of threads stepping the tree.
+    pub n_threads: usize,
+    /// Whether the subtree below the chosen action is kept for the next move (otherwise a new tree
+    /// is created for each move).
+    pub reuse_tree: bool,
+    /// The temperature used to choose the first `temperature_moves` moves: an action is sampled
+    /// with a probability proportional to `visits^(1 / temperature)` of its edge.  A temperature of
+    /// zero always chooses [`SearchTree::best_action`].
+    pub temperature: f64,
+    /// The number of moves at the start of each game that are chosen with `temperature`; later
+    /// moves are chosen via [`SearchTree::best_action`].
+    pub temperature_moves: usize,
+    /// The maximum number of moves per game (a game that reaches the limit is not terminal).
+    pub max_moves: Option<usize>,
+}
+
+impl Default for SelfPlayConfig {
+    fn default() -> Self {
+        Self {
+            budget: SearchBudget::Steps(100),
+            n_threads: 1,
+            reuse_tree: true,
+            temperature: 1.0,
+            temperature_moves: 0,
+            max_moves: None,
+        }
+    }
+}
+
+/// A move of a game played by [`SelfPlay`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MoveRecord<P, A> {
+    /// The player taking the action.
+    pub player: P,
+    /// The action applied.
+    pub action: A,
+    /// `true` if the action was sampled with a temperature rather than chosen via
+    /// [`SearchTree::best_action`].
+    pub sampled: bool,
+    /// The number of steps performed to choose the action.
+    pub n_steps: usize,
+    /// The time spent searching and choosing the action.
+    pub elapsed: Duration,
+}
+
+/// A game played by [`SelfPlay`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord<S, P, A, Q> {
+    /// The moves in the order in which they were played.
+    pub moves: Vec<MoveRecord<P, A>>,
+    /// The player at the final root, i.e. the player to move when the game ended.
+    pub final_player: P,
+    /// The state of the final root, from which the outcome of the game can be determined.
+    pub final_state: S,
+    /// The score of the final root.
+    pub final_score: Option<Q>,
+    /// `true` if the game ended in a terminal state, `false` if it was stopped after
+    /// [`SelfPlayConfig::max_moves`] moves.
+    pub terminal: bool,
+    /// The duration of the game.
+    pub elapsed: Duration,
+}
+
+/// A driver that plays complete games of a [`SearchTree`] against itself, e.g. to tune the
+/// parameters of a [`GameDynamics`] implementation or to generate training data (see also
+/// [`TraceRecorder`](crate::TraceRecorder)).
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use std::sync::Mutex;
+///
+/// let mut self_play = SelfPlay::new(
+///     SelfPlayConfig {
+///         budget: SearchBudget::Steps(50),
+///         temperature_moves: 4,
+///         ..Default::default()
+///     },
+///     0,
+/// );
+/// let make_tree = |player, state| {
+///     let game = nim::Nim {
+///         max_move: 3,
+///         rng: Mutex::new(rand::SeedableRng::seed_from_u64(0)),
+///     };
+///     Tree::new(game, GetState, player, state)
+/// };
+/// let game = self_play.play(make_tree, nim::Player::P1, 20);
+/// assert!(game.terminal);
+/// assert_eq!(game.final_state, 0);
+/// ```
+#[derive(Debug)]
+pub struct SelfPlay {
+    config: SelfPlayConfig,
+    rng: StdRng,
+}
+
+impl SelfPlay {
+    /// Construct a new `SelfPlay` driver; moves chosen with a temperature are sampled with a
+    /// generator seeded with `seed`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.n_threads` is zero or `config.temperature` is negative.
+    pub fn new(config: SelfPlayConfig, seed: u64) -> Self {
+        assert!(config.n_threads > 0, "at least one thread is required");
+        assert!(
+            config.temperature >= 0.0,
+            "the temperature must not be negative"
+        );
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Returns the configuration of the driver.
+    pub fn config(&self) -> &SelfPlayConfig {
+        &self.config
+    }
+
+    /// Plays a game starting with `player` to move in `state`.  Trees are created via
+    /// `make_tree(player, state)`: once for the game if trees are reused, otherwise once for every
+    /// move.
+    #[allow(clippy::type_complexity)]
+    pub fn play<T, F>(
+        &mut self,
+        mut make_tree: F,
+        player: <T::GD as GameDynamics>::Player,
+        state: <T::GD as GameDynamics>::State,
+    ) -> GameRecord<
+        <T::GD as GameDynamics>::State,
+        <T::GD as GameDynamics>::Player,
+        <T::GD as GameDynamics>::Action,
+        <T::GD as GameDynamics>::Score,
+    >
+    where
+        F: FnMut(<T::GD as GameDynamics>::Player, <T::GD as GameDynamics>::State) -> T,
+        T: SearchTree + Sync,
+        <T::GD as GameDynamics>::State: Clone,
+        <T::GD as GameDynamics>::Player: Clone,
+        <T::GD as GameDynamics>::Action: Clone,
+        <T::GD as GameDynamics>::Score: Clone,
+    {
+        let start = Instant::now();
+        let mut tree = make_tree(player, state);
+        let mut moves = Vec::new();
+        let terminal = loop {
+            if self.config.max_moves.into_iter().any(|n| moves.len() >= n) {
+                break false;
+            }
+            let t0 = Instant::now();
+            let n_steps = search(&tree, self.config.budget, self.config.n_threads);
+            let sample = moves.len() < self.config.temperature_moves;
+            let (action, sampled) = match self.choose(&tree, sample) {
+                Some(choice) => choice,
+                None => break true,
+            };
+            let player = tree.get_root_info().player;
+            tree.apply_action(&action);
+            moves.push(MoveRecord {
+                player,
+                action,
+                sampled,
+                n_steps,
+                elapsed: t0.elapsed(),
+            });
+            if !self.config.reuse_tree {
+                let root = tree.get_root_info();
+                let state = root.state.expect("the root has a state");
+                tree = make_tree(root.player, state);
+            }
+        };
+
+        let root = tree.get_root_info();
+        GameRecord {
+            moves,
+            final_player: root.player,
+            final_state: root.state.expect("the root has a state"),
+            final_score: root.score,
+            terminal,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    // Returns the action to apply at the root and whether it was sampled with the temperature, or
+    // `None` if the root is terminal
+    fn choose<T>(
+        &mut self,
+        tree: &T,
+        sample: bool,
+    ) -> Option<(<T::GD as GameDynamics>::Action, bool)>
+    where
+        T: SearchTree,
+        <T::GD as GameDynamics>::Score: Clone,
+    {
+        let best = match expand_root(tree) {
+            Status::Action(a) | Status::ActionWip(a) => a,
+            _ => return None,
+        };
+        if !sample || self.config.temperature == 0.0 {
+            return Some((best, false));
+        }
+        match tree.sample_action(self.config.temperature, &mut self.rng) {
+            Status::Action(a) | Status::ActionWip(a) => Some((a, true)),
+            _ => Some((best, false)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nim::{Nim, Player};
+    use crate::tree::state_memory::GetState;
+    use crate::tree::Tree;
+
+    use std::sync::Mutex;
+
+    fn make_tree(player: Player, state: usize) -> impl SearchTree<GD = Nim> + Sync {
+        let game = Nim {
+            max_move: 3,
+            rng: Mutex::new(StdRng::seed_from_u64(0)),
+        };
+        Tree::new(game, GetState, player, state)
+    }
+
+    #[test]
+    fn test_play() {
+        for &reuse_tree in &[true, false] {
+            let mut self_play = SelfPlay::new(
+                SelfPlayConfig {
+                    budget: SearchBudget::Steps(30),
+                    n_threads: 2,
+                    reuse_tree,
+                    temperature_moves: 3,
+                    ..Default::default()
+                },
+                0,
+            );
+            let game = self_play.play(make_tree, Player::P1, 20);
+            assert!(game.terminal);
+            assert_eq!(game.final_state, 0);
+            assert_eq!(game.moves.iter().map(|m| m.action).sum::<usize>(), 20);
+            assert!(game.moves.iter().take(3).all(|m| m.sampled));
+            assert!(game.moves.iter().skip(3).all(|m| !m.sampled));
+            assert!(game.moves.iter().all(|m| m.n_steps == 30));
+            let players = game.moves.iter().map(|m| m.player.clone());
+            assert!(players
+                .zip(game.moves.iter().skip(1).map(|m| m.player.clone()))
+                .all(|(p, q)| p != q));
+        }
+    }
+
+    #[test]
+    fn test_zero_temperature() {
+        // a temperature of zero chooses the best action, so no move counts as sampled
+        let mut self_play = SelfPlay::new(
+            SelfPlayConfig {
+                budget: SearchBudget::Steps(30),
+                temperature: 0.0,
+                temperature_moves: 3,
+                ..Default::default()
+            },
+            0,
+        );
+        let game = self_play.play(make_tree, Player::P1, 20);
+        assert!(game.terminal);
+        assert!(game.moves.iter().all(|m| !m.sampled));
+    }
+
+    #[test]
+    fn test_max_moves() {
+        let mut self_play = SelfPlay::new(
+            SelfPlayConfig {
+                budget: SearchBudget::Steps(0),
+                max_moves: Some(2),
+                ..Default::default()
+            },
+            0,
+        );
+        let game = self_play.play(make_tree, Player::P1, 20);
+        assert!(!game.terminal);
+        assert_eq!(game.moves.len(), 2);
+        assert_eq!(game.final_player, Player::P1);
+    }
+}
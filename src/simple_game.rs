@@ -0,0 +1,419 @@
+// `Uct` adapts a `SimpleGame` to `GameDynamics`.  The score of a node holds the number of playouts
+// (rollouts or heuristic evaluations) below the node and the sum of their rewards per player;
+// `backprop_scores` recomputes a node's score as the sum of its children's scores, so the score of
+// a node with several parents counts towards each of them.  Children are created and scored when
+// their parent is expanded, i.e. every child has been played out once before it is first selected.
+// The exploration bonus uses the visit counts of the edges rather than the number of playouts since
+// selecting a terminal child does not change any score.
+
+use crate::game_dynamics::{EdgeStats, GameDynamics, SelectNodeState};
+use crate::reproducible::with_search_rng;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use std::ops::Deref;
+
+/// A simpler alternative to implementing [`GameDynamics`]: implement the rules of the game via
+/// `SimpleGame` and search it with the [`Uct`] adapter, which provides UCT selection, random
+/// rollouts and the backup of the rewards.
+///
+/// The players take turns in the order given by [`SimpleGame::next_player`].  The game must end
+/// after a finite number of actions from any state.
+pub trait SimpleGame {
+    /// The players of the game, e.g. an enum (or `()` for a one player game).
+    type Player: Clone + PartialEq;
+    /// The state of the game (see [`GameDynamics::State`]).
+    type State: Clone;
+    /// An action that transitions from one state to another.
+    type Action: Clone;
+
+    /// Returns the actions available to `player` in `state`, or an empty `Vec` if the game is
+    /// over.
+    fn legal_actions(&self, player: &Self::Player, state: &Self::State) -> Vec<Self::Action>;
+
+    /// Returns the state after applying `action` to `state`.
+    fn apply(&self, state: Self::State, action: &Self::Action) -> Self::State;
+
+    /// Returns the player to move after `player`.
+    fn next_player(&self, player: &Self::Player) -> Self::Player;
+
+    /// Returns the reward of every player at the end of the game, where `player` is the player to
+    /// move in the final `state`.  Players that are not included receive a reward of zero.
+    ///
+    /// Rewards should be in `[0, 1]` (e.g. 1 for a win, 0.5 for a draw, and 0 for a loss) so that
+    /// they are on the scale of the exploration bonus of [`Uct`].
+    fn reward(&self, player: &Self::Player, state: &Self::State) -> Vec<(Self::Player, f64)>;
+
+    /// Returns an estimate of [`SimpleGame::reward`] for a state where the game is not over yet,
+    /// or `None` to estimate the rewards via a random rollout to the end of the game.
+    ///
+    /// The default implementation returns `None`.
+    #[allow(unused_variables)]
+    fn heuristic(
+        &self,
+        player: &Self::Player,
+        state: &Self::State,
+    ) -> Option<Vec<(Self::Player, f64)>> {
+        None
+    }
+}
+
+/// The score of a node of a [`SimpleGame`] searched via [`Uct`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UctScore<P> {
+    /// The number of playouts (rollouts or heuristic evaluations) below the node.
+    pub playouts: usize,
+    /// The sum of the rewards of the playouts for each player.
+    pub rewards: Vec<(P, f64)>,
+}
+
+impl<P: PartialEq> UctScore<P> {
+    /// Returns the mean reward of `player`, or zero if there are no playouts.
+    pub fn mean(&self, player: &P) -> f64 {
+        if self.playouts == 0 {
+            return 0.0;
+        }
+        let total = self
+            .rewards
+            .iter()
+            .find(|(p, _)| p == player)
+            .map(|(_, r)| *r)
+            .unwrap_or(0.0);
+        total / self.playouts as f64
+    }
+
+    fn add(&mut self, other: &Self)
+    where
+        P: Clone,
+    {
+        self.playouts += other.playouts;
+        for (player, reward) in other.rewards.iter() {
+            match self.rewards.iter_mut().find(|(p, _)| p == player) {
+                Some((_, total)) => *total += reward,
+                None => self.rewards.push((player.clone(), *reward)),
+            }
+        }
+    }
+}
+
+/// Implements [`GameDynamics`] for a [`SimpleGame`] with UCT selection:
+///
+/// `mean(a) + c * sqrt(ln(N + 1) / (N(a) + 1))`
+///
+/// where `mean(a)` is the mean reward of the player to move over the playouts below the child
+/// reached via action `a`, `N(a)` is the number of times the edge was traversed, `N` is the sum of
+/// `N(a)` over all children, and `c` is the exploration constant.  The best action is the one
+/// that was traversed most often (ties are broken by the mean reward).
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+///
+/// // the players take turns to take 1 to 3 items; whoever takes the last item wins
+/// struct Take;
+///
+/// impl SimpleGame for Take {
+///     type Player = u8;
+///     type State = u32;
+///     type Action = u32;
+///
+///     fn legal_actions(&self, _player: &u8, state: &u32) -> Vec<u32> {
+///         (1..=3).filter(|a| a <= state).collect()
+///     }
+///
+///     fn apply(&self, state: u32, action: &u32) -> u32 {
+///         state - action
+///     }
+///
+///     fn next_player(&self, player: &u8) -> u8 {
+///         1 - player
+///     }
+///
+///     fn reward(&self, player: &u8, _state: &u32) -> Vec<(u8, f64)> {
+///         // the player to move once no items are left has lost
+///         vec![(1 - player, 1.0)]
+///     }
+/// }
+///
+/// let tree = Tree::new(Uct::new(Take), GetState, 0, 10);
+/// for _ in 0..1000 {
+///     tree.step();
+/// }
+/// // taking 2 items leaves a multiple of 4 to the opponent
+/// assert!(matches!(tree.best_action(), Status::Action(2)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Uct<G> {
+    game: G,
+    exploration: f64,
+}
+
+impl<G> Uct<G> {
+    /// Construct a new `Uct` adapter with an exploration constant of `sqrt(2)`.
+    pub fn new(game: G) -> Self {
+        Self {
+            game,
+            exploration: std::f64::consts::SQRT_2,
+        }
+    }
+
+    /// Sets the exploration constant `c`.
+    pub fn with_exploration(mut self, exploration: f64) -> Self {
+        self.exploration = exploration;
+        self
+    }
+
+    /// Returns the game.
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+
+    // Returns the action with the highest UCT value (or that was traversed most often) given the
+    // mean reward of the player to move and the number of visits of each child
+    fn select<I, A>(&self, purpose: SelectNodeState, children: I) -> A
+    where
+        I: Iterator<Item = (Option<f64>, usize, A)>,
+    {
+        let children = children.collect::<Vec<_>>();
+        let n_total = children.iter().map(|(_, n, _)| n).sum::<usize>() as f64;
+        let key = |mean: Option<f64>, n: usize| match purpose {
+            // unscored children are tried first
+            SelectNodeState::Explore => match mean {
+                Some(mean) => (
+                    mean + self.exploration * ((n_total + 1.0).ln() / (n as f64 + 1.0)).sqrt(),
+                    0.0,
+                ),
+                None => (f64::INFINITY, 0.0),
+            },
+            SelectNodeState::Exploit => (n as f64, mean.unwrap_or(f64::NEG_INFINITY)),
+        };
+        children
+            .into_iter()
+            .map(|(mean, n, a)| (key(mean, n), a))
+            .max_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap())
+            .map(|(_, a)| a)
+            .expect("a node with children")
+    }
+}
+
+impl<G: SimpleGame> Uct<G> {
+    // Plays random actions until the game is over (or the heuristic provides an estimate)
+    fn playout(&self, mut player: G::Player, mut state: G::State) -> UctScore<G::Player> {
+        let rewards = loop {
+            let actions = self.game.legal_actions(&player, &state);
+            if actions.is_empty() {
+                break self.game.reward(&player, &state);
+            }
+            if let Some(rewards) = self.game.heuristic(&player, &state) {
+                break rewards;
+            }
+            let i = with_search_rng(|rng| rng.gen_range(0, actions.len()));
+            state = self.game.apply(state, &actions[i]);
+            player = self.game.next_player(&player);
+        };
+        UctScore {
+            playouts: 1,
+            rewards,
+        }
+    }
+}
+
+impl<G: SimpleGame> GameDynamics for Uct<G> {
+    type Player = G::Player;
+    type State = G::State;
+    type Action = G::Action;
+    type Score = UctScore<G::Player>;
+    type ActionIter = Vec<(G::Player, G::Action)>;
+
+    fn available_actions(
+        &self,
+        player: &Self::Player,
+        state: &Self::State,
+    ) -> Option<Self::ActionIter> {
+        let actions = self.game.legal_actions(player, state);
+        if actions.is_empty() {
+            return None;
+        }
+        let next_player = self.game.next_player(player);
+        Some(
+            actions
+                .into_iter()
+                .map(|a| (next_player.clone(), a))
+                .collect(),
+        )
+    }
+
+    fn apply_action(&self, state: Self::State, action: &Self::Action) -> Option<Self::State> {
+        Some(self.game.apply(state, action))
+    }
+
+    fn select_node<II, Q, A>(
+        &self,
+        _parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        _parent_node_state: &Self::State,
+        purpose: SelectNodeState,
+        scores_and_actions: II,
+    ) -> Self::Action
+    where
+        Self: Sized,
+        II: Clone + IntoIterator<Item = (Q, A)>,
+        Q: Deref<Target = Option<Self::Score>>,
+        A: Deref<Target = Self::Action>,
+    {
+        let children = scores_and_actions.into_iter().map(|(q, a)| match &*q {
+            Some(q) => (Some(q.mean(parent_player)), q.playouts, a.clone()),
+            None => (None, 0, a.clone()),
+        });
+        self.select(purpose, children)
+    }
+
+    fn select_node_with_edges<II, Q, A>(
+        &self,
+        _parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        _parent_node_state: &Self::State,
+        purpose: SelectNodeState,
+        scores_actions_and_edges: II,
+    ) -> Self::Action
+    where
+        Self: Sized,
+        II: Clone + IntoIterator<Item = (Q, A, EdgeStats)>,
+        Q: Deref<Target = Option<Self::Score>>,
+        A: Deref<Target = Self::Action>,
+    {
+        let children = scores_actions_and_edges.into_iter().map(|(q, a, edge)| {
+            let mean = q.as_ref().map(|q| q.mean(parent_player));
+            (mean, edge.visits, a.clone())
+        });
+        self.select(purpose, children)
+    }
+
+    fn backprop_scores<II, Q>(
+        &self,
+        _player: &Self::Player,
+        score_current: Option<&Self::Score>,
+        child_scores: II,
+    ) -> Option<Self::Score>
+    where
+        Self: Sized,
+        II: Clone + IntoIterator<Item = Q>,
+        Q: Deref<Target = Self::Score>,
+    {
+        let mut score = UctScore {
+            playouts: 0,
+            rewards: Vec::new(),
+        };
+        for child in child_scores {
+            score.add(&child);
+        }
+        if score_current == Some(&score) {
+            return None;
+        }
+        Some(score)
+    }
+
+    fn score_leaf(
+        &self,
+        _parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        state: &Self::State,
+    ) -> Option<Self::Score> {
+        let player = self.game.next_player(parent_player);
+        Some(self.playout(player, state.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reproducible::ReproducibleSearch;
+    use crate::tree::state_memory::StoreState;
+    use crate::tree::{SearchTree, Status, Tree};
+
+    // Tic-tac-toe with the board encoded as 9 cells (0: empty, 1 and 2: the players)
+    struct TicTacToe;
+
+    const LINES: [[usize; 3]; 8] = [
+        [0, 1, 2],
+        [3, 4, 5],
+        [6, 7, 8],
+        [0, 3, 6],
+        [1, 4, 7],
+        [2, 5, 8],
+        [0, 4, 8],
+        [2, 4, 6],
+    ];
+
+    fn winner(board: &[u8; 9]) -> Option<u8> {
+        LINES
+            .iter()
+            .find(|l| board[l[0]] != 0 && board[l[0]] == board[l[1]] && board[l[1]] == board[l[2]])
+            .map(|l| board[l[0]])
+    }
+
+    impl SimpleGame for TicTacToe {
+        type Player = u8;
+        type State = [u8; 9];
+        type Action = usize;
+
+        fn legal_actions(&self, _player: &u8, board: &[u8; 9]) -> Vec<usize> {
+            if winner(board).is_some() {
+                return Vec::new();
+            }
+            (0..9).filter(|&i| board[i] == 0).collect()
+        }
+
+        fn apply(&self, mut board: [u8; 9], action: &usize) -> [u8; 9] {
+            let n_moves = board.iter().filter(|&&c| c != 0).count();
+            board[*action] = if n_moves % 2 == 0 { 1 } else { 2 };
+            board
+        }
+
+        fn next_player(&self, player: &u8) -> u8 {
+            3 - player
+        }
+
+        fn reward(&self, _player: &u8, board: &[u8; 9]) -> Vec<(u8, f64)> {
+            match winner(board) {
+                Some(p) => vec![(p, 1.0)],
+                None => vec![(1, 0.5), (2, 0.5)],
+            }
+        }
+    }
+
+    #[test]
+    fn test_score() {
+        let mut score = UctScore {
+            playouts: 2,
+            rewards: vec![('a', 1.0)],
+        };
+        score.add(&UctScore {
+            playouts: 2,
+            rewards: vec![('a', 0.5), ('b', 1.5)],
+        });
+        assert_eq!(score.playouts, 4);
+        assert_eq!(score.mean(&'a'), 0.375);
+        assert_eq!(score.mean(&'b'), 0.375);
+        assert_eq!(score.mean(&'c'), 0.0);
+    }
+
+    #[test]
+    fn test_tic_tac_toe() {
+        // player 2 must block the line of player 1 (cells 0 and 1)
+        let board = [1, 1, 0, 0, 2, 0, 0, 0, 0];
+        let tree = Tree::new(Uct::new(TicTacToe), StoreState, 2, board);
+        ReproducibleSearch::new(0, 1).run(&tree, 2000);
+        assert!(matches!(tree.best_action(), Status::Action(2)));
+
+        // player 1 can win immediately
+        let board = [1, 1, 0, 2, 2, 0, 0, 0, 0];
+        let tree = Tree::new(Uct::new(TicTacToe), StoreState, 1, board);
+        ReproducibleSearch::new(0, 1).run(&tree, 500);
+        assert!(matches!(tree.best_action(), Status::Action(2)));
+        let score = tree.get_root_info().score.unwrap();
+        assert!(score.playouts > 0);
+    }
+}
@@ -0,0 +1,354 @@
+// Counters are updated with `Ordering::Relaxed` throughout: they are purely informational and
+// never used to synchronize access to other data, so a snapshot taken while worker threads are
+// running may be slightly inconsistent across fields (e.g. `score_leaf_calls` may already include
+// a call whose duration has not yet been added to `score_leaf_time`).
+
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+/// Contains information about a `Tree`'s registry as well as statistics about the search.
+///
+/// All fields are cumulative since the `Tree` was created or since the last call to
+/// [`RegistryInfo::reset`], except for `len` and `memory_bytes` (which reflect the current size of
+/// the registry) and `max_depth`.  Durations are stored in nanoseconds; use
+/// [`RegistryInfo::snapshot`] to obtain a plain [`RegistryStats`] with `Duration` fields.
+pub struct RegistryInfo {
+    /// The number of times an action applied to a leaf node has resulted in a state that already
+    /// existed in the [`Tree`](crate::Tree).
+    pub hits: AtomicUsize,
+    /// The number of times an action applied to a leaf node has resulted in a state that is new in
+    /// the [`Tree`](crate::Tree).
+    pub misses: AtomicUsize,
+    /// The number of times an action applied to a leaf node has resulted in the state of one of
+    /// the leaf's (grand)*parents (see [`CycleHandling`](crate::CycleHandling)).
+    pub cycles: AtomicUsize,
+    /// The number of nodes in the [`Tree`](crate::Tree).
+    pub len: AtomicUsize,
+    /// An estimate of the number of bytes used by the nodes in the [`Tree`](crate::Tree) (see
+    /// [`MemoryHints`]).
+    pub memory_bytes: AtomicUsize,
+    /// The number of calls to [`SearchTree::step`](crate::SearchTree::step).
+    pub steps: AtomicUsize,
+    /// The number of leaf nodes that were expanded (i.e. for which
+    /// [`GameDynamics::available_actions`](crate::GameDynamics::available_actions) was called).
+    pub expansions: AtomicUsize,
+    /// The number of leaves scored by [`GameDynamics::score_leaf`](crate::GameDynamics::score_leaf)
+    /// or [`GameDynamics::score_leaves`](crate::GameDynamics::score_leaves).
+    pub score_leaf_calls: AtomicUsize,
+    /// The cumulative time spent in `GameDynamics::score_leaf` and `GameDynamics::score_leaves` in
+    /// nanoseconds.
+    pub score_leaf_nanos: AtomicU64,
+    /// The number of calls to
+    /// [`GameDynamics::backprop_scores`](crate::GameDynamics::backprop_scores).
+    pub backprop_calls: AtomicUsize,
+    /// The cumulative time spent in `GameDynamics::backprop_scores` in nanoseconds.
+    pub backprop_nanos: AtomicU64,
+    /// The number of parent nodes queued for a score update during backpropagation, i.e. the
+    /// cumulative fan-out of the backpropagation heap.
+    pub backprop_fanout: AtomicUsize,
+    /// The maximum depth of any node created in the [`Tree`](crate::Tree).
+    pub max_depth: AtomicUsize,
+    /// The cumulative time spent waiting to acquire a write lock on the registry in nanoseconds.
+    pub registry_lock_wait_nanos: AtomicU64,
+    /// The cumulative time `step` spent waiting on a root move in progress in nanoseconds.
+    pub prune_lock_wait_nanos: AtomicU64,
+    /// The number of times a thread waited on another thread to finish creating a branch (i.e.
+    /// no work could be stolen).
+    pub notifier_waits: AtomicUsize,
+    /// The cumulative time spent waiting on other threads to finish creating a branch in
+    /// nanoseconds.
+    pub notifier_wait_nanos: AtomicU64,
+}
+
+impl RegistryInfo {
+    pub(crate) fn new() -> Self {
+        Self {
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            cycles: AtomicUsize::new(0),
+            len: AtomicUsize::new(0),
+            memory_bytes: AtomicUsize::new(0),
+            steps: AtomicUsize::new(0),
+            expansions: AtomicUsize::new(0),
+            score_leaf_calls: AtomicUsize::new(0),
+            score_leaf_nanos: AtomicU64::new(0),
+            backprop_calls: AtomicUsize::new(0),
+            backprop_nanos: AtomicU64::new(0),
+            backprop_fanout: AtomicUsize::new(0),
+            max_depth: AtomicUsize::new(0),
+            registry_lock_wait_nanos: AtomicU64::new(0),
+            prune_lock_wait_nanos: AtomicU64::new(0),
+            notifier_waits: AtomicUsize::new(0),
+            notifier_wait_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns a copy of the current values of all counters.
+    pub fn snapshot(&self) -> RegistryStats {
+        let nanos = |x: &AtomicU64| Duration::from_nanos(x.load(Ordering::Relaxed));
+        RegistryStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            cycles: self.cycles.load(Ordering::Relaxed),
+            len: self.len.load(Ordering::Relaxed),
+            memory_bytes: self.memory_bytes.load(Ordering::Relaxed),
+            steps: self.steps.load(Ordering::Relaxed),
+            expansions: self.expansions.load(Ordering::Relaxed),
+            score_leaf_calls: self.score_leaf_calls.load(Ordering::Relaxed),
+            score_leaf_time: nanos(&self.score_leaf_nanos),
+            backprop_calls: self.backprop_calls.load(Ordering::Relaxed),
+            backprop_time: nanos(&self.backprop_nanos),
+            backprop_fanout: self.backprop_fanout.load(Ordering::Relaxed),
+            max_depth: self.max_depth.load(Ordering::Relaxed),
+            registry_lock_wait: nanos(&self.registry_lock_wait_nanos),
+            prune_lock_wait: nanos(&self.prune_lock_wait_nanos),
+            notifier_waits: self.notifier_waits.load(Ordering::Relaxed),
+            notifier_wait: nanos(&self.notifier_wait_nanos),
+        }
+    }
+
+    /// Resets all cumulative counters to zero and returns their values prior to the reset.  `len`,
+    /// `memory_bytes` and `max_depth` are left unchanged since they describe the current shape of
+    /// the `Tree`.
+    pub fn reset(&self) -> RegistryStats {
+        let swap = |x: &AtomicUsize| x.swap(0, Ordering::Relaxed);
+        let swap_nanos = |x: &AtomicU64| Duration::from_nanos(x.swap(0, Ordering::Relaxed));
+        RegistryStats {
+            hits: swap(&self.hits),
+            misses: swap(&self.misses),
+            cycles: swap(&self.cycles),
+            len: self.len.load(Ordering::Relaxed),
+            memory_bytes: self.memory_bytes.load(Ordering::Relaxed),
+            steps: swap(&self.steps),
+            expansions: swap(&self.expansions),
+            score_leaf_calls: swap(&self.score_leaf_calls),
+            score_leaf_time: swap_nanos(&self.score_leaf_nanos),
+            backprop_calls: swap(&self.backprop_calls),
+            backprop_time: swap_nanos(&self.backprop_nanos),
+            backprop_fanout: swap(&self.backprop_fanout),
+            max_depth: self.max_depth.load(Ordering::Relaxed),
+            registry_lock_wait: swap_nanos(&self.registry_lock_wait_nanos),
+            prune_lock_wait: swap_nanos(&self.prune_lock_wait_nanos),
+            notifier_waits: swap(&self.notifier_waits),
+            notifier_wait: swap_nanos(&self.notifier_wait_nanos),
+        }
+    }
+
+    // Runs `f` and adds the elapsed time to `nanos`
+    pub(crate) fn timed<T>(nanos: &AtomicU64, f: impl FnOnce() -> T) -> T {
+        let t0 = Instant::now();
+        let r = f();
+        Self::add_elapsed(nanos, t0);
+        r
+    }
+
+    pub(crate) fn add_elapsed(nanos: &AtomicU64, since: Instant) {
+        nanos.fetch_add(since.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Estimates of the number of bytes that values of the `GameDynamics`' associated types own on the
+/// heap, used to estimate the memory used by a `Tree` (see [`RegistryInfo::memory_bytes`] and
+/// [`Tree::with_memory_hints`](crate::Tree::with_memory_hints)).
+///
+/// The estimate of a node's memory consists of the fixed size of the node (including its entry in
+/// the registry and the edges leading to it) plus the hints; e.g. a `State` that is a `Vec<u8>`
+/// of 64 elements owns 64 bytes on the heap in addition to the `size_of` the `Vec` itself, which
+/// is already part of the fixed size.  All hints default to zero.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryHints {
+    /// The heap bytes owned by a `State`; only charged for nodes that store their state (see
+    /// [`state_memory`](crate::state_memory)).
+    pub state: usize,
+    /// The heap bytes owned by a `Score`.
+    pub score: usize,
+    /// The heap bytes owned by an `Action`; charged twice per edge (the action is stored by the
+    /// parent and the child).
+    pub action: usize,
+    /// The bytes owned by the `ActionIter` of a node whose children are being created; only
+    /// charged until all children of the node are created.
+    pub action_iter: usize,
+}
+
+/// A point-in-time copy of the counters in [`RegistryInfo`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegistryStats {
+    /// See [`RegistryInfo::hits`].
+    pub hits: usize,
+    /// See [`RegistryInfo::misses`].
+    pub misses: usize,
+    /// See [`RegistryInfo::cycles`].
+    pub cycles: usize,
+    /// See [`RegistryInfo::len`].
+    pub len: usize,
+    /// See [`RegistryInfo::memory_bytes`].
+    pub memory_bytes: usize,
+    /// See [`RegistryInfo::steps`].
+    pub steps: usize,
+    /// See [`RegistryInfo::expansions`].
+    pub expansions: usize,
+    /// See [`RegistryInfo::score_leaf_calls`].
+    pub score_leaf_calls: usize,
+    /// See [`RegistryInfo::score_leaf_nanos`].
+    pub score_leaf_time: Duration,
+    /// See [`RegistryInfo::backprop_calls`].
+    pub backprop_calls: usize,
+    /// See [`RegistryInfo::backprop_nanos`].
+    pub backprop_time: Duration,
+    /// See [`RegistryInfo::backprop_fanout`].
+    pub backprop_fanout: usize,
+    /// See [`RegistryInfo::max_depth`].
+    pub max_depth: usize,
+    /// See [`RegistryInfo::registry_lock_wait_nanos`].
+    pub registry_lock_wait: Duration,
+    /// See [`RegistryInfo::prune_lock_wait_nanos`].
+    pub prune_lock_wait: Duration,
+    /// See [`RegistryInfo::notifier_waits`].
+    pub notifier_waits: usize,
+    /// See [`RegistryInfo::notifier_wait_nanos`].
+    pub notifier_wait: Duration,
+}
+
+impl RegistryStats {
+    /// Writes the statistics in the [Prometheus text
+    /// format](https://prometheus.io/docs/instrumenting/exposition_formats/).  Each metric name is
+    /// prepended with `prefix` (e.g. `"recon_mcts"` results in `recon_mcts_steps_total`).
+    pub fn write_prometheus(&self, prefix: &str, w: &mut impl Write) -> fmt::Result {
+        let counters = [
+            (
+                "registry_hits_total",
+                "Registry hits (transpositions).",
+                self.hits as f64,
+            ),
+            (
+                "registry_misses_total",
+                "Registry misses (new nodes).",
+                self.misses as f64,
+            ),
+            (
+                "cycles_total",
+                "Actions leading back to an ancestor's state.",
+                self.cycles as f64,
+            ),
+            ("steps_total", "Calls to step.", self.steps as f64),
+            (
+                "expansions_total",
+                "Leaf nodes expanded.",
+                self.expansions as f64,
+            ),
+            (
+                "score_leaf_calls_total",
+                "Calls to score_leaf.",
+                self.score_leaf_calls as f64,
+            ),
+            (
+                "score_leaf_seconds_total",
+                "Time spent in score_leaf.",
+                self.score_leaf_time.as_secs_f64(),
+            ),
+            (
+                "backprop_calls_total",
+                "Calls to backprop_scores.",
+                self.backprop_calls as f64,
+            ),
+            (
+                "backprop_seconds_total",
+                "Time spent in backprop_scores.",
+                self.backprop_time.as_secs_f64(),
+            ),
+            (
+                "backprop_fanout_total",
+                "Parent nodes queued during backpropagation.",
+                self.backprop_fanout as f64,
+            ),
+            (
+                "registry_lock_wait_seconds_total",
+                "Time spent waiting on the registry lock.",
+                self.registry_lock_wait.as_secs_f64(),
+            ),
+            (
+                "prune_lock_wait_seconds_total",
+                "Time spent waiting on root moves.",
+                self.prune_lock_wait.as_secs_f64(),
+            ),
+            (
+                "notifier_waits_total",
+                "Waits on branches created by other threads.",
+                self.notifier_waits as f64,
+            ),
+            (
+                "notifier_wait_seconds_total",
+                "Time spent waiting on branches created by other threads.",
+                self.notifier_wait.as_secs_f64(),
+            ),
+        ];
+        let gauges = [
+            ("nodes", "Nodes in the registry.", self.len as f64),
+            (
+                "memory_bytes",
+                "Estimated memory used by the nodes.",
+                self.memory_bytes as f64,
+            ),
+            ("max_depth", "Maximum node depth.", self.max_depth as f64),
+        ];
+
+        for (kind, metrics) in &[("counter", &counters[..]), ("gauge", &gauges[..])] {
+            for (name, help, value) in metrics.iter() {
+                writeln!(w, "# HELP {}_{} {}", prefix, name, help)?;
+                writeln!(w, "# TYPE {}_{} {}", prefix, name, kind)?;
+                writeln!(w, "{}_{} {}", prefix, name, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the statistics in the Prometheus text format.  See
+    /// [`RegistryStats::write_prometheus`].
+    pub fn to_prometheus(&self, prefix: &str) -> String {
+        let mut s = String::new();
+        self.write_prometheus(prefix, &mut s)
+            .expect("writing to a `String` does not fail");
+        s
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_reset() {
+        let info = RegistryInfo::new();
+        info.steps.fetch_add(3, Ordering::Relaxed);
+        info.len.store(7, Ordering::Relaxed);
+        info.memory_bytes.store(100, Ordering::Relaxed);
+        RegistryInfo::timed(&info.score_leaf_nanos, || {
+            std::thread::sleep(Duration::from_millis(1))
+        });
+
+        let s = info.reset();
+        assert_eq!(s.steps, 3);
+        assert_eq!(s.len, 7);
+        assert_eq!(s.memory_bytes, 100);
+        assert!(s.score_leaf_time >= Duration::from_millis(1));
+
+        let s = info.snapshot();
+        assert_eq!(s.steps, 0);
+        assert_eq!(s.len, 7);
+        assert_eq!(s.score_leaf_time, Duration::from_nanos(0));
+    }
+
+    #[test]
+    fn test_prometheus() {
+        let stats = RegistryStats {
+            steps: 5,
+            len: 2,
+            ..Default::default()
+        };
+        let text = stats.to_prometheus("mcts");
+        assert!(text.contains("# TYPE mcts_steps_total counter\nmcts_steps_total 5\n"));
+        assert!(text.contains("# TYPE mcts_nodes gauge\nmcts_nodes 2\n"));
+    }
+}
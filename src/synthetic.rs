@@ -0,0 +1,534 @@
+// A synthetic two-player game on a random DAG, used to stress the concurrent parts of the `Tree`
+// with shapes that Nim cannot produce.  A state is identified by its depth and an id; the
+// children of a state, whether it is terminal, and its values are derived by hashing the id
+// together with the seed, so the game is deterministic and needs no storage.  A child id is
+// either drawn from a small pool shared by all states of the next depth (a transposition) or
+// from the full range of `u64` (a state that is almost certainly new).  Since every action
+// increases the depth, the graph is acyclic, and the smallest number of actions from the initial
+// state to any state equals its depth.  The exact minimax value of every state can be computed
+// by recursion, which is what the search is verified against.
+
+use crate::game_dynamics::{EdgeStats, GameDynamics, SelectNodeState};
+use crate::reproducible::with_search_rng;
+
+use rand::Rng;
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::time::Duration;
+
+/// The parameters of a [`SyntheticGame`].
+#[derive(Debug, Clone)]
+pub struct SyntheticConfig {
+    /// The number of actions available at each non-terminal state.
+    pub branching: usize,
+    /// The maximum number of actions from the initial state; all states at this depth are
+    /// terminal.
+    pub max_depth: usize,
+    /// The probability that the child reached by an action is drawn from a pool of
+    /// `transposition_pool` states shared by all states of the same depth.
+    pub transposition_rate: f64,
+    /// The number of states per depth that transpositions are drawn from.
+    pub transposition_pool: u64,
+    /// The probability that a state below the maximum depth (other than the initial state) is
+    /// terminal.
+    pub terminal_rate: f64,
+    /// The time [`GameDynamics::score_leaf`] sleeps before returning, to simulate an expensive
+    /// evaluator.
+    pub latency: Duration,
+    /// The probability that [`GameDynamics::score_leaf`] panics for a state (the same states
+    /// panic every time).  A panic poisons the locks of the tree being searched, so the other
+    /// threads stepping the tree panic as well (rather than waiting for the failed step) and the
+    /// tree can no longer be used.
+    pub panic_rate: f64,
+    /// The seed from which the graph and the values of the states are derived.
+    pub seed: u64,
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> Self {
+        Self {
+            branching: 3,
+            max_depth: 6,
+            transposition_rate: 0.5,
+            transposition_pool: 8,
+            terminal_rate: 0.1,
+            latency: Duration::from_secs(0),
+            panic_rate: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/// The player to move in a [`SyntheticGame`]; `Max` moves at even depths and `Min` at odd
+/// depths.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Side {
+    /// The player maximizing the score.
+    Max,
+    /// The player minimizing the score.
+    Min,
+}
+
+impl Side {
+    fn at_depth(depth: usize) -> Self {
+        if depth % 2 == 0 {
+            Side::Max
+        } else {
+            Side::Min
+        }
+    }
+}
+
+/// A state of a [`SyntheticGame`].
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct SyntheticState {
+    /// The number of actions from the initial state.
+    pub depth: usize,
+    /// The id of the state among the states of the same depth.
+    pub id: u64,
+}
+
+/// A configurable two-player zero-sum game on a random DAG (see [`SyntheticConfig`]) with
+/// known optimal values, intended for stress tests of the search (e.g. registry lookups racing
+/// on transpositions, branches created by several threads, and depth updates).
+///
+/// Scores are minimax values in `[-1, 1]` from the perspective of [`Side::Max`]: terminal states
+/// score `-1`, `0` or `1`, other leaves are scored by a heuristic in `(-1, 1)`, and parents are
+/// scored with the best child score for the player to move.  Once the [`Tree`](crate::Tree) is
+/// fully expanded, the root's score equals [`SyntheticGame::optimal_value`].  For exploration,
+/// the least visited child is selected, so a search eventually expands every reachable state
+/// (see [`SyntheticGame::reachable_states`]).  Scores of `-1` and `1` are proven and reported
+/// as exact by [`GameDynamics::score_bounds`], so that children that lose with certainty can be
+/// pruned (see [`Tree::with_dominance_pruning`](crate::Tree::with_dominance_pruning)).
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+///
+/// let game = SyntheticGame::new(SyntheticConfig::default());
+/// let (player, state) = game.initial();
+/// let optimal = game.optimal_value(&state);
+/// let n_states = game.reachable_states(&state);
+///
+/// let tree = Tree::new(game, GetState, player, state);
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| for _ in 0..5000 { tree.step(); });
+///     }
+/// });
+/// let n_nodes = tree.get_registry_info().len.load(std::sync::atomic::Ordering::Relaxed);
+/// assert_eq!(n_nodes, n_states);
+/// assert_eq!(tree.get_root_info().score, Some(optimal));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SyntheticGame {
+    config: SyntheticConfig,
+}
+
+// SplitMix64 (https://prng.di.unimi.it/splitmix64.c)
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Salts separating the independent quantities derived from a state
+const CHILD: u64 = 1;
+const TRANSPOSITION: u64 = 2;
+const TERMINAL: u64 = 3;
+const VALUE: u64 = 4;
+const PANIC: u64 = 5;
+
+impl SyntheticGame {
+    /// Construct a new `SyntheticGame`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.branching` or `config.transposition_pool` is zero.
+    pub fn new(config: SyntheticConfig) -> Self {
+        assert!(
+            config.branching > 0,
+            "the branching factor must be positive"
+        );
+        assert!(
+            config.transposition_pool > 0,
+            "the transposition pool must not be empty"
+        );
+        Self { config }
+    }
+
+    /// Returns the configuration of the game.
+    pub fn config(&self) -> &SyntheticConfig {
+        &self.config
+    }
+
+    /// Returns the player to move in the initial state and the initial state.
+    pub fn initial(&self) -> (Side, SyntheticState) {
+        (Side::Max, SyntheticState { depth: 0, id: 0 })
+    }
+
+    fn hash(&self, state: &SyntheticState, salt: u64, x: u64) -> u64 {
+        let h = mix(self.config.seed ^ mix(salt));
+        let h = mix(h ^ state.depth as u64);
+        let h = mix(h ^ state.id);
+        mix(h ^ x)
+    }
+
+    // Returns a number in `[0, 1)` derived from the state
+    fn uniform(&self, state: &SyntheticState, salt: u64, x: u64) -> f64 {
+        (self.hash(state, salt, x) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns `true` if no actions are available in `state`.
+    pub fn is_terminal(&self, state: &SyntheticState) -> bool {
+        state.depth >= self.config.max_depth
+            || (state.depth > 0 && self.uniform(state, TERMINAL, 0) < self.config.terminal_rate)
+    }
+
+    /// Returns the state reached by applying `action` (an index smaller than
+    /// [`SyntheticConfig::branching`]) to a non-terminal `state`.
+    pub fn child(&self, state: &SyntheticState, action: usize) -> SyntheticState {
+        let action = action as u64;
+        let id = if self.uniform(state, TRANSPOSITION, action) < self.config.transposition_rate {
+            self.hash(state, CHILD, action) % self.config.transposition_pool
+        } else {
+            self.hash(state, CHILD, action)
+        };
+        SyntheticState {
+            depth: state.depth + 1,
+            id,
+        }
+    }
+
+    /// Returns the value of a terminal `state`.
+    pub fn terminal_value(&self, state: &SyntheticState) -> f64 {
+        (self.hash(state, VALUE, 0) % 3) as f64 - 1.0
+    }
+
+    // A noisy estimate of the value of a non-terminal state
+    fn heuristic(&self, state: &SyntheticState) -> f64 {
+        0.9 * (2.0 * self.uniform(state, VALUE, 0) - 1.0)
+    }
+
+    /// Returns the minimax value of `state` from the perspective of [`Side::Max`].
+    pub fn optimal_value(&self, state: &SyntheticState) -> f64 {
+        self.optimal_value_memo(state, &mut HashMap::new())
+    }
+
+    fn optimal_value_memo(
+        &self,
+        state: &SyntheticState,
+        memo: &mut HashMap<SyntheticState, f64>,
+    ) -> f64 {
+        if let Some(v) = memo.get(state) {
+            return *v;
+        }
+        let v = if self.is_terminal(state) {
+            self.terminal_value(state)
+        } else {
+            let values = (0..self.config.branching)
+                .map(|a| self.optimal_value_memo(&self.child(state, a), memo));
+            match Side::at_depth(state.depth) {
+                Side::Max => values.fold(f64::NEG_INFINITY, f64::max),
+                Side::Min => values.fold(f64::INFINITY, f64::min),
+            }
+        };
+        memo.insert(*state, v);
+        v
+    }
+
+    /// Returns the actions that lead to a child whose minimax value equals the minimax value of
+    /// `state` (empty for a terminal state).
+    pub fn optimal_actions(&self, state: &SyntheticState) -> Vec<usize> {
+        if self.is_terminal(state) {
+            return Vec::new();
+        }
+        let mut memo = HashMap::new();
+        let v = self.optimal_value_memo(state, &mut memo);
+        (0..self.config.branching)
+            .filter(|a| self.optimal_value_memo(&self.child(state, *a), &mut memo) == v)
+            .collect()
+    }
+
+    /// Returns the number of distinct states reachable from `state` (including `state`).
+    pub fn reachable_states(&self, state: &SyntheticState) -> usize {
+        let mut seen = HashSet::new();
+        let mut stack = vec![*state];
+        while let Some(s) = stack.pop() {
+            if !seen.insert(s) || self.is_terminal(&s) {
+                continue;
+            }
+            stack.extend((0..self.config.branching).map(|a| self.child(&s, a)));
+        }
+        seen.len()
+    }
+
+    // Returns the action with the highest value (the first one in case of a tie)
+    fn best(values_and_actions: impl Iterator<Item = (f64, usize)>) -> usize {
+        values_and_actions
+            .fold(None, |best: Option<(f64, usize)>, (v, a)| match best {
+                Some((w, _)) if w >= v => best,
+                _ => Some((v, a)),
+            })
+            .expect("a branch has children")
+            .1
+    }
+
+    // The score of a child from the perspective of the player choosing it
+    fn value_for(player: &Side, score: Option<&f64>) -> f64 {
+        match (player, score) {
+            (Side::Max, Some(q)) => *q,
+            (Side::Min, Some(q)) => -q,
+            (_, None) => f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl GameDynamics for SyntheticGame {
+    type Player = Side;
+    type State = SyntheticState;
+    type Action = usize;
+    type Score = f64;
+    type ActionIter = Vec<(Side, usize)>;
+
+    fn available_actions(
+        &self,
+        _player: &Side,
+        state: &SyntheticState,
+    ) -> Option<Self::ActionIter> {
+        if self.is_terminal(state) {
+            None
+        } else {
+            let next = Side::at_depth(state.depth + 1);
+            Some((0..self.config.branching).map(|a| (next, a)).collect())
+        }
+    }
+
+    fn apply_action(&self, state: SyntheticState, action: &usize) -> Option<SyntheticState> {
+        if *action < self.config.branching {
+            Some(self.child(&state, *action))
+        } else {
+            None
+        }
+    }
+
+    fn select_node<II, Q, A>(
+        &self,
+        _parent_score: Option<&f64>,
+        parent_player: &Side,
+        _parent_node_state: &SyntheticState,
+        _purpose: SelectNodeState,
+        scores_and_actions: II,
+    ) -> usize
+    where
+        II: IntoIterator<Item = (Q, A)>,
+        Q: Deref<Target = Option<f64>>,
+        A: Deref<Target = usize>,
+    {
+        Self::best(
+            scores_and_actions
+                .into_iter()
+                .map(|(q, a)| (Self::value_for(parent_player, q.as_ref()), *a)),
+        )
+    }
+
+    // The least visited edge for exploration (with random tie breaking so that concurrent threads
+    // spread over the children), which expands the tree breadth first so that it is eventually
+    // expanded completely; the best score for exploitation
+    fn select_node_with_edges<II, Q, A>(
+        &self,
+        _parent_score: Option<&f64>,
+        parent_player: &Side,
+        _parent_node_state: &SyntheticState,
+        purpose: SelectNodeState,
+        scores_actions_and_edges: II,
+    ) -> usize
+    where
+        II: Clone + IntoIterator<Item = (Q, A, EdgeStats)>,
+        Q: Deref<Target = Option<f64>>,
+        A: Deref<Target = usize>,
+    {
+        match purpose {
+            SelectNodeState::Explore => with_search_rng(|rng| {
+                Self::best(
+                    scores_actions_and_edges
+                        .into_iter()
+                        .map(|(_, a, e)| (rng.gen::<f64>() - e.visits as f64, *a)),
+                )
+            }),
+            SelectNodeState::Exploit => Self::best(
+                scores_actions_and_edges
+                    .into_iter()
+                    .map(|(q, a, _)| (Self::value_for(parent_player, q.as_ref()), *a)),
+            ),
+        }
+    }
+
+    fn backprop_scores<II, Q>(
+        &self,
+        player: &Side,
+        _score_current: Option<&f64>,
+        child_scores: II,
+    ) -> Option<f64>
+    where
+        II: Clone + IntoIterator<Item = Q>,
+        Q: Deref<Target = f64>,
+    {
+        let scores = child_scores.into_iter().map(|q| *q);
+        match player {
+            Side::Max => scores.fold(None, |m: Option<f64>, q| Some(m.map_or(q, |m| m.max(q)))),
+            Side::Min => scores.fold(None, |m: Option<f64>, q| Some(m.map_or(q, |m| m.min(q)))),
+        }
+    }
+
+    fn score_leaf(
+        &self,
+        _parent_score: Option<&f64>,
+        _parent_player: &Side,
+        state: &SyntheticState,
+    ) -> Option<f64> {
+        if !self.config.latency.is_zero() {
+            std::thread::sleep(self.config.latency);
+        }
+        if self.uniform(state, PANIC, 0) < self.config.panic_rate {
+            panic!("synthetic evaluator failure at {:?}", state);
+        }
+        if self.is_terminal(state) {
+            Some(self.terminal_value(state))
+        } else {
+            Some(self.heuristic(state))
+        }
+    }
+
+    // The heuristic is in `(-1, 1)`, so a score of `-1` or `1` is the proven minimax value; any
+    // other score may still change to any value in `[-1, 1]`
+    fn score_bounds(&self, parent_player: &Side, score: &f64) -> Option<(f64, f64)> {
+        let v = Self::value_for(parent_player, Some(score));
+        if v.abs() == 1.0 {
+            Some((v, v))
+        } else {
+            Some((-1.0, 1.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{assert_game_dynamics, ConformanceConfig};
+    use crate::tree::state_memory::GetState;
+    use crate::tree::{SearchTree, Status, Tree};
+
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::Ordering;
+    use std::sync::{mpsc, Arc};
+
+    fn search<T: SearchTree + Sync>(tree: &T, n_threads: usize, n_steps: usize) {
+        std::thread::scope(|s| {
+            for _ in 0..n_threads {
+                s.spawn(|| {
+                    for _ in 0..n_steps {
+                        tree.step();
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_conformance() {
+        for seed in 0..5 {
+            let game = SyntheticGame::new(SyntheticConfig {
+                seed,
+                ..Default::default()
+            });
+            let (player, state) = game.initial();
+            assert_game_dynamics(&game, player, state, &ConformanceConfig::default());
+        }
+    }
+
+    #[test]
+    fn test_optimal_value() {
+        for seed in 0..10 {
+            let game = SyntheticGame::new(SyntheticConfig {
+                branching: 4,
+                max_depth: 5,
+                transposition_rate: 0.7,
+                transposition_pool: 4,
+                seed,
+                ..Default::default()
+            });
+            let (player, state) = game.initial();
+            let optimal = game.optimal_value(&state);
+            let optimal_actions = game.optimal_actions(&state);
+            let n_states = game.reachable_states(&state);
+            assert!(!optimal_actions.is_empty());
+
+            let tree = Tree::new(game, GetState, player, state);
+            search(&tree, 4, 3000);
+            let info = tree.get_registry_info();
+            assert_eq!(info.len.load(Ordering::Relaxed), n_states);
+            assert!(info.hits.load(Ordering::Relaxed) > 0);
+            assert_eq!(tree.get_root_info().score, Some(optimal));
+            assert!(
+                matches!(tree.best_action(), Status::Action(a) if optimal_actions.contains(&a))
+            );
+
+            // the depth of a node is its smallest distance from the root, i.e. the state's depth
+            let mut stack = vec![tree.root_cursor()];
+            while let Some(cursor) = stack.pop() {
+                assert_eq!(cursor.info().unwrap().depth, cursor.state().unwrap().depth);
+                stack.extend(cursor.children().into_iter().map(|(_, _, c)| c));
+            }
+        }
+    }
+
+    #[test]
+    fn test_latency() {
+        let game = SyntheticGame::new(SyntheticConfig {
+            max_depth: 4,
+            latency: Duration::from_micros(200),
+            ..Default::default()
+        });
+        let (player, state) = game.initial();
+        let optimal = game.optimal_value(&state);
+        let tree = Tree::new(game, GetState, player, state);
+        search(&tree, 8, 500);
+        assert_eq!(tree.get_root_info().score, Some(optimal));
+    }
+
+    #[test]
+    fn test_panicking_evaluator() {
+        let game = SyntheticGame::new(SyntheticConfig {
+            panic_rate: 0.2,
+            ..Default::default()
+        });
+        let (player, state) = game.initial();
+        let tree = Arc::new(Tree::new(game, GetState, player, state));
+        let n_threads = 4;
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..n_threads {
+            let tree = Arc::clone(&tree);
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    for _ in 0..1000 {
+                        tree.step();
+                    }
+                }));
+                tx.send(result.is_err()).unwrap();
+            });
+        }
+        // the threads that did not run into the failure panic on the poisoned locks of the tree
+        // instead of waiting for the work of the failed thread forever
+        for _ in 0..n_threads {
+            let panicked = rx
+                .recv_timeout(Duration::from_secs(10))
+                .expect("search hung after an evaluator panic");
+            assert!(panicked);
+        }
+    }
+}
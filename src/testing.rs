@@ -0,0 +1,617 @@
+// The `Tree` relies on invariants of `GameDynamics` implementations that the type system cannot
+// express: states are deduplicated via the registry by their hash and `PartialEq`, children are
+// keyed by action, the action returned by `select_node_with_edges` is looked up among the
+// children, and a `Some` from `available_actions` turns a node into a branch that needs at least
+// one child.  Violations typically surface as hangs or panics deep inside the search, so the
+// checker exercises an implementation directly: it performs seeded random walks from an initial
+// state, checks every visited state, and shrinks the path to each violation by removing actions as
+// long as the violation is still reproduced (a simple form of property based shrinking).
+
+use crate::game_dynamics::{EdgeStats, GameDynamics, SelectNodeState};
+use crate::tree::hash_of;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+use std::mem;
+
+/// The parameters of [`check_game_dynamics`].
+#[derive(Debug, Clone)]
+pub struct ConformanceConfig {
+    /// The number of random walks starting at the initial state.
+    pub n_walks: usize,
+    /// The maximum number of actions per walk.
+    pub max_depth: usize,
+    /// The seed of the generator choosing the actions of the walks.
+    pub seed: u64,
+    /// Whether states may repeat along a walk, i.e. whether the `Tree` is configured to handle
+    /// cycles (see [`Tree::with_cycle_handling`](crate::Tree::with_cycle_handling)).
+    pub allow_cycles: bool,
+    /// Whether the paths to violations are shrunk (see [`Violation::path`]).
+    pub shrink: bool,
+}
+
+impl Default for ConformanceConfig {
+    fn default() -> Self {
+        Self {
+            n_walks: 100,
+            max_depth: 100,
+            seed: 0,
+            allow_cycles: false,
+            shrink: true,
+        }
+    }
+}
+
+/// An invariant of [`GameDynamics`] violated by an implementation.  Unless stated otherwise, the
+/// violation concerns the state reached by applying the actions of [`Violation::path`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViolationKind<A> {
+    /// [`GameDynamics::available_actions`] returned `Some` with an empty iterator; a terminal
+    /// state must return `None` (unlike an action that cannot be applied, see
+    /// [`GameDynamics::apply_action`]).
+    EmptyActions,
+    /// [`GameDynamics::available_actions`] yielded the action more than once.
+    DuplicateAction(A),
+    /// Two calls of [`GameDynamics::available_actions`] returned different players or actions.
+    NonDeterministicActions,
+    /// Applying the last action of the path twice to the same state returned different states
+    /// (or succeeded only once).
+    NonDeterministicApply,
+    /// The state is not equal to its clone.
+    InconsistentEq,
+    /// The state and an equal state (its clone or the result of applying the last action of the
+    /// path again) have different hashes.
+    InconsistentHash,
+    /// The state is equal to a state visited earlier on the path.
+    Cycle,
+    /// [`GameDynamics::select_node_with_edges`] returned an action that is not one of the
+    /// children's actions.
+    IllegalSelection {
+        /// The purpose passed to `select_node_with_edges`.
+        purpose: SelectNodeState,
+        /// The action returned.
+        action: A,
+    },
+}
+
+/// A violation found by [`check_game_dynamics`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation<A> {
+    /// The kind of the violation.
+    pub kind: ViolationKind<A>,
+    /// The actions leading from the initial state to the state violating the invariant.  The path
+    /// is the shortest found and, if [`ConformanceConfig::shrink`] is set, no single action can be
+    /// removed without losing the violation (or making the path illegal).
+    pub path: Vec<A>,
+}
+
+impl<A: Debug> fmt::Display for Violation<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ViolationKind::EmptyActions => write!(f, "no actions are available at a non-terminal")?,
+            ViolationKind::DuplicateAction(a) => write!(f, "action {:?} is available twice", a)?,
+            ViolationKind::NonDeterministicActions => {
+                write!(f, "available actions are not deterministic")?
+            }
+            ViolationKind::NonDeterministicApply => {
+                write!(f, "applying an action is not deterministic")?
+            }
+            ViolationKind::InconsistentEq => write!(f, "state is not equal to its clone")?,
+            ViolationKind::InconsistentHash => write!(f, "equal states have different hashes")?,
+            ViolationKind::Cycle => write!(f, "state repeats an earlier state")?,
+            ViolationKind::IllegalSelection { purpose, action } => write!(
+                f,
+                "{:?} selected action {:?} which is not a child",
+                purpose, action
+            )?,
+        }
+        write!(f, " after actions {:?}", self.path)
+    }
+}
+
+/// The result of [`check_game_dynamics`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConformanceReport<A> {
+    /// The number of states checked (including repeated visits).
+    pub n_states: usize,
+    /// At most one violation of each kind, in the order in which they were found.
+    pub violations: Vec<Violation<A>>,
+}
+
+impl<A> ConformanceReport<A> {
+    /// Returns `true` if no violations were found.
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+// A state on a path together with its player and score (as assigned when it was created)
+struct Position<P, S, Q> {
+    player: P,
+    state: S,
+    score: Option<Q>,
+}
+
+// A child of a position, i.e. an action that could be applied
+struct Child<P, S, A, Q> {
+    action: A,
+    position: Position<P, S, Q>,
+}
+
+struct Checker<'a, GD: GameDynamics> {
+    game_dynamics: &'a GD,
+    allow_cycles: bool,
+}
+
+impl<'a, GD, S, P, A, Q> Checker<'a, GD>
+where
+    GD: GameDynamics<Player = P, State = S, Action = A, Score = Q>,
+    S: Clone + Hash + PartialEq,
+    P: Clone + PartialEq,
+    A: Clone + PartialEq,
+{
+    // Returns the positions along `path` or `None` if an action is not available or cannot be
+    // applied
+    fn replay(&self, player: &P, state: &S, path: &[A]) -> Option<Vec<Position<P, S, Q>>> {
+        let mut positions = vec![Position {
+            player: player.clone(),
+            state: state.clone(),
+            score: None,
+        }];
+        for a in path {
+            let parent = positions.last().unwrap();
+            let child = self.children(parent).into_iter().find(|c| c.action == *a)?;
+            positions.push(child.position);
+        }
+        Some(positions)
+    }
+
+    fn available(&self, position: &Position<P, S, Q>) -> Option<Vec<(P, A)>> {
+        self.game_dynamics
+            .available_actions(&position.player, &position.state)
+            .map(|actions| actions.into_iter().collect())
+    }
+
+    // Returns the children of the position in the order of `available_actions`; empty for a
+    // terminal position
+    fn children(&self, position: &Position<P, S, Q>) -> Vec<Child<P, S, A, Q>> {
+        let gd = self.game_dynamics;
+        self.available(position)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(p, a)| {
+                let state = gd.apply_action(position.state.clone(), &a)?;
+                let score = gd.score_leaf(position.score.as_ref(), &position.player, &state);
+                Some(Child {
+                    action: a,
+                    position: Position {
+                        player: p,
+                        state,
+                        score,
+                    },
+                })
+            })
+            .collect()
+    }
+
+    // Returns the violations of the last position of `positions`, reached via `path`
+    fn violations(&self, positions: &[Position<P, S, Q>], path: &[A]) -> Vec<ViolationKind<A>> {
+        let gd = self.game_dynamics;
+        let mut found = Vec::new();
+        let (position, earlier) = positions.split_last().unwrap();
+        let state = &position.state;
+
+        let clone = state.clone();
+        if clone != *state {
+            found.push(ViolationKind::InconsistentEq);
+        } else if hash_of(&clone) != hash_of(state) {
+            found.push(ViolationKind::InconsistentHash);
+        }
+        if let (Some(parent), Some(a)) = (earlier.last(), path.last()) {
+            match gd.apply_action(parent.state.clone(), a) {
+                Some(again) if again == *state => {
+                    if hash_of(&again) != hash_of(state) {
+                        found.push(ViolationKind::InconsistentHash);
+                    }
+                }
+                _ => found.push(ViolationKind::NonDeterministicApply),
+            }
+        }
+        if !self.allow_cycles && earlier.iter().any(|p| p.state == *state) {
+            found.push(ViolationKind::Cycle);
+        }
+
+        let actions = match self.available(position) {
+            Some(actions) => actions,
+            None => return found,
+        };
+        if self.available(position).as_ref() != Some(&actions) {
+            found.push(ViolationKind::NonDeterministicActions);
+        }
+        if let Some(i) =
+            (1..actions.len()).find(|&i| actions[..i].iter().any(|x| x.1 == actions[i].1))
+        {
+            found.push(ViolationKind::DuplicateAction(actions[i].1.clone()));
+        }
+
+        if actions.is_empty() {
+            found.push(ViolationKind::EmptyActions);
+            return found;
+        }
+        let children = self.children(position);
+        if children.is_empty() {
+            // none of the actions can be applied, i.e. the state is terminal
+            return found;
+        }
+        let parent_score = gd.backprop_scores(
+            &position.player,
+            position.score.as_ref(),
+            children.iter().filter_map(|c| c.position.score.as_ref()),
+        );
+        let parent_score = parent_score.as_ref().or(position.score.as_ref());
+        let edges = children
+            .iter()
+            .map(|c| {
+                let prior = gd.action_prior(parent_score, &position.player, state, &c.action);
+                (&c.position.score, &c.action, EdgeStats { prior, visits: 1 })
+            })
+            .collect::<Vec<_>>();
+        for &purpose in &[SelectNodeState::Explore, SelectNodeState::Exploit] {
+            let action = gd.select_node_with_edges(
+                parent_score,
+                &position.player,
+                state,
+                purpose,
+                edges.clone(),
+            );
+            if !children.iter().any(|c| c.action == action) {
+                found.push(ViolationKind::IllegalSelection { purpose, action });
+            }
+        }
+        found
+    }
+
+    // Removes actions from `path` as long as the violation is still found at the end of the path
+    fn shrink(&self, player: &P, state: &S, kind: &ViolationKind<A>, mut path: Vec<A>) -> Vec<A> {
+        let mut i = 0;
+        while i < path.len() {
+            let mut candidate = path.clone();
+            candidate.remove(i);
+            let reproduced = self
+                .replay(player, state, &candidate)
+                .map(|positions| self.violations(&positions, &candidate))
+                .is_some_and(|found| {
+                    found
+                        .iter()
+                        .any(|k| mem::discriminant(k) == mem::discriminant(kind))
+                });
+            if reproduced {
+                path = candidate;
+                i = 0;
+            } else {
+                i += 1;
+            }
+        }
+        path
+    }
+}
+
+/// Checks that a [`GameDynamics`] implementation satisfies the invariants the [`Tree`] relies on
+/// (see [`ViolationKind`]) by performing random walks from `player` to move in `state`.  Each
+/// walk applies random actions until a terminal state or [`ConformanceConfig::max_depth`] is
+/// reached, checking every state on the way; the children of a state are scored via
+/// [`GameDynamics::score_leaf`] and passed to [`GameDynamics::select_node_with_edges`] as
+/// they would be after the state was expanded.  The walks only depend on `config.seed` (and on
+/// any randomness of the implementation).
+///
+/// [`Tree`]: crate::Tree
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use std::sync::Mutex;
+///
+/// let game = nim::Nim {
+///     max_move: 3,
+///     rng: Mutex::new(rand::SeedableRng::seed_from_u64(0)),
+/// };
+/// let report = check_game_dynamics(&game, nim::Player::P1, 20, &ConformanceConfig::default());
+/// assert!(report.is_ok());
+/// assert!(report.n_states > 100);
+/// ```
+pub fn check_game_dynamics<GD, S, P, A, Q>(
+    game_dynamics: &GD,
+    player: P,
+    state: S,
+    config: &ConformanceConfig,
+) -> ConformanceReport<A>
+where
+    GD: GameDynamics<Player = P, State = S, Action = A, Score = Q>,
+    S: Clone + Hash + PartialEq,
+    P: Clone + PartialEq,
+    A: Clone + PartialEq,
+{
+    let checker = Checker {
+        game_dynamics,
+        allow_cycles: config.allow_cycles,
+    };
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut n_states = 0;
+    let mut violations: Vec<Violation<A>> = Vec::new();
+
+    for _ in 0..config.n_walks {
+        let mut positions = checker
+            .replay(&player, &state, &[])
+            .expect("the empty path is legal");
+        let mut path = Vec::new();
+        loop {
+            n_states += 1;
+            for kind in checker.violations(&positions, &path) {
+                let same = violations
+                    .iter_mut()
+                    .find(|v| mem::discriminant(&v.kind) == mem::discriminant(&kind));
+                match same {
+                    Some(v) if v.path.len() > path.len() => {
+                        *v = Violation {
+                            kind,
+                            path: path.clone(),
+                        }
+                    }
+                    Some(_) => {}
+                    None => violations.push(Violation {
+                        kind,
+                        path: path.clone(),
+                    }),
+                }
+            }
+            if path.len() >= config.max_depth {
+                break;
+            }
+            let mut children = checker.children(positions.last().unwrap());
+            if children.is_empty() {
+                break;
+            }
+            let child = children.swap_remove(rng.gen_range(0, children.len()));
+            path.push(child.action);
+            positions.push(child.position);
+        }
+    }
+
+    if config.shrink {
+        for v in violations.iter_mut() {
+            v.path = checker.shrink(&player, &state, &v.kind, mem::take(&mut v.path));
+        }
+    }
+    ConformanceReport {
+        n_states,
+        violations,
+    }
+}
+
+/// Calls [`check_game_dynamics`] and panics with a description of the violations, if any.
+///
+/// # Panics
+///
+/// Panics if a violation is found.
+pub fn assert_game_dynamics<GD, S, P, A, Q>(
+    game_dynamics: &GD,
+    player: P,
+    state: S,
+    config: &ConformanceConfig,
+) where
+    GD: GameDynamics<Player = P, State = S, Action = A, Score = Q>,
+    S: Clone + Hash + PartialEq,
+    P: Clone + PartialEq,
+    A: Clone + PartialEq + Debug,
+{
+    let report = check_game_dynamics(game_dynamics, player, state, config);
+    if !report.is_ok() {
+        let violations = report
+            .violations
+            .iter()
+            .map(|v| format!("  {}", v))
+            .collect::<Vec<_>>();
+        panic!(
+            "the game dynamics violate {} invariant(s):\n{}",
+            violations.len(),
+            violations.join("\n")
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::simple_game::{SimpleGame, Uct};
+
+    use std::hash::Hasher;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // a counting game that can be broken in various ways: add 1 or 2 until reaching 6
+    #[derive(Default)]
+    struct Count {
+        // yields the action 1 twice at 3
+        duplicate: bool,
+        // if non-zero, applying an action to 4 alternates between 5 and 6
+        alternate: AtomicUsize,
+        // the state goes back from 5 to 0 via action 2
+        cycle: bool,
+    }
+
+    // hashes differently on each call
+    #[derive(Clone, Debug, PartialEq)]
+    struct Unstable(u8);
+
+    impl Hash for Unstable {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            static CALLS: AtomicUsize = AtomicUsize::new(0);
+            self.0.hash(state);
+            CALLS.fetch_add(1, Ordering::Relaxed).hash(state);
+        }
+    }
+
+    impl SimpleGame for Count {
+        type Player = ();
+        type State = u8;
+        type Action = u8;
+
+        fn legal_actions(&self, _player: &(), state: &u8) -> Vec<u8> {
+            match *state {
+                3 if self.duplicate => vec![1, 1, 2],
+                s if s < 6 => vec![1, 2],
+                _ => Vec::new(),
+            }
+        }
+
+        fn apply(&self, state: u8, action: &u8) -> u8 {
+            match state {
+                4 if self.alternate.load(Ordering::Relaxed) > 0 => {
+                    5 + self.alternate.fetch_add(1, Ordering::Relaxed) as u8 % 2
+                }
+                5 if self.cycle && *action == 2 => 0,
+                s => (s + action).min(6),
+            }
+        }
+
+        fn next_player(&self, _player: &()) {}
+
+        fn reward(&self, _player: &(), state: &u8) -> Vec<((), f64)> {
+            vec![((), if *state == 6 { 1.0 } else { 0.0 })]
+        }
+    }
+
+    fn check(count: Count) -> Vec<Violation<u8>> {
+        let game = Uct::new(count);
+        check_game_dynamics(&game, (), 0, &ConformanceConfig::default()).violations
+    }
+
+    #[test]
+    fn test_conforming() {
+        assert_game_dynamics(&Uct::new(Count::default()), (), 0, &Default::default());
+    }
+
+    #[test]
+    fn test_violations() {
+        let violations = check(Count {
+            duplicate: true,
+            ..Default::default()
+        });
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, ViolationKind::DuplicateAction(1));
+        // shrunk to a shortest path to 3
+        assert_eq!(violations[0].path.len(), 2);
+        assert_eq!(violations[0].path.iter().sum::<u8>(), 3);
+
+        let violations = check(Count {
+            alternate: AtomicUsize::new(1),
+            ..Default::default()
+        });
+        assert!(violations
+            .iter()
+            .any(|v| v.kind == ViolationKind::NonDeterministicApply));
+
+        let violations = check(Count {
+            cycle: true,
+            ..Default::default()
+        });
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, ViolationKind::Cycle);
+        let path = &violations[0].path;
+        assert_eq!(path.last(), Some(&2));
+        assert_eq!(path.iter().sum::<u8>(), 7);
+        let config = ConformanceConfig {
+            allow_cycles: true,
+            max_depth: 20,
+            ..Default::default()
+        };
+        let game = Uct::new(Count {
+            cycle: true,
+            ..Default::default()
+        });
+        assert!(check_game_dynamics(&game, (), 0, &config).is_ok());
+    }
+
+    #[test]
+    fn test_empty_and_hash() {
+        // `Uct` returns `None` for an empty vector of legal actions, so `available_actions` is
+        // broken via a wrapper
+        struct Empty(Uct<Count>);
+
+        impl GameDynamics for Empty {
+            type Player = ();
+            type State = Unstable;
+            type Action = u8;
+            type Score = <Uct<Count> as GameDynamics>::Score;
+            type ActionIter = Vec<((), u8)>;
+
+            fn available_actions(&self, _: &(), state: &Unstable) -> Option<Vec<((), u8)>> {
+                Some(self.0.available_actions(&(), &state.0).unwrap_or_default())
+            }
+
+            fn apply_action(&self, state: Unstable, action: &u8) -> Option<Unstable> {
+                self.0.apply_action(state.0, action).map(Unstable)
+            }
+
+            fn select_node<II, Q, A>(
+                &self,
+                _: Option<&Self::Score>,
+                _: &(),
+                _: &Unstable,
+                _: SelectNodeState,
+                _: II,
+            ) -> u8
+            where
+                II: IntoIterator<Item = (Q, A)>,
+            {
+                // never a legal action
+                0
+            }
+
+            fn backprop_scores<II, Q>(
+                &self,
+                _: &(),
+                _: Option<&Self::Score>,
+                _: II,
+            ) -> Option<Self::Score> {
+                None
+            }
+
+            fn score_leaf(
+                &self,
+                _: Option<&Self::Score>,
+                _: &(),
+                _: &Unstable,
+            ) -> Option<Self::Score> {
+                None
+            }
+        }
+
+        let game = Empty(Uct::new(Count::default()));
+        let report = check_game_dynamics(&game, (), Unstable(0), &ConformanceConfig::default());
+        let kinds = report
+            .violations
+            .iter()
+            .map(|v| (v.kind.clone(), v.path.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (ViolationKind::InconsistentHash, 0),
+                (
+                    ViolationKind::IllegalSelection {
+                        purpose: SelectNodeState::Explore,
+                        action: 0
+                    },
+                    0
+                ),
+                (ViolationKind::EmptyActions, 3),
+            ]
+        );
+        let message = format!("{}", report.violations[2]);
+        assert!(message.starts_with("no actions are available"));
+    }
+}
@@ -0,0 +1,469 @@
+// A match plays games between two engines (`A` and `B`), each of which searches its own tree.  The
+// action chosen by the engine to move is applied to both trees so that the engine that is not to
+// move keeps (and later reuses) its statistics of the position.  Engine `A` plays the first player
+// in even games and engine `B` in odd games; for the player to move, the engine is determined by
+// comparing the root's player with the first player, i.e. the games must be two-player games.
+// Workers pull game indices from a shared counter so that a decision of the SPRT stops scheduling
+// new games (games already in progress are completed and counted).
+
+use crate::async_search::{expand_root, search, SearchBudget};
+use crate::game_dynamics::GameDynamics;
+use crate::tree::{SearchTree, Status};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The result of a finished game of a match, determined by the `judge` passed to [`run_match`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome<P> {
+    /// The player won the game.
+    Win(P),
+    /// The game was drawn.
+    Draw,
+}
+
+/// The results of a match from the perspective of engine `A`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MatchStats {
+    /// The number of games won by engine `A`.
+    pub wins: usize,
+    /// The number of drawn games (including games adjudicated after
+    /// [`MatchConfig::max_moves`]).
+    pub draws: usize,
+    /// The number of games lost by engine `A`.
+    pub losses: usize,
+}
+
+impl MatchStats {
+    /// Returns the number of games played.
+    pub fn n_games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Returns the mean score of engine `A` (a win counts 1, a draw 0.5), or `None` if no games
+    /// have been played.
+    pub fn score(&self) -> Option<f64> {
+        if self.n_games() == 0 {
+            return None;
+        }
+        let (w, d, l) = (self.wins as f64, self.draws as f64, self.losses as f64);
+        Some(mean_and_variance(w, d, l).0)
+    }
+
+    // The variance of the score of a single game
+    fn variance(&self) -> Option<f64> {
+        if self.n_games() == 0 {
+            return None;
+        }
+        let (w, d, l) = (self.wins as f64, self.draws as f64, self.losses as f64);
+        Some(mean_and_variance(w, d, l).1)
+    }
+
+    /// Returns the estimated Elo difference of engine `A` over engine `B`.  The estimate is
+    /// infinite if one of the engines won every game.
+    pub fn elo(&self) -> Option<f64> {
+        self.score().map(elo_from_score)
+    }
+
+    /// Returns the bounds of the confidence interval of the Elo difference for the standard
+    /// normal quantile `z` (e.g. `1.96` for a 95% interval), based on the normal approximation of
+    /// the mean score.
+    pub fn elo_interval(&self, z: f64) -> Option<(f64, f64)> {
+        let s = self.score()?;
+        let se = (self.variance()? / self.n_games() as f64).sqrt();
+        let lo = (s - z * se).max(0.0);
+        let hi = (s + z * se).min(1.0);
+        Some((elo_from_score(lo), elo_from_score(hi)))
+    }
+
+    fn add(&mut self, result: Option<bool>) {
+        match result {
+            Some(true) => self.wins += 1,
+            Some(false) => self.losses += 1,
+            None => self.draws += 1,
+        }
+    }
+}
+
+// The mean and the variance of the score of a single game given the (possibly fractional) number
+// of wins, draws and losses
+fn mean_and_variance(w: f64, d: f64, l: f64) -> (f64, f64) {
+    let n = w + d + l;
+    let s = (w + 0.5 * d) / n;
+    let var = (w * (1.0 - s).powi(2) + d * (0.5 - s).powi(2) + l * s.powi(2)) / n;
+    (s, var)
+}
+
+fn elo_from_score(s: f64) -> f64 {
+    -400.0 * (1.0 / s - 1.0).log10()
+}
+
+fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// A sequential probability ratio test of the hypotheses that the Elo difference of engine `A`
+/// over engine `B` is `elo0` (H0) or `elo1` (H1), used to stop a match as soon as the results are
+/// conclusive.
+///
+/// The log-likelihood ratio is computed with the normal approximation of the mean score (the
+/// "generalized" SPRT commonly used for testing chess engines).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    /// The Elo difference under H0.
+    pub elo0: f64,
+    /// The Elo difference under H1.
+    pub elo1: f64,
+    /// The probability of accepting H1 if H0 is true.
+    pub alpha: f64,
+    /// The probability of accepting H0 if H1 is true.
+    pub beta: f64,
+}
+
+/// The state of a [`Sprt`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SprtDecision {
+    /// The results are not yet conclusive.
+    Continue,
+    /// The Elo difference is `elo0` (or less).
+    AcceptH0,
+    /// The Elo difference is `elo1` (or more).
+    AcceptH1,
+}
+
+impl Sprt {
+    /// Returns the log-likelihood ratio of H1 over H0, or `None` if no games have been played.
+    ///
+    /// The variance of the score is zero if all games had the same result; in this case, the
+    /// ratio is computed as if half a game had been won and half a game had been lost in
+    /// addition.
+    pub fn llr(&self, stats: &MatchStats) -> Option<f64> {
+        let s = stats.score()?;
+        let mut var = stats.variance()?;
+        if var == 0.0 {
+            let (w, d, l) = (stats.wins as f64, stats.draws as f64, stats.losses as f64);
+            var = mean_and_variance(w + 0.5, d, l + 0.5).1;
+        }
+        let (s0, s1) = (score_from_elo(self.elo0), score_from_elo(self.elo1));
+        Some(stats.n_games() as f64 * (s1 - s0) * (2.0 * s - s0 - s1) / (2.0 * var))
+    }
+
+    /// Returns the lower and upper bounds of the log-likelihood ratio at which H0 and H1 are
+    /// accepted, respectively.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Returns the decision of the test given the results so far.
+    pub fn decide(&self, stats: &MatchStats) -> SprtDecision {
+        let (lower, upper) = self.bounds();
+        match self.llr(stats) {
+            Some(llr) if llr >= upper => SprtDecision::AcceptH1,
+            Some(llr) if llr <= lower => SprtDecision::AcceptH0,
+            _ => SprtDecision::Continue,
+        }
+    }
+}
+
+/// The parameters of [`run_match`].
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+    /// The maximum number of games (fewer games are played if the SPRT is conclusive earlier).
+    pub n_games: usize,
+    /// The number of games played in parallel, each on its own thread.
+    pub n_parallel: usize,
+    /// The search performed by engine `A` before each of its moves.
+    pub budget_a: SearchBudget,
+    /// The search performed by engine `B` before each of its moves.
+    pub budget_b: SearchBudget,
+    /// The number of threads searching for engine `A`.
+    pub threads_a: usize,
+    /// The number of threads searching for engine `B`.
+    pub threads_b: usize,
+    /// The number of moves after which a game is adjudicated as a draw.
+    pub max_moves: Option<usize>,
+    /// Stops the match once the test is conclusive.
+    pub sprt: Option<Sprt>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            n_games: 100,
+            n_parallel: 1,
+            budget_a: SearchBudget::Steps(100),
+            budget_b: SearchBudget::Steps(100),
+            threads_a: 1,
+            threads_b: 1,
+            max_moves: None,
+            sprt: None,
+        }
+    }
+}
+
+/// The results of [`run_match`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchReport {
+    /// The results from the perspective of engine `A`.
+    pub stats: MatchStats,
+    /// The decision of [`MatchConfig::sprt`], if any.
+    pub sprt: Option<SprtDecision>,
+    /// The duration of the match.
+    pub elapsed: Duration,
+}
+
+/// Plays a match between two engines and returns the results from the perspective of engine `A`.
+///
+/// The engines search trees created by `make_tree_a(player, state)` and
+/// `make_tree_b(player, state)` (e.g. trees with different [`GameDynamics`] implementations or
+/// parameters); a new pair of trees is created for every game.  Each game starts with
+/// `first_player` to move in `state`; engine `A` plays `first_player` in even games and engine `B`
+/// in odd games.  Once the root is terminal, `judge(player, state)` determines the outcome from
+/// the player to move at the final root and the final state.
+///
+/// # Panics
+///
+/// Panics if `config.n_parallel`, `config.threads_a` or `config.threads_b` is zero.
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use std::sync::Mutex;
+///
+/// let make_tree = |player, state| {
+///     let game = nim::Nim {
+///         max_move: 3,
+///         rng: Mutex::new(rand::SeedableRng::seed_from_u64(0)),
+///     };
+///     Tree::new(game, GetState, player, state)
+/// };
+/// let config = MatchConfig {
+///     n_games: 4,
+///     n_parallel: 2,
+///     budget_a: SearchBudget::Steps(100),
+///     budget_b: SearchBudget::Steps(1),
+///     ..Default::default()
+/// };
+/// // in Nim, the player to move once no items are left has lost
+/// let judge = |player: &nim::Player, _: &usize| match player {
+///     nim::Player::P1 => Outcome::Win(nim::Player::P2),
+///     nim::Player::P2 => Outcome::Win(nim::Player::P1),
+/// };
+/// let report = run_match(&config, make_tree, make_tree, nim::Player::P1, 21, judge);
+/// assert_eq!(report.stats.n_games(), 4);
+/// println!("{:?} {:?}", report.stats.elo(), report.stats.elo_interval(1.96));
+/// ```
+pub fn run_match<TA, TB, FA, FB, J, P, S, A>(
+    config: &MatchConfig,
+    make_tree_a: FA,
+    make_tree_b: FB,
+    first_player: P,
+    state: S,
+    judge: J,
+) -> MatchReport
+where
+    TA: SearchTree + Sync,
+    TB: SearchTree + Sync,
+    TA::GD: GameDynamics<Player = P, State = S, Action = A>,
+    TB::GD: GameDynamics<Player = P, State = S, Action = A>,
+    <TA::GD as GameDynamics>::Score: Clone,
+    FA: Fn(P, S) -> TA + Sync,
+    FB: Fn(P, S) -> TB + Sync,
+    J: Fn(&P, &S) -> Outcome<P> + Sync,
+    P: Clone + PartialEq + Sync,
+    S: Clone + Sync,
+{
+    assert!(config.n_parallel > 0, "at least one thread is required");
+    assert!(
+        config.threads_a > 0 && config.threads_b > 0,
+        "each engine requires at least one search thread"
+    );
+    let start = Instant::now();
+    let next_game = AtomicUsize::new(0);
+    let results = Mutex::new((MatchStats::default(), SprtDecision::Continue));
+    std::thread::scope(|s| {
+        for _ in 0..config.n_parallel {
+            s.spawn(|| loop {
+                if results.lock().unwrap().1 != SprtDecision::Continue {
+                    break;
+                }
+                let i = next_game.fetch_add(1, Ordering::Relaxed);
+                if i >= config.n_games {
+                    break;
+                }
+                let a = make_tree_a(first_player.clone(), state.clone());
+                let b = make_tree_b(first_player.clone(), state.clone());
+                let a_is_first = i % 2 == 0;
+                let result = play_game(config, &a, &b, &first_player, a_is_first, &judge);
+
+                let mut results = results.lock().unwrap();
+                results.0.add(result);
+                if let Some(sprt) = config.sprt {
+                    if results.1 == SprtDecision::Continue {
+                        results.1 = sprt.decide(&results.0);
+                    }
+                }
+            });
+        }
+    });
+
+    let (stats, decision) = results.into_inner().unwrap();
+    MatchReport {
+        stats,
+        sprt: config.sprt.map(|_| decision),
+        elapsed: start.elapsed(),
+    }
+}
+
+// Plays a game and returns `Some(true)` if engine `A` won, `Some(false)` if it lost, and `None` if
+// the game was drawn
+fn play_game<TA, TB, J, P, S, A>(
+    config: &MatchConfig,
+    a: &TA,
+    b: &TB,
+    first_player: &P,
+    a_is_first: bool,
+    judge: &J,
+) -> Option<bool>
+where
+    TA: SearchTree + Sync,
+    TB: SearchTree + Sync,
+    TA::GD: GameDynamics<Player = P, State = S, Action = A>,
+    TB::GD: GameDynamics<Player = P, State = S, Action = A>,
+    <TA::GD as GameDynamics>::Score: Clone,
+    J: Fn(&P, &S) -> Outcome<P>,
+    P: Clone + PartialEq,
+    S: Clone,
+{
+    let is_a = |player: &P| (player == first_player) == a_is_first;
+    let mut n_moves = 0;
+    loop {
+        if config.max_moves.into_iter().any(|n| n_moves >= n) {
+            return None;
+        }
+        let root = a.get_root_info();
+        let status = if is_a(&root.player) {
+            search(a, config.budget_a, config.threads_a);
+            expand_root(a)
+        } else {
+            search(b, config.budget_b, config.threads_b);
+            expand_root(b)
+        };
+        match status {
+            Status::Action(action) | Status::ActionWip(action) => {
+                // the engine that is not to move may not have expanded the root yet
+                expand_root(a);
+                expand_root(b);
+                a.apply_action(&action);
+                b.apply_action(&action);
+                n_moves += 1;
+            }
+            _ => {
+                let state = root.state.expect("the root has a state");
+                return match judge(&root.player, &state) {
+                    Outcome::Win(winner) => Some(is_a(&winner)),
+                    Outcome::Draw => None,
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stats(wins: usize, draws: usize, losses: usize) -> MatchStats {
+        MatchStats {
+            wins,
+            draws,
+            losses,
+        }
+    }
+
+    #[test]
+    fn test_elo() {
+        assert_eq!(stats(0, 0, 0).elo(), None);
+        assert!(stats(5, 10, 5).elo().unwrap().abs() < 1e-9);
+        // a score of 0.75 corresponds to ~191 Elo
+        let elo = stats(15, 0, 5).elo().unwrap();
+        assert!((elo - 190.85).abs() < 0.01);
+        let (lo, hi) = stats(15, 0, 5).elo_interval(1.96).unwrap();
+        assert!(lo < elo && elo < hi);
+        let (lo2, hi2) = stats(150, 0, 50).elo_interval(1.96).unwrap();
+        assert!(lo < lo2 && hi2 < hi);
+    }
+
+    #[test]
+    fn test_sprt() {
+        let sprt = Sprt {
+            elo0: 0.0,
+            elo1: 50.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+        let (lower, upper) = sprt.bounds();
+        assert!((upper - 2.944).abs() < 1e-3);
+        assert!((lower + 2.944).abs() < 1e-3);
+        assert_eq!(sprt.decide(&stats(0, 0, 0)), SprtDecision::Continue);
+        assert_eq!(sprt.decide(&stats(6, 0, 4)), SprtDecision::Continue);
+        assert_eq!(sprt.decide(&stats(300, 100, 100)), SprtDecision::AcceptH1);
+        assert_eq!(sprt.decide(&stats(100, 100, 300)), SprtDecision::AcceptH0);
+        // the ratio is defined even if all games had the same result
+        assert_eq!(sprt.decide(&stats(1, 0, 0)), SprtDecision::Continue);
+        assert_eq!(sprt.decide(&stats(20, 0, 0)), SprtDecision::AcceptH1);
+    }
+
+    #[test]
+    fn test_run_match() {
+        use crate::nim::{Nim, Player};
+        use crate::tree::state_memory::GetState;
+        use crate::tree::Tree;
+        use rand::SeedableRng;
+
+        let make_tree = |player, state| {
+            let game = Nim {
+                max_move: 3,
+                rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+            };
+            Tree::new(game, GetState, player, state)
+        };
+        let judge = |player: &Player, _: &usize| match player {
+            Player::P1 => Outcome::Win(Player::P2),
+            Player::P2 => Outcome::Win(Player::P1),
+        };
+        let config = MatchConfig {
+            n_games: 200,
+            n_parallel: 2,
+            budget_a: SearchBudget::Steps(300),
+            budget_b: SearchBudget::Steps(0),
+            threads_a: 2,
+            sprt: Some(Sprt {
+                elo0: 0.0,
+                elo1: 100.0,
+                alpha: 0.05,
+                beta: 0.05,
+            }),
+            ..Default::default()
+        };
+        let report = run_match(&config, make_tree, make_tree, Player::P1, 21, judge);
+        assert_eq!(report.sprt, Some(SprtDecision::AcceptH1));
+        assert!(report.stats.n_games() < 200);
+        assert!(report.stats.wins > report.stats.losses);
+
+        // adjudicated draws
+        let config = MatchConfig {
+            n_games: 4,
+            max_moves: Some(2),
+            ..config
+        };
+        let report = run_match(&config, make_tree, make_tree, Player::P1, 21, judge);
+        assert_eq!(report.stats.draws, 4);
+        assert_eq!(report.sprt, Some(SprtDecision::Continue));
+    }
+}
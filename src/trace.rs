@@ -0,0 +1,268 @@
+// `TraceRecorder` is a `TreeObserver`: the tree reports the statistics of the root's children
+// right before the root is moved (see `TreeObserver::root_moving`), so the recorder captures the
+// search result for every move without the caller having to query the tree.  The outcome of the
+// game is only known once the game is over, so it is not part of `TraceRecord` but passed to
+// `TraceRecorder::write_jsonl`.
+
+use crate::game_dynamics::EdgeStats;
+use crate::observer::TreeObserver;
+use crate::tree::NodeInfo;
+
+use serde::{Deserialize, Serialize};
+
+use std::io::Write;
+use std::sync::Mutex;
+
+/// The statistics of one of the root's children in a [`TraceRecord`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceChild<A, Q> {
+    /// The action leading to the child.
+    pub action: A,
+    /// The number of times the edge was traversed.
+    pub visits: usize,
+    /// The prior of the edge (see
+    /// [`GameDynamics::action_prior`](crate::GameDynamics::action_prior)).
+    pub prior: Option<f64>,
+    /// The score of the child.
+    pub score: Option<Q>,
+}
+
+/// The search result at a root of the tree and the action that was applied to it, recorded by a
+/// [`TraceRecorder`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceRecord<S, P, A, Q> {
+    /// The number of actions applied before this one since the recorder was created or cleared.
+    pub ply: usize,
+    /// The player taking an action at the root.
+    pub player: P,
+    /// The state of the root.
+    pub state: Option<S>,
+    /// The score of the root.
+    pub score: Option<Q>,
+    /// The action that was applied.
+    pub action: A,
+    /// The statistics of the root's children, i.e. the visit and score distribution over the
+    /// available actions.
+    pub children: Vec<TraceChild<A, Q>>,
+}
+
+// A line of the JSON Lines output
+#[derive(Serialize)]
+struct TraceLine<'a, S, P, A, Q, R> {
+    #[serde(flatten)]
+    record: &'a TraceRecord<S, P, A, Q>,
+    outcome: R,
+}
+
+/// Records the search result at every root of a game, e.g. to generate training data from
+/// self-play.
+///
+/// Register the recorder with [`SearchTree::add_observer`](crate::SearchTree::add_observer); a
+/// [`TraceRecord`] is then captured whenever an action is applied via
+/// [`SearchTree::apply_action`](crate::SearchTree::apply_action) or
+/// [`SearchTree::apply_best_action`](crate::SearchTree::apply_best_action) (and removed again if
+/// the action is taken back via [`SearchTree::undo`](crate::SearchTree::undo)).  Once the game is
+/// over, write the records with the final outcome via [`TraceRecorder::write_jsonl`].
+///
+/// # Examples
+///
+/// ```
+/// use recon_mcts::prelude::*;
+/// use serde::Serialize;
+/// use std::sync::Arc;
+///
+/// fn play<T>(tree: &T) -> Vec<u8>
+/// where
+///     T: SearchTree,
+///     <T::GD as GameDynamics>::State: 'static + Clone + Send + Sync + Serialize,
+///     <T::GD as GameDynamics>::Player: 'static + Clone + PartialEq + Send + Sync + Serialize,
+///     <T::GD as GameDynamics>::Action: 'static + Clone + Send + Sync + Serialize,
+///     <T::GD as GameDynamics>::Score: 'static + Clone + Send + Sync + Serialize,
+/// {
+///     let recorder = Arc::new(TraceRecorder::new());
+///     tree.add_observer(Arc::clone(&recorder) as _);
+///     loop {
+///         for _ in 0..100 {
+///             tree.step();
+///         }
+///         if let Status::Terminal = tree.apply_best_action() {
+///             break;
+///         }
+///     }
+///     // e.g. in Nim, the player to move once the game is over has lost
+///     let loser = tree.get_root_info().player;
+///     let mut jsonl = Vec::new();
+///     recorder
+///         .write_jsonl(&mut jsonl, |record| record.player != loser)
+///         .unwrap();
+///     jsonl
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TraceRecorder<S, P, A, Q> {
+    records: Mutex<Vec<TraceRecord<S, P, A, Q>>>,
+}
+
+impl<S, P, A, Q> TraceRecorder<S, P, A, Q> {
+    /// Construct a new, empty `TraceRecorder`.
+    pub fn new() -> Self {
+        Self {
+            records: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns the number of records.
+    pub fn len(&self) -> usize {
+        self.records.lock().unwrap().len()
+    }
+
+    /// Returns `true` if there are no records.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes and returns all records, e.g. to start recording the next game.
+    pub fn take(&self) -> Vec<TraceRecord<S, P, A, Q>> {
+        std::mem::take(&mut *self.records.lock().unwrap())
+    }
+
+    /// Returns a copy of the records in the order in which the actions were applied.
+    pub fn records(&self) -> Vec<TraceRecord<S, P, A, Q>>
+    where
+        TraceRecord<S, P, A, Q>: Clone,
+    {
+        self.records.lock().unwrap().clone()
+    }
+
+    /// Writes the records as [JSON Lines](https://jsonlines.org/), one JSON object per record
+    /// with the fields of [`TraceRecord`] and an additional field `outcome` that is back-filled
+    /// with `outcome(record)`, e.g. the final result of the game from the perspective of the
+    /// record's player.
+    pub fn write_jsonl<W, F, R>(&self, mut writer: W, mut outcome: F) -> serde_json::Result<()>
+    where
+        W: Write,
+        F: FnMut(&TraceRecord<S, P, A, Q>) -> R,
+        R: Serialize,
+        S: Serialize,
+        P: Serialize,
+        A: Serialize,
+        Q: Serialize,
+    {
+        for record in self.records.lock().unwrap().iter() {
+            let line = TraceLine {
+                record,
+                outcome: outcome(record),
+            };
+            serde_json::to_writer(&mut writer, &line)?;
+            writer.write_all(b"\n").map_err(serde_json::Error::io)?;
+        }
+        writer.flush().map_err(serde_json::Error::io)
+    }
+}
+
+impl<S, P, A, Q> Default for TraceRecorder<S, P, A, Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S, P, A, Q> TreeObserver<S, P, A, Q> for TraceRecorder<S, P, A, Q>
+where
+    S: Clone + Send + Sync,
+    P: Clone + Send + Sync,
+    A: Clone + Send + Sync,
+    Q: Clone + Send + Sync,
+{
+    fn root_moving(
+        &self,
+        action: &A,
+        root: &NodeInfo<S, P, Q>,
+        children: &[(A, EdgeStats, NodeInfo<S, P, Q>)],
+    ) {
+        let children = children
+            .iter()
+            .map(|(a, stats, child)| TraceChild {
+                action: a.clone(),
+                visits: stats.visits,
+                prior: stats.prior,
+                score: child.score.clone(),
+            })
+            .collect();
+        let mut records = self.records.lock().unwrap();
+        let ply = records.len();
+        records.push(TraceRecord {
+            ply,
+            player: root.player.clone(),
+            state: root.state.clone(),
+            score: root.score.clone(),
+            action: action.clone(),
+            children,
+        });
+    }
+
+    fn root_undone(&self, _action: &A, _node: &NodeInfo<S, P, Q>) {
+        self.records.lock().unwrap().pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::Status;
+
+    fn info(player: u8, state: u32, score: f64) -> NodeInfo<u32, u8, f64> {
+        NodeInfo {
+            depth: 0,
+            state: Some(state),
+            player,
+            score: Some(score),
+            n_parents: 0,
+            n_children: Status::Action(2),
+        }
+    }
+
+    #[test]
+    fn test_write_jsonl() {
+        let recorder = TraceRecorder::new();
+        let edge = |visits| EdgeStats {
+            prior: None,
+            visits,
+        };
+        let children = |state: u32| {
+            vec![
+                ('a', edge(3), info(1, state + 1, 0.25)),
+                ('b', edge(1), info(1, state + 2, 0.75)),
+            ]
+        };
+        recorder.root_moving(&'a', &info(0, 0, 0.5), &children(0));
+        recorder.root_moving(&'b', &info(1, 1, 0.5), &children(1));
+        recorder.root_moving(&'a', &info(0, 3, 0.5), &children(3));
+        recorder.root_undone(&'a', &info(0, 3, 0.5));
+        assert_eq!(recorder.len(), 2);
+
+        let mut out = Vec::new();
+        recorder
+            .write_jsonl(&mut out, |record| if record.player == 0 { 1 } else { -1 })
+            .unwrap();
+        let lines = String::from_utf8(out).unwrap();
+        let lines = lines
+            .lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["ply"], 0);
+        assert_eq!(lines[0]["action"], "a");
+        assert_eq!(lines[0]["outcome"], 1);
+        assert_eq!(lines[1]["ply"], 1);
+        assert_eq!(lines[1]["state"], 1);
+        assert_eq!(lines[1]["outcome"], -1);
+        assert_eq!(lines[1]["children"][0]["visits"], 3);
+
+        // the records (without the outcome) can be read back
+        let record: TraceRecord<u32, u8, char, f64> =
+            serde_json::from_value(lines[1].clone()).unwrap();
+        assert_eq!(record, recorder.records()[1]);
+        assert_eq!(recorder.take().len(), 2);
+        assert!(recorder.is_empty());
+    }
+}
@@ -1,17 +1,26 @@
 #![allow(clippy::type_complexity)]
 
-use crate::game_dynamics::{GameDynamics, SelectNodeState};
+use crate::annotation::{self, Annotation};
+use crate::game_dynamics::{EdgeStats, GameDynamics, SelectNodeState};
 use crate::lockref;
+use crate::observer::{self, Observers, TreeEvent, TreeObserver};
+use crate::root_parallel::{ChildReport, NodeReport, SearchReport};
+use crate::stats::{MemoryHints, RegistryInfo};
 use crate::unique_heap::{self, UniqueHeap};
 
+use rand::distributions::WeightedIndex;
+use rand::{Rng, RngCore};
+
 use std::cmp::Reverse;
-use std::collections::{hash_map::DefaultHasher, HashMap, HashSet};
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
-use std::hash::{Hash, Hasher};
+use std::hash::{BuildHasherDefault, Hash, Hasher};
 use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, RangeInclusive};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock, Weak};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::Instant;
 
 /// Convenience type alias.
 pub type TreeAlias<GD, M> = Tree<NodeAlias<GD, M>, GD>;
@@ -99,12 +108,25 @@ pub trait SearchTree {
     /// Returns a `Status` with the currently anticipated `GameDynamics::Action` if available.
     fn best_action(&self) -> Status<<Self::GD as GameDynamics>::Action>;
 
+    /// Returns the principal variation, i.e. the sequence of actions starting at the root where
+    /// each action is selected as in [`SearchTree::best_action`] at the `Node` reached by the
+    /// previous actions.  The sequence ends at a leaf or terminal `Node`.
+    fn principal_variation(&self) -> Vec<<Self::GD as GameDynamics>::Action>;
+
     /// Move the root based on the selected action
     fn apply_action(&self, a: &<Self::GD as GameDynamics>::Action);
 
     /// Check for the best action and then apply it to move the root
     fn apply_best_action(&self) -> Status<<Self::GD as GameDynamics>::Action>;
 
+    /// Takes back the most recently applied action by moving the root back to the previous root
+    /// kept in the history (see [`Tree::with_history`]).  Returns `false` if the history is empty.
+    fn undo(&self) -> bool;
+
+    /// Returns the number of previous roots kept in the history, i.e. the number of actions that
+    /// can be taken back via [`SearchTree::undo`].
+    fn history_len(&self) -> usize;
+
     /// Returns a `NodeInfo` for the `SearchTree`'s root.
     fn get_root_info(
         &self,
@@ -137,6 +159,81 @@ pub trait SearchTree {
         <Self::GD as GameDynamics>::Player: Clone,
         <Self::GD as GameDynamics>::Score: Clone;
 
+    /// Returns `Some(Vec<(GameDynamics::Action, EdgeStats)>)` with the prior and visit count of
+    /// the edges from the `SearchTree`'s root to its children.  Returns a `None` under the same
+    /// conditions as [`SearchTree::get_next_move_info`].
+    fn get_next_move_edges(&self) -> Option<Vec<(<Self::GD as GameDynamics>::Action, EdgeStats)>>;
+
+    /// Mixes [Dirichlet](https://en.wikipedia.org/wiki/Dirichlet_distribution) noise into the
+    /// priors of the edges from the `SearchTree`'s root to its children, i.e. each prior `p` is
+    /// replaced by `(1 - epsilon) * p + epsilon * eta` where `eta ~ Dir(alpha)`.  Edges without a
+    /// prior are treated as having a uniform prior.  This is typically called after each move
+    /// (once the root's children have been created) to encourage exploration at the root, e.g.
+    /// AlphaZero uses `epsilon = 0.25` and `alpha` inversely proportional to the typical number of
+    /// legal moves.
+    ///
+    /// Returns `false` (and leaves the priors unchanged) if not all of the root's children have
+    /// been created yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alpha` is not positive or if `epsilon` is not in `[0, 1]`.
+    fn add_root_noise(&self, alpha: f64, epsilon: f64, rng: &mut dyn RngCore) -> bool;
+
+    /// Replaces the priors of the edges from the `SearchTree`'s root to its children by the
+    /// priors in `priors` (e.g. to seed the search from an [`OpeningBook`](crate::OpeningBook)).
+    /// The priors of edges whose action is not contained in `priors` are left unchanged.
+    ///
+    /// Returns `false` (and leaves the priors unchanged) if not all of the root's children have
+    /// been created yet.
+    fn set_root_priors(&self, priors: &[(<Self::GD as GameDynamics>::Action, f64)]) -> bool;
+
+    /// Samples an action from the `SearchTree`'s root in proportion to `N(a)^(1 / temperature)`
+    /// where `N(a)` is the visit count of the edge (see
+    /// [`SearchTree::get_next_move_edges`]).  A `temperature` of `0.0` selects the most visited
+    /// action.  If none of the edges has been visited, the action is selected as in
+    /// [`SearchTree::best_action`].  The root is not moved; use [`SearchTree::apply_action`] to
+    /// apply the sampled action.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `temperature` is negative or not finite.
+    fn sample_action(
+        &self,
+        temperature: f64,
+        rng: &mut dyn RngCore,
+    ) -> Status<<Self::GD as GameDynamics>::Action>;
+
+    /// Returns a [`SearchReport`] with the statistics of the root's children and, if
+    /// `include_nodes` is `true`, of all nodes reachable from the root.  Reports of independently
+    /// searched trees can be combined via [`SearchReport::merge`] (see
+    /// [`search_root_parallel`](crate::search_root_parallel)).
+    fn search_report(
+        &self,
+        include_nodes: bool,
+    ) -> SearchReport<<Self::GD as GameDynamics>::Action, <Self::GD as GameDynamics>::Score>
+    where
+        <Self::GD as GameDynamics>::Score: Clone;
+
+    /// Recomputes the scores of all `Node`s reachable from the root without changing the
+    /// structure of the `SearchTree`, e.g. to compare evaluators on the same explored DAG after
+    /// the parameters of the `GameDynamics` (which can be modified via interior mutability, see
+    /// [`SearchTree::get_game_dynamics`]) were changed.  First, `GameDynamics::score_leaf` (or
+    /// `GameDynamics::score_repetition`) is re-run on all leaves using `n_threads` threads; then
+    /// `GameDynamics::backprop_scores` is re-run on all other `Node`s from the leaves to the root
+    /// in the order of [`SearchTree::find_children_sorted_with_depth`].  A leaf with multiple
+    /// parents is scored using one of its parents; the parents' scores passed to `score_leaf`
+    /// are the scores before rescoring.  Blocks other operations on the `SearchTree` while running
+    /// and returns the number of leaves that were rescored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n_threads` is zero.
+    fn rescore(&self, n_threads: usize) -> usize
+    where
+        NodeAlias<Self::GD, Self::Memory>: OnDrop + Send + Sync,
+        Self::GD: Sync;
+
     /// Returns a vector of topologically sorted `(ArcNode, usize)` pairs where the `usize`
     /// indicates the distance from the `ArcNode` to the leaf that has the maximum reachable depth.
     /// The vector is sorted such that index `0` is a leaf and the last element is the root node.
@@ -150,17 +247,74 @@ pub trait SearchTree {
     where
         NodeAlias<Self::GD, Self::Memory>: OnDrop;
 
+    /// Returns a [`Cursor`] pointing to the root.
+    fn root_cursor(&self) -> Cursor<'_, NodeAlias<Self::GD, Self::Memory>, Self::GD>
+    where
+        NodeAlias<Self::GD, Self::Memory>: OnDrop;
+
+    /// Returns a [`Cursor`] pointing to the `Node` reached from the root by applying `actions` in
+    /// order, or `None` if the `Node` has not been created.
+    fn find_by_path(
+        &self,
+        actions: &[<Self::GD as GameDynamics>::Action],
+    ) -> Option<Cursor<'_, NodeAlias<Self::GD, Self::Memory>, Self::GD>>
+    where
+        NodeAlias<Self::GD, Self::Memory>: OnDrop;
+
+    /// Returns a [`Cursor`] pointing to the `Node` reachable from the root with the given `player`
+    /// and `state`, or `None` if there is no such `Node`.
+    fn find_by_state(
+        &self,
+        player: &<Self::GD as GameDynamics>::Player,
+        state: &<Self::GD as GameDynamics>::State,
+    ) -> Option<Cursor<'_, NodeAlias<Self::GD, Self::Memory>, Self::GD>>
+    where
+        NodeAlias<Self::GD, Self::Memory>: OnDrop;
+
+    /// Returns [`Cursor`]s pointing to all `Node`s reachable from the root that match `filter`,
+    /// ordered by their distance from the root.
+    fn find_nodes(
+        &self,
+        filter: &NodeFilter,
+    ) -> Vec<Cursor<'_, NodeAlias<Self::GD, Self::Memory>, Self::GD>>
+    where
+        NodeAlias<Self::GD, Self::Memory>: OnDrop;
+
+    /// Returns the number of transpositions reachable from the root, i.e. `Node`s with more than
+    /// one parent.
+    fn count_transpositions(&self) -> usize;
+
     /// Returns a `HashSet` of all `Node`s currently in the `SearchTree`.
     fn get_registry_nodes(&self) -> HashSet<WeakWrap<NodeAlias<Self::GD, Self::Memory>>>
     where
         NodeAlias<Self::GD, Self::Memory>: OnDrop;
 
-    /// Returns summary statistics for the `SearchTree`'s registry.
+    /// Returns summary statistics for the `SearchTree`'s registry and the search.  See
+    /// [`RegistryInfo::snapshot`](crate::RegistryInfo::snapshot) and
+    /// [`RegistryInfo::reset`](crate::RegistryInfo::reset).
     fn get_registry_info(&self) -> &RegistryInfo;
 
     /// Returns a reference to the game dynamics.
     fn get_game_dynamics(&self) -> Arc<Self::GD>;
 
+    /// Registers a [`TreeObserver`](crate::TreeObserver) that is notified of subsequent changes to
+    /// the `SearchTree`.
+    fn add_observer(
+        &self,
+        observer: Arc<
+            dyn TreeObserver<
+                <Self::GD as GameDynamics>::State,
+                <Self::GD as GameDynamics>::Player,
+                <Self::GD as GameDynamics>::Action,
+                <Self::GD as GameDynamics>::Score,
+            >,
+        >,
+    ) where
+        <Self::GD as GameDynamics>::State: 'static,
+        <Self::GD as GameDynamics>::Player: 'static + Clone,
+        <Self::GD as GameDynamics>::Action: 'static,
+        <Self::GD as GameDynamics>::Score: 'static + Clone;
+
     #[doc(hidden)]
     #[cfg(any(test, feature = "test_internals"))]
     fn get_tree(&self) -> &TreeAlias<Self::GD, Self::Memory>
@@ -191,6 +345,11 @@ where
         Self::best_action(&self)
     }
 
+    #[inline(always)]
+    fn principal_variation(&self) -> Vec<<Self::GD as GameDynamics>::Action> {
+        Self::principal_variation(self)
+    }
+
     #[inline(always)]
     fn apply_action(&self, a: &<Self::GD as GameDynamics>::Action) {
         Self::apply_action(self, a)
@@ -201,6 +360,16 @@ where
         Self::apply_best_action(self)
     }
 
+    #[inline(always)]
+    fn undo(&self) -> bool {
+        Self::undo(self)
+    }
+
+    #[inline(always)]
+    fn history_len(&self) -> usize {
+        Self::history_len(self)
+    }
+
     #[inline(always)]
     fn get_root_info(
         &self,
@@ -236,6 +405,50 @@ where
         Self::get_next_move_info(self)
     }
 
+    #[inline(always)]
+    fn get_next_move_edges(&self) -> Option<Vec<(<Self::GD as GameDynamics>::Action, EdgeStats)>> {
+        Self::get_next_move_edges(self)
+    }
+
+    #[inline(always)]
+    fn add_root_noise(&self, alpha: f64, epsilon: f64, rng: &mut dyn RngCore) -> bool {
+        Self::add_root_noise(self, alpha, epsilon, rng)
+    }
+
+    #[inline(always)]
+    fn set_root_priors(&self, priors: &[(<Self::GD as GameDynamics>::Action, f64)]) -> bool {
+        Self::set_root_priors(self, priors)
+    }
+
+    #[inline(always)]
+    fn sample_action(
+        &self,
+        temperature: f64,
+        rng: &mut dyn RngCore,
+    ) -> Status<<Self::GD as GameDynamics>::Action> {
+        Self::sample_action(self, temperature, rng)
+    }
+
+    #[inline(always)]
+    fn search_report(
+        &self,
+        include_nodes: bool,
+    ) -> SearchReport<<Self::GD as GameDynamics>::Action, <Self::GD as GameDynamics>::Score>
+    where
+        <Self::GD as GameDynamics>::Score: Clone,
+    {
+        Self::search_report(self, include_nodes)
+    }
+
+    #[inline(always)]
+    fn rescore(&self, n_threads: usize) -> usize
+    where
+        NodeAlias<Self::GD, Self::Memory>: OnDrop + Send + Sync,
+        Self::GD: Sync,
+    {
+        Self::rescore(self, n_threads)
+    }
+
     #[inline(always)]
     fn find_children_sorted_with_depth(
         &self,
@@ -246,6 +459,53 @@ where
         Self::find_children_sorted_with_depth(self)
     }
 
+    #[inline(always)]
+    fn root_cursor(&self) -> Cursor<'_, NodeAlias<Self::GD, Self::Memory>, Self::GD>
+    where
+        NodeAlias<Self::GD, Self::Memory>: OnDrop,
+    {
+        Self::root_cursor(self)
+    }
+
+    #[inline(always)]
+    fn find_by_path(
+        &self,
+        actions: &[<Self::GD as GameDynamics>::Action],
+    ) -> Option<Cursor<'_, NodeAlias<Self::GD, Self::Memory>, Self::GD>>
+    where
+        NodeAlias<Self::GD, Self::Memory>: OnDrop,
+    {
+        Self::find_by_path(self, actions)
+    }
+
+    #[inline(always)]
+    fn find_by_state(
+        &self,
+        player: &<Self::GD as GameDynamics>::Player,
+        state: &<Self::GD as GameDynamics>::State,
+    ) -> Option<Cursor<'_, NodeAlias<Self::GD, Self::Memory>, Self::GD>>
+    where
+        NodeAlias<Self::GD, Self::Memory>: OnDrop,
+    {
+        Self::find_by_state(self, player, state)
+    }
+
+    #[inline(always)]
+    fn find_nodes(
+        &self,
+        filter: &NodeFilter,
+    ) -> Vec<Cursor<'_, NodeAlias<Self::GD, Self::Memory>, Self::GD>>
+    where
+        NodeAlias<Self::GD, Self::Memory>: OnDrop,
+    {
+        Self::find_nodes(self, filter)
+    }
+
+    #[inline(always)]
+    fn count_transpositions(&self) -> usize {
+        Self::count_transpositions(self)
+    }
+
     #[inline(always)]
     fn get_registry_nodes(&self) -> HashSet<WeakWrap<NodeAlias<Self::GD, Self::Memory>>>
     where
@@ -264,6 +524,17 @@ where
         Self::get_game_dynamics(self)
     }
 
+    #[inline(always)]
+    fn add_observer(&self, observer: Arc<dyn TreeObserver<S, P, A, Q>>)
+    where
+        S: 'static,
+        P: 'static + Clone,
+        A: 'static,
+        Q: 'static + Clone,
+    {
+        Self::add_observer(self, observer)
+    }
+
     #[cfg(any(test, feature = "test_internals"))]
     #[inline(always)]
     fn get_tree(&self) -> &TreeAlias<Self::GD, Self::Memory>
@@ -288,7 +559,7 @@ pub enum Status<T> {
 }
 
 impl<T> Status<T> {
-    fn from_children<I, A, N>(c: &Children<I, A, N>, f: impl FnOnce(&HashMap<A, N>) -> T) -> Self {
+    fn from_children<I, A, N>(c: &Children<I, A, N>, f: impl FnOnce(&ChildMap<A, N>) -> T) -> Self {
         match c {
             Children::NewLeaf => Status::Pending,
             Children::BranchWip(h) => Status::ActionWip(f(h.scored_ref())),
@@ -298,6 +569,28 @@ impl<T> Status<T> {
     }
 }
 
+/// Determines how a `Tree` handles an action that results in the state of one of the expanded
+/// node's (grand)*parents, i.e. a cycle in the graph of game states (e.g. a repeated position in
+/// chess).  See [`Tree::with_cycle_handling`].
+///
+/// Except for `Acyclic`, every registry hit (i.e. transposition) requires a search of the
+/// (grand)*parents of the node being expanded, so cycle detection is only enabled on request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleHandling {
+    /// No cycle detection; the `GameDynamics` implementation guarantees that no state can be
+    /// reached again (see [`GameDynamics::apply_action`]).  This is the default.
+    Acyclic,
+    /// The repeated state becomes a new terminal node scored via
+    /// [`GameDynamics::score_repetition`] (e.g. as a draw).
+    Draw,
+    /// The action is discarded as though [`GameDynamics::apply_action`] returned `None`.
+    BreakEdge,
+    /// The repeated state becomes a new node that is distinct from the node with the same state
+    /// (i.e. nodes are keyed by their state and the number of times the state was repeated along
+    /// the path), which unrolls the cycle.
+    PathDependent,
+}
+
 /// Contains information about a specific `Node`.
 #[derive(Debug, Clone)]
 pub struct NodeInfo<S, P, Q> {
@@ -316,6 +609,25 @@ pub struct NodeInfo<S, P, Q> {
     pub n_children: Status<usize>,
 }
 
+/// Selects `Node`s in [`SearchTree::find_nodes`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeFilter {
+    /// The range of the number of actions from the root to the `Node` along a shortest path.
+    pub depth: RangeInclusive<usize>,
+    /// The range of the number of visits of the edges leading to the `Node` (see
+    /// [`Cursor::visits`]).
+    pub visits: RangeInclusive<usize>,
+}
+
+impl Default for NodeFilter {
+    fn default() -> Self {
+        Self {
+            depth: 0..=usize::MAX,
+            visits: 0..=usize::MAX,
+        }
+    }
+}
+
 use state_memory::StateMemory;
 pub mod state_memory {
     //! Provides mixins to statically configure how each node's state is stored in memory and
@@ -455,6 +767,11 @@ pub mod state_memory {
     }
 }
 
+// Children are stored with a fixed (i.e. not randomly seeded) hasher so that the order in which
+// `GD::select_node` receives the children only depends on the order in which they were inserted;
+// this is required for `ReproducibleSearch`
+pub(crate) type ChildMap<A, N> = HashMap<A, N, BuildHasherDefault<DefaultHasher>>;
+
 // NewLeaf: a child node without children of its own
 // BranchWip: a node whose children have *not* all been scored via `GD::score_leaf`
 // Branch: a node whose children have all been scored via `GD::score_leaf`
@@ -462,12 +779,12 @@ pub mod state_memory {
 enum Children<I, A, N> {
     NewLeaf,
     BranchWip(BranchWip<I, A, N>),
-    Branch(HashMap<A, N>),
+    Branch(ChildMap<A, N>),
     None,
 }
 
 impl<I, A, N> Children<I, A, N> {
-    fn as_map(&self) -> Option<&HashMap<A, N>> {
+    fn as_map(&self) -> Option<&ChildMap<A, N>> {
         match self {
             Children::BranchWip(h) => Some(h.scored_ref()),
             Children::Branch(h) => Some(h),
@@ -475,7 +792,7 @@ impl<I, A, N> Children<I, A, N> {
         }
     }
 
-    fn as_map_mut(&mut self) -> Option<&mut HashMap<A, N>> {
+    fn as_map_mut(&mut self) -> Option<&mut ChildMap<A, N>> {
         match self {
             Children::BranchWip(h) => Some(h.scored_mut()),
             Children::Branch(h) => Some(h),
@@ -491,6 +808,66 @@ impl<I, A, N> Children<I, A, N> {
     }
 }
 
+// The value stored for each action in `Children`: the child together with the statistics of the
+// edge leading to it; `Deref`s to the child so that most code can ignore the edge
+struct Edge<N> {
+    node: N,
+    prior: Option<f64>,
+    visits: AtomicUsize,
+    // set once the child was found to be dominated by a sibling (see `Tree::select_node`)
+    dominated: AtomicBool,
+}
+
+impl<N> Edge<N> {
+    fn new(node: N, prior: Option<f64>) -> Self {
+        Self {
+            node,
+            prior,
+            visits: AtomicUsize::new(0),
+            dominated: AtomicBool::new(false),
+        }
+    }
+
+    fn stats(&self) -> EdgeStats {
+        EdgeStats {
+            prior: self.prior,
+            visits: self.visits.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<N> Deref for Edge<N> {
+    type Target = N;
+    fn deref(&self) -> &Self::Target {
+        &self.node
+    }
+}
+
+// The edges that `Node::move_root` removed to disconnect the new root from the old root and from
+// the new root's other parents; kept in the `Tree`'s history so that the move can be undone
+struct Detached<A, N: ?Sized + OnDrop> {
+    edge: Edge<ArcWrap<N>>,
+    other_parents: Vec<(A, WeakWrap<N>, Edge<ArcWrap<N>>)>,
+}
+
+// The hash of a value with the standard library's `DefaultHasher`, e.g. of the player and state of
+// a `Node` (see `book_key`)
+pub(crate) fn hash_of<T: ?Sized + Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// The memory estimate shared by all nodes of a `Tree`: the estimated total number of bytes and
+// the number of bytes charged for each edge and each stored state (derived from the
+// `MemoryHints`), which are refunded when an edge is removed or a state is dropped
+#[derive(Default)]
+struct MemoryUsage {
+    bytes: AtomicUsize,
+    edge_bytes: AtomicUsize,
+    state_bytes: AtomicUsize,
+}
+
 use branch_wip::BranchWip;
 mod branch_wip {
     // The main purpose is to provide an abstraction around building a new branch and provide a
@@ -498,8 +875,9 @@ mod branch_wip {
     // completion (if they are not able to steal any of the work) without holding a reference to
     // `BranchWip` (which would require maintaining a lock)
 
+    use super::ChildMap;
+
     use std::cmp::Eq;
-    use std::collections::HashMap;
     use std::hash::Hash;
     use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
 
@@ -532,9 +910,11 @@ mod branch_wip {
     pub(crate) struct BranchWip<I, A, N> {
         unscored: I,
         unscored_done: bool,
-        scored: Option<HashMap<A, N>>,
+        scored: Option<ChildMap<A, N>>,
         scores_pending: usize,
         notifier: Arc<Notifier>,
+        // set for a branch that is resumed from an existing map (see `BranchWip::resume`)
+        skip_scored: bool,
     }
 
     impl<I, A, N> BranchWip<I, A, N> {
@@ -542,25 +922,43 @@ mod branch_wip {
             Self {
                 unscored,
                 unscored_done: false,
-                scored: Some(HashMap::new()),
+                scored: Some(ChildMap::default()),
                 scores_pending: 0,
                 notifier: Arc::new(Notifier::new()),
+                skip_scored: false,
+            }
+        }
+
+        // Completes an existing map of children: the pairs of `unscored` whose action is already
+        // in `scored` are skipped
+        pub fn resume(unscored: I, scored: ChildMap<A, N>) -> Self {
+            Self {
+                scored: Some(scored),
+                skip_scored: true,
+                ..Self::new(unscored)
             }
         }
 
-        pub fn scored_ref(&self) -> &HashMap<A, N> {
+        pub fn scored_ref(&self) -> &ChildMap<A, N> {
             self.scored.as_ref().unwrap()
         }
 
-        pub fn scored_mut(&mut self) -> &mut HashMap<A, N> {
+        pub fn scored_mut(&mut self) -> &mut ChildMap<A, N> {
             self.scored.as_mut().unwrap()
         }
 
         pub fn next_unscored<P>(&mut self) -> Option<(P, A)>
         where
             I: Iterator<Item = (P, A)>,
+            A: Eq + Hash,
         {
-            let n = self.unscored.next();
+            let mut n = self.unscored.next();
+            if self.skip_scored {
+                let scored = self.scored.as_ref().unwrap();
+                while matches!(n, Some((_, ref a)) if scored.contains_key(a)) {
+                    n = self.unscored.next();
+                }
+            }
             if n.is_some() {
                 self.scores_pending += 1;
             } else {
@@ -596,7 +994,7 @@ mod branch_wip {
             self.scores_pending -= 1;
         }
 
-        pub fn take_scored(&mut self) -> Option<HashMap<A, N>> {
+        pub fn take_scored(&mut self) -> Option<ChildMap<A, N>> {
             self.scored.take()
         }
 
@@ -628,16 +1026,30 @@ where
     M: ?Sized,
 {
     hash: u64,
+    // the number of times the state was repeated when the node was created with
+    // `CycleHandling::Draw` or `CycleHandling::PathDependent`; part of the registry key; only
+    // modified before the node is registered
+    repetition: AtomicUsize,
     player: P,
     depth: AtomicUsize,
     state: RwLock<Option<S>>,
     score: RwLock<Option<Q>>,
     score_gen: AtomicUsize,
     parents: RwLock<HashSet<(A, WeakWrap<Self>)>>,
-    children: RwLock<Children<I, A, ArcWrap<Self>>>,
+    children: RwLock<Children<I, A, Edge<ArcWrap<Self>>>>,
+    // set once a child that was dominated by a sibling has been disconnected from the node (see
+    // `Tree::release_dominated`); cleared when the children are restored at the root
+    released: AtomicBool,
     registry: Arc<RwLock<HashSet<WeakWrap<Self>>>>,
     registered: AtomicBool,
     game_dynamics: Arc<GD>,
+    observers: Arc<Observers<Self, A>>,
+    annotation: Annotation,
+    // the estimated memory used by all nodes of the tree (shared by all nodes) and the number of
+    // bytes charged for this node, which are subtracted from the total when the node is dropped;
+    // see `Node::charge`
+    memory: Arc<MemoryUsage>,
+    charged: AtomicUsize,
     // Use `fn() -> M` in `PhantomData` because it is covariant over `M` like `M` itself (which
     // requires drop check because it suggests ownership) or `*const  M` (which is not `Send`);
     // though just using `M` and going through the drop check would really be ok here since `M` is
@@ -662,7 +1074,8 @@ where
         registry: Arc<RwLock<HashSet<WeakNode<GD, S, P, A, Q, I, M>>>>,
     ) -> ArcWrap<Self> {
         let node = Self {
-            hash: Self::hash(&player, &state),
+            hash: hash_of(&(&player, &state)),
+            repetition: AtomicUsize::new(0),
             player,
             depth: AtomicUsize::new(0),
             state: RwLock::new(Some(state)),
@@ -670,9 +1083,14 @@ where
             score_gen: AtomicUsize::new(0),
             parents: RwLock::new(HashSet::new()),
             children: RwLock::new(Children::NewLeaf),
+            released: AtomicBool::new(false),
             registry,
             registered: AtomicBool::new(false),
             game_dynamics,
+            observers: Arc::new(Observers::new()),
+            annotation: Annotation::new(),
+            memory: Arc::new(MemoryUsage::default()),
+            charged: AtomicUsize::new(0),
             _marker: PhantomData,
         };
         let this = ArcWrap::<Self> {
@@ -693,10 +1111,13 @@ where
         let depth = AtomicUsize::new(0);
         let registry = Arc::clone(&parent_node.registry);
         let game_dynamics = Arc::clone(&parent_node.game_dynamics);
-        let hash = Node::<GD, S, P, A, Q, I, M>::hash(&player, &state);
+        let observers = Arc::clone(&parent_node.observers);
+        let memory = Arc::clone(&parent_node.memory);
+        let hash = hash_of(&(&player, &state));
         ArcNode {
             inner: Arc::new(Node {
                 hash,
+                repetition: AtomicUsize::new(0),
                 player,
                 depth,
                 state: RwLock::new(Some(state)),
@@ -704,9 +1125,14 @@ where
                 score_gen: AtomicUsize::new(0),
                 parents: RwLock::new(HashSet::new()),
                 children: RwLock::new(Children::NewLeaf),
+                released: AtomicBool::new(false),
                 registry,
                 registered: AtomicBool::new(false),
                 game_dynamics,
+                observers,
+                annotation: Annotation::new(),
+                memory,
+                charged: AtomicUsize::new(0),
                 _marker: PhantomData,
             }),
         }
@@ -732,8 +1158,50 @@ where
         debug_assert!(_r, "node already in registry");
     }
 
-    fn connect_child(self_arc: &ArcWrap<Self>, a: A, child: &ArcNode<GD, S, P, A, Q, I, M>)
-    where
+    // Adds `bytes` to the memory estimate of the node (and of the tree)
+    fn charge(&self, bytes: usize) {
+        self.charged.fetch_add(bytes, Ordering::Relaxed);
+        self.memory.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn refund(&self, bytes: usize) {
+        self.charged.fetch_sub(bytes, Ordering::Relaxed);
+        self.memory.bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    // Sets the sizes charged for the edges and states of the tree's nodes (see `Tree::new`)
+    fn set_memory_hints(&self, hints: &MemoryHints) {
+        let edge_bytes = Self::edge_bytes(hints);
+        self.memory.edge_bytes.store(edge_bytes, Ordering::Relaxed);
+        self.memory
+            .state_bytes
+            .store(hints.state, Ordering::Relaxed);
+    }
+
+    // The estimated size of a node excluding the edges leading to it: the node (including the
+    // reference counts of its `Arc`), the node's entry in the registry, and the heap memory owned by
+    // the score and (if the node stores it) the state
+    fn node_bytes(hints: &MemoryHints, stores_state: bool) -> usize {
+        std::mem::size_of::<Self>()
+            + 2 * std::mem::size_of::<usize>()
+            + std::mem::size_of::<WeakWrap<Self>>()
+            + hints.score
+            + if stores_state { hints.state } else { 0 }
+    }
+
+    // The estimated size of an edge, which is stored by both the parent and the child
+    fn edge_bytes(hints: &MemoryHints) -> usize {
+        std::mem::size_of::<(A, Edge<ArcWrap<Self>>)>()
+            + std::mem::size_of::<(A, WeakWrap<Self>)>()
+            + 2 * hints.action
+    }
+
+    fn connect_child(
+        self_arc: &ArcWrap<Self>,
+        a: A,
+        prior: Option<f64>,
+        child: &ArcNode<GD, S, P, A, Q, I, M>,
+    ) where
         A: Clone,
     {
         // connection from child to self (parent)
@@ -746,7 +1214,7 @@ where
         // connection from self (parent) to child
         let mut children_wlk = self_arc.children.write().unwrap();
         let branch_wip_mut = children_wlk.as_wip_mut().unwrap();
-        branch_wip_mut.scored_insert(a, ArcNode::clone(child));
+        branch_wip_mut.scored_insert(a, Edge::new(ArcNode::clone(child), prior));
 
         #[cfg(debug_assertions)]
         {
@@ -779,10 +1247,11 @@ where
         }
     }
 
-    fn move_root(&self, action: &A) -> ArcNode<GD, S, P, A, Q, I, M> {
+    fn move_root(&self, action: &A) -> (ArcNode<GD, S, P, A, Q, I, M>, Detached<A, Self>) {
         debug_assert_eq!(self.parents.read().unwrap().len(), 0);
+        trace_span!("move_root", hash = self.hash);
 
-        let new_root = self
+        let edge = self
             .children
             .write()
             .unwrap()
@@ -790,25 +1259,29 @@ where
             .expect("root's children not (yet) a `Branch`")
             .remove(action)
             .expect("missing child for action");
+        let new_root = ArcNode::clone(&edge.node);
+
+        let mut other_parents = Vec::new();
+        for (a, wn) in new_root.parents.write().unwrap().drain() {
+            let p = WeakNode::upgrade(&wn);
+            // the root itself is another parent if further actions lead to the new root
+            if p.as_ptr() == self.as_ptr() && a == *action {
+                continue;
+            }
+            let r = p.children.write().unwrap().as_map_mut().unwrap().remove(&a);
+            debug_assert!(r.is_some(), "parent did not know about child");
+            other_parents.extend(r.map(|e| (a, wn, e)));
+        }
+        trace_event!(
+            DEBUG,
+            new_root = new_root.hash,
+            other_parents = other_parents.len(),
+            "moving root"
+        );
 
-        let r = new_root
-            .parents
-            .write()
-            .unwrap()
-            .drain()
-            .map(|(a, wn)| (a, WeakNode::upgrade(&wn)))
-            .filter(|(_, p)| p.as_ptr() != self.as_ptr())
-            .all(|(a, p)| {
-                p.children
-                    .write()
-                    .unwrap()
-                    .as_map_mut()
-                    .unwrap()
-                    .remove(&a)
-                    .is_some()
-            });
-
-        debug_assert!(r, "parent did not know about child");
+        // the edges are stored by the history (if any) but no longer part of the tree
+        let edge_bytes = self.memory.edge_bytes.load(Ordering::Relaxed);
+        new_root.refund(edge_bytes * (1 + other_parents.len()));
 
         if let ref mut s @ None = *new_root.state.write().unwrap() {
             let state_old = self
@@ -820,13 +1293,123 @@ where
                 .clone();
 
             *s = GD::apply_action(&*self.game_dynamics, state_old, action);
+            new_root.charge(self.memory.state_bytes.load(Ordering::Relaxed));
+        }
+
+        let detached = Detached {
+            edge,
+            other_parents,
+        };
+        (new_root, detached)
+    }
+
+    // Reverts `move_root`: reconnects the child removed by `move_root` (i.e. the current root) to
+    // `self` and to its other parents; other parents that have been dropped in the meantime (i.e.
+    // that were only reachable from a previous root that was evicted from the history) are skipped
+    fn restore_root(self_arc: &ArcWrap<Self>, action: A, detached: Detached<A, Self>)
+    where
+        A: Clone,
+    {
+        let Detached {
+            edge,
+            other_parents,
+        } = detached;
+        let child = ArcNode::clone(&edge.node);
+        let edge_bytes = child.memory.edge_bytes.load(Ordering::Relaxed);
+        let mut parents_wlk = child.parents.write().unwrap();
+        debug_assert_eq!(parents_wlk.len(), 0);
+
+        for (a, wn, e) in other_parents {
+            let p = match wn.try_upgrade() {
+                Some(p) => p,
+                None => continue,
+            };
+            let r = p
+                .children
+                .write()
+                .unwrap()
+                .as_map_mut()
+                .unwrap()
+                .insert(a.clone(), e);
+            debug_assert!(r.is_none(), "parent already has a child for the action");
+            parents_wlk.insert((a, wn));
+            child.charge(edge_bytes);
+        }
+
+        let r = self_arc
+            .children
+            .write()
+            .unwrap()
+            .as_map_mut()
+            .expect("previous root's children not a `Branch`")
+            .insert(action.clone(), edge);
+        debug_assert!(
+            r.is_none(),
+            "previous root already has a child for the action"
+        );
+        parents_wlk.insert((action, ArcNode::downgrade(self_arc)));
+        child.charge(edge_bytes);
+    }
+
+    // Disconnects all children from the node; children that have no other parent are dropped
+    // (which in turn drops their own children if they are no longer reachable)
+    fn drop_children(self_arc: &ArcWrap<Self>) {
+        if let Some(ref mut children) = self_arc.children.write().unwrap().as_map_mut() {
+            for (a, c) in children.drain() {
+                Self::unlink_child(self_arc, a, c);
+            }
+        }
+    }
+
+    // Disconnects the child for `action` from the node (see `Node::drop_children`); returns
+    // `false` if the node has no such child
+    fn drop_child(self_arc: &ArcWrap<Self>, action: &A) -> bool {
+        let removed = match self_arc.children.write().unwrap().as_map_mut() {
+            Some(children) => children.remove_entry(action),
+            None => None,
+        };
+        match removed {
+            Some((a, c)) => {
+                Self::unlink_child(self_arc, a, c);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Removes the node from the parents of a child that was already removed from the node's
+    // children; the child is dropped with the edge unless it has another parent
+    fn unlink_child(self_arc: &ArcWrap<Self>, a: A, c: Edge<ArcWrap<Self>>) {
+        c.refund(c.memory.edge_bytes.load(Ordering::Relaxed));
+
+        // The below is effectively the same condition as:
+        // if c.parents.read().unwrap().len() == 1 {
+        if Arc::strong_count(&c.inner) == 1 {
+            *c.state.write().unwrap() = Some(c.inner.get_state());
         }
 
-        new_root.parents.write().unwrap().clear();
-        new_root
+        let r = c
+            .parents
+            .write()
+            .unwrap()
+            .remove(&(a, ArcNode::downgrade(self_arc)));
+
+        debug_assert!(
+            c.state.read().unwrap().is_some() || !c.parents.read().unwrap().is_empty(),
+            "child needs a parent",
+        );
+
+        debug_assert!(
+            r,
+            "\
+                could not remove dropped node as child's parents:\n\
+                \tchild {:p} parent {:p}\
+            ",
+            &*c.inner, &**self_arc,
+        );
     }
 
-    fn update_score(&self) -> bool
+    fn update_score(&self, stats: &RegistryInfo) -> bool
     where
         GD: GameDynamics<Score = Q>,
     {
@@ -854,12 +1437,17 @@ where
                 // loading `score_gen`
                 let gen = self.score_gen.load(Ordering::Acquire);
                 let score_cur_rlk = self.score.read().expect("no score");
-                let score_new = GD::backprop_scores(
-                    &*self.game_dynamics,
-                    &self.player,
-                    score_cur_rlk.as_ref(),
-                    scores,
-                );
+                stats.backprop_calls.fetch_add(1, Ordering::Relaxed);
+                let score_new = RegistryInfo::timed(&stats.backprop_nanos, || {
+                    annotation::install(&self.annotation, || {
+                        GD::backprop_scores(
+                            &*self.game_dynamics,
+                            &self.player,
+                            score_cur_rlk.as_ref(),
+                            scores,
+                        )
+                    })
+                });
                 drop(score_cur_rlk);
                 if let Some(score) = score_new {
                     let mut score_wlk = self.score.write().expect("no score");
@@ -891,27 +1479,32 @@ where
         }
     }
 
-    fn backprop_scores(self_arc: &ArcWrap<Self>) -> usize
+    fn backprop_scores(self_arc: &ArcWrap<Self>, stats: &RegistryInfo) -> usize
     where
         GD: GameDynamics<Score = Q>,
     {
         let mut n_updates = 0;
         let mut h = UniqueHeap::new();
         let d = self_arc.depth.load(Ordering::Relaxed);
+        trace_span!("backprop_scores", hash = self_arc.hash, depth = d);
         h.push((d, ArcWrap::clone(self_arc)));
 
         while let Some((_, node)) = h.pop() {
-            if node.update_score() {
+            if node.update_score(stats) {
                 n_updates += 1;
+                node.observers.notify(TreeEvent::ScoreUpdated, &node);
 
                 node.parents.read().unwrap().iter().for_each(|(_, p)| {
                     let p = WeakWrap::upgrade(&p);
                     let dp = p.depth.load(Ordering::Relaxed);
-                    h.push((dp, p));
+                    if h.push((dp, p)) {
+                        stats.backprop_fanout.fetch_add(1, Ordering::Relaxed);
+                    }
                 });
             }
         }
 
+        trace_event!(TRACE, n_updates, "backpropagated scores");
         n_updates
     }
 
@@ -1009,6 +1602,24 @@ where
         }
     }
 
+    // Returns `true` if `ancestor` is `self_arc` or one of its (grand)*parents
+    fn has_ancestor(self_arc: &ArcWrap<Self>, ancestor: &ArcWrap<Self>) -> bool {
+        let mut stack = vec![ArcNode::clone(self_arc)];
+        let mut visited = HashSet::new();
+        while let Some(node) = stack.pop() {
+            if node.as_ptr() == ancestor.as_ptr() {
+                return true;
+            }
+            for (_, p) in node.parents.read().unwrap().iter() {
+                let p = WeakNode::upgrade(p);
+                if visited.insert(p.as_ptr()) {
+                    stack.push(p);
+                }
+            }
+        }
+        false
+    }
+
     // Returns a vector of topologically sorted `ArcNode`s such that `sorted[0]` is a leaf node
     // with `inv_depth` == 0 and the last element is `self`.  The vector represents the children,
     // grandchildren, etc. of `self` (as well as `self` itself).  Note that `inv_depth` is always
@@ -1038,15 +1649,43 @@ where
         }
     }
 
-    fn as_ptr(&self) -> *const Self {
-        self as *const _
+    // Returns the nodes reachable from `self_arc` (including `self_arc`) in breadth first order
+    // together with the number of actions from `self_arc` to the node along a shortest path and the
+    // number of visits of the edges leading to the node (for `self_arc`, the number of visits of
+    // the edges leaving it)
+    fn find_reachable(self_arc: &ArcWrap<Self>) -> Vec<(ArcWrap<Self>, usize, usize)> {
+        let mut nodes = vec![(ArcNode::clone(self_arc), 0, 0)];
+        let mut index = HashMap::new();
+        index.insert(self_arc.as_ptr(), 0);
+        let mut i = 0;
+        while i < nodes.len() {
+            let depth = nodes[i].1 + 1;
+            let edges = match nodes[i].0.children.read().unwrap().as_map() {
+                Some(map) => map
+                    .values()
+                    .map(|e| (ArcNode::clone(&e.node), e.visits.load(Ordering::Relaxed)))
+                    .collect::<Vec<_>>(),
+                None => Vec::new(),
+            };
+            if i == 0 {
+                nodes[0].2 = edges.iter().map(|(_, visits)| visits).sum();
+            }
+            for (child, visits) in edges {
+                match index.entry(child.as_ptr()) {
+                    Entry::Occupied(j) => nodes[*j.get()].2 += visits,
+                    Entry::Vacant(j) => {
+                        j.insert(nodes.len());
+                        nodes.push((child, depth, visits));
+                    }
+                }
+            }
+            i += 1;
+        }
+        nodes
     }
 
-    fn hash(player: &P, state: &S) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        player.hash(&mut hasher);
-        state.hash(&mut hasher);
-        hasher.finish()
+    fn as_ptr(&self) -> *const Self {
+        self as *const _
     }
 
     /// Returns a [`NodeInfo`] with information about the `Node`.
@@ -1061,9 +1700,31 @@ where
             score: self.score.read().unwrap().clone(),
             state: self.state.read().unwrap().clone(),
             n_parents: self.parents.read().unwrap().len(),
-            n_children: Status::from_children(&*self.children.read().unwrap(), HashMap::len),
+            n_children: Status::from_children(&*self.children.read().unwrap(), ChildMap::len),
+        }
+    }
+
+    // Returns the actions leading to the node's children, the statistics of the edges and a
+    // `NodeInfo` for each child
+    pub(crate) fn get_children_info(&self) -> Vec<(A, EdgeStats, NodeInfo<S, P, Q>)>
+    where
+        A: Clone,
+        P: Clone,
+        Q: Clone,
+    {
+        match self.children.read().unwrap().as_map() {
+            Some(map) => map
+                .iter()
+                .map(|(a, e)| (a.clone(), e.stats(), e.node.get_node_info()))
+                .collect(),
+            None => Vec::new(),
         }
     }
+
+    /// Returns the [`Annotation`] of the `Node`.
+    pub fn annotation(&self) -> &Annotation {
+        &self.annotation
+    }
 }
 
 /// A trait used to remove nodes from the transposition table that are no longer reachable from the
@@ -1089,6 +1750,17 @@ where
     P: Hash + PartialEq<P>,
 {
     fn on_drop(self_arc: &ArcWrap<Self>) {
+        trace_span!(
+            "drop_node",
+            hash = self_arc.hash,
+            depth = self_arc.depth.load(Ordering::Relaxed)
+        );
+        if self_arc.registered.load(Ordering::Relaxed) {
+            self_arc.observers.notify(TreeEvent::NodeDropped, self_arc);
+        }
+        let charged = self_arc.charged.load(Ordering::Relaxed);
+        self_arc.refund(charged);
+
         if let Some(ref children) = self_arc.children.read().unwrap().as_map() {
             if !children.is_empty() && self_arc.state.read().unwrap().is_none() {
                 // the orphan must have a state because it is needed when the orphan's children
@@ -1116,35 +1788,7 @@ where
             );
         }
 
-        if let Some(ref mut children) = self_arc.children.write().unwrap().as_map_mut() {
-            for (a, c) in children.drain() {
-                // The below is effectively the same condition as:
-                // if c.parents.read().unwrap().len() == 1 {
-                if Arc::strong_count(&c.inner) == 1 {
-                    *c.state.write().unwrap() = Some(c.inner.get_state());
-                }
-
-                let r = c
-                    .parents
-                    .write()
-                    .unwrap()
-                    .remove(&(a, ArcNode::downgrade(self_arc)));
-
-                debug_assert!(
-                    c.state.read().unwrap().is_some() || !c.parents.read().unwrap().is_empty(),
-                    "child needs a parent",
-                );
-
-                debug_assert!(
-                    r,
-                    "\
-                        could not remove dropped node as child's parents:\n\
-                        \tchild {:p} parent {:p}\
-                    ",
-                    &*c.inner, &**self_arc,
-                );
-            }
-        }
+        Self::drop_children(self_arc);
 
         if self_arc.registered.load(Ordering::Relaxed) {
             let _r = self_arc
@@ -1179,6 +1823,7 @@ where
         H: Hasher,
     {
         h.write_u64(self.hash);
+        h.write_usize(self.repetition.load(Ordering::Relaxed));
     }
 }
 
@@ -1191,7 +1836,8 @@ where
     P: Hash + PartialEq<P>,
 {
     fn eq(&self, rhs: &Self) -> bool {
-        <Self as StateMemory>::eq(self, rhs)
+        self.repetition.load(Ordering::Relaxed) == rhs.repetition.load(Ordering::Relaxed)
+            && <Self as StateMemory>::eq(self, rhs)
     }
 }
 
@@ -1328,6 +1974,10 @@ impl<T: ?Sized + OnDrop> WeakWrap<T> {
             inner: self.inner.upgrade().expect("upgrade failed"),
         }
     }
+
+    fn try_upgrade(&self) -> Option<ArcWrap<T>> {
+        self.inner.upgrade().map(|inner| ArcWrap { inner })
+    }
 }
 
 impl<T: ?Sized + OnDrop> std::hash::Hash for WeakWrap<T>
@@ -1366,32 +2016,153 @@ impl<T: ?Sized + OnDrop> Clone for WeakWrap<T> {
     }
 }
 
-#[derive(Debug)]
-/// Contains information about a `Tree`'s registry.
-pub struct RegistryInfo {
-    /// The number of times an action applied to a leaf node has resulted in a state that already
-    /// existed in the [`Tree`].
-    pub hits: AtomicUsize,
-    /// The number of times an action applied to a leaf node has resulted in a state that is new in
-    /// the [`Tree`].
-    pub misses: AtomicUsize,
-    /// The number of nodes in the [`Tree`].
-    pub len: AtomicUsize,
+/// A read-only handle to a `Node` of a [`Tree`] used to navigate the DAG, e.g. to inspect
+/// arbitrary positions (see [`SearchTree::root_cursor`]).
+///
+/// A `Cursor` does not keep its `Node` alive: once the `Node` is no longer part of the `Tree`
+/// (i.e. it was pruned after an action was applied), [`Cursor::info`] and
+/// [`Cursor::state`] return `None` and the `Node` has neither children nor parents.
+pub struct Cursor<'a, N: ?Sized + OnDrop, GD: ?Sized + GameDynamics> {
+    tree: &'a Tree<N, GD>,
+    node: WeakWrap<N>,
 }
 
-impl RegistryInfo {
-    fn new() -> Self {
+impl<'a, N: ?Sized + OnDrop, GD: ?Sized + GameDynamics> Clone for Cursor<'a, N, GD> {
+    fn clone(&self) -> Self {
         Self {
-            hits: AtomicUsize::new(0),
-            misses: AtomicUsize::new(0),
-            len: AtomicUsize::new(0),
+            tree: self.tree,
+            node: self.node.clone(),
         }
     }
 }
 
-/// An acyclic collection of connected `Node`s with a unique root.
-#[derive(Debug)]
-pub struct Tree<N: ?Sized + OnDrop, GD: ?Sized> {
+impl<'a, N: ?Sized + OnDrop, GD: ?Sized + GameDynamics> Debug for Cursor<'a, N, GD> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cursor")
+            .field("node", &self.node.inner.as_ptr())
+            .finish()
+    }
+}
+
+impl<'a, GD, S, P, A, Q, II, I, M> Cursor<'a, Node<GD, S, P, A, Q, I, M>, GD>
+where
+    Node<GD, S, P, A, Q, I, M>: StateMemory<State = S>,
+    GD: GameDynamics<Player = P, State = S, Action = A, Score = Q, ActionIter = II>,
+    II: IntoIterator<IntoIter = I, Item = (P, A)>,
+    I: Iterator<Item = (P, A)>,
+    A: Hash + Eq + Clone,
+    S: Hash + PartialEq<S> + Clone,
+    P: Hash + PartialEq<P>,
+{
+    fn new(
+        tree: &'a Tree<Node<GD, S, P, A, Q, I, M>, GD>,
+        node: &ArcNode<GD, S, P, A, Q, I, M>,
+    ) -> Self {
+        Self {
+            tree,
+            node: ArcNode::downgrade(node),
+        }
+    }
+
+    // Calls `f` with the node while holding the `prune_lock` (so that the node is not dropped
+    // while it is accessed); returns `None` if the node has been dropped
+    fn with_node<T>(&self, f: impl FnOnce(&ArcNode<GD, S, P, A, Q, I, M>) -> T) -> Option<T> {
+        let _prune_rlk = self.tree.prune_lock.read().unwrap();
+        self.node.try_upgrade().map(|node| f(&node))
+    }
+
+    /// Returns a [`NodeInfo`] for the `Node`.
+    pub fn info(&self) -> Option<NodeInfo<S, P, Q>>
+    where
+        P: Clone,
+        Q: Clone,
+    {
+        self.with_node(|node| node.get_node_info())
+    }
+
+    /// Returns the state of the `Node` (computed if it is not stored, see [`state_memory`]).
+    pub fn state(&self) -> Option<S> {
+        self.with_node(|node| node.get_state())
+    }
+
+    /// Calls `f` with the [`Annotation`] of the `Node`.  The tree must not be modified by `f`
+    /// (e.g. via [`SearchTree::step`] or [`SearchTree::apply_action`]).
+    pub fn with_annotation<T>(&self, f: impl FnOnce(&Annotation) -> T) -> Option<T> {
+        self.with_node(|node| f(&node.annotation))
+    }
+
+    /// Returns the number of visits of the edges leading to the `Node` from all of its parents
+    /// (for the root, the number of visits of the edges leaving it).
+    pub fn visits(&self) -> usize {
+        self.with_node(|node| {
+            let parents = node.parents.read().unwrap();
+            if parents.is_empty() {
+                node.children
+                    .read()
+                    .unwrap()
+                    .as_map()
+                    .map_or(0, |map| map.values().map(|e| e.stats().visits).sum())
+            } else {
+                parents
+                    .iter()
+                    .filter_map(|(a, p)| {
+                        let p = p.try_upgrade()?;
+                        let children = p.children.read().unwrap();
+                        Some(children.as_map()?.get(a)?.stats().visits)
+                    })
+                    .sum()
+            }
+        })
+        .unwrap_or(0)
+    }
+
+    /// Returns the `Node`'s children together with the actions and the statistics of the edges
+    /// leading to them.  Empty if the children of the `Node` have not been created yet.
+    pub fn children(&self) -> Vec<(A, EdgeStats, Self)> {
+        self.with_node(|node| match node.children.read().unwrap().as_map() {
+            Some(map) => map
+                .iter()
+                .map(|(a, e)| (a.clone(), e.stats(), Self::new(self.tree, &e.node)))
+                .collect(),
+            None => Vec::new(),
+        })
+        .unwrap_or_default()
+    }
+
+    /// Returns the child reached by `action`, if it exists.
+    pub fn child(&self, action: &A) -> Option<Self> {
+        self.with_node(|node| {
+            let children = node.children.read().unwrap();
+            let edge = children.as_map()?.get(action)?;
+            Some(Self::new(self.tree, &edge.node))
+        })
+        .flatten()
+    }
+
+    /// Returns the `Node`'s parents together with the actions leading from the parents to the
+    /// `Node`.  Empty for the root.
+    pub fn parents(&self) -> Vec<(A, Self)> {
+        self.with_node(|node| {
+            node.parents
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(a, p)| {
+                    let p = Self {
+                        tree: self.tree,
+                        node: p.clone(),
+                    };
+                    (a.clone(), p)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+}
+
+/// An acyclic collection of connected `Node`s with a unique root.
+#[derive(Debug)]
+pub struct Tree<N: ?Sized + OnDrop, GD: ?Sized + GameDynamics> {
     root: RwLock<ArcWrap<N>>,
     // `registry` is a transposition table used to check whether a new node already exists in the
     // tree because there was some other sequence of actions that would lead to the same game
@@ -1401,6 +2172,35 @@ pub struct Tree<N: ?Sized + OnDrop, GD: ?Sized> {
     reg_info: RegistryInfo,
     game_dynamics: Arc<GD>,
     prune_lock: RwLock<()>,
+    cycle_handling: CycleHandling,
+    // previous roots, most recent last; see `Tree::with_history`
+    history: Mutex<VecDeque<HistoryEntry<N, GD::Action>>>,
+    max_history: usize,
+    keep_subtrees: bool,
+    memory_hints: MemoryHints,
+    // see `Tree::with_dominance_pruning`; the dominated children found by `Tree::select_node` are
+    // queued until the `prune_lock` can be acquired to release them
+    prune_dominated: bool,
+    dominated: Mutex<Vec<(WeakWrap<N>, GD::Action)>>,
+    // see `Tree::with_leaf_batch_size`
+    leaf_batch_size: usize,
+}
+
+// A previous root together with the action that moved the root away from it; `detached` is only
+// kept if the subtrees of previous roots are kept
+struct HistoryEntry<N: ?Sized + OnDrop, A> {
+    root: ArcWrap<N>,
+    action: A,
+    detached: Option<Detached<A, N>>,
+}
+
+impl<N: ?Sized + OnDrop, A> Debug for HistoryEntry<N, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HistoryEntry")
+            .field("root", &format!("{:p}", &*self.root))
+            .field("keeps_subtree", &self.detached.is_some())
+            .finish()
+    }
 }
 
 impl<GD, S, P, A, Q, II, I, M> Tree<Node<GD, S, P, A, Q, I, M>, GD>
@@ -1423,35 +2223,213 @@ where
             root_state,
             Arc::clone(&registry),
         );
+        let memory_hints = MemoryHints::default();
+        root.set_memory_hints(&memory_hints);
+        root.charge(Node::node_bytes(&memory_hints, true));
         Tree {
             root: RwLock::new(root),
             registry,
             reg_info: RegistryInfo::new(),
             game_dynamics,
             prune_lock: RwLock::new(()),
+            cycle_handling: CycleHandling::Acyclic,
+            history: Mutex::new(VecDeque::new()),
+            max_history: 0,
+            keep_subtrees: false,
+            memory_hints,
+            prune_dominated: false,
+            dominated: Mutex::new(Vec::new()),
+            leaf_batch_size: 1,
         }
     }
 
+    /// Sets how actions that lead back to the state of a (grand)*parent are handled (the default
+    /// is [`CycleHandling::Acyclic`]).
+    ///
+    /// With any other `CycleHandling`, every registry hit runs a depth-first search over all
+    /// (grand)*parents of the expanded node while holding the write lock of the registry, i.e.
+    /// while no other thread can create a node.  The cost of a hit therefore grows with the number
+    /// of nodes between the root and the expanded node (including those reached through
+    /// transpositions), which can dominate the time spent expanding deep trees.
+    pub fn with_cycle_handling(mut self, cycle_handling: CycleHandling) -> Self {
+        self.cycle_handling = cycle_handling;
+        self
+    }
+
+    /// Keeps up to `max_len` previous roots so that moves can be taken back via
+    /// [`SearchTree::undo`] (the default is to keep no history, in which case the nodes that are
+    /// no longer reachable from the root are pruned when an action is applied).
+    ///
+    /// If `keep_subtrees` is `true`, the complete trees below the previous roots are kept, i.e.
+    /// nothing is pruned until a previous root is evicted from the history; an undo then restores
+    /// the previous root with all of its statistics (including those of the subtree that was
+    /// expanded after the move) and is cheap.  Otherwise, only the previous roots themselves are
+    /// kept: applying an action prunes all other children of the old root, and an undo prunes the
+    /// tree below the root that is taken back, so the restored root has to be expanded again.
+    ///
+    /// Nodes kept in the history remain in the registry (and are included in
+    /// [`SearchTree::get_registry_info`]), so transpositions can connect newly created nodes to
+    /// them.
+    pub fn with_history(mut self, max_len: usize, keep_subtrees: bool) -> Self {
+        self.max_history = max_len;
+        self.keep_subtrees = keep_subtrees;
+        self
+    }
+
+    /// Sets the [`MemoryHints`] used to estimate the memory used by the `Tree` (see
+    /// [`RegistryInfo::memory_bytes`]).  The estimates of existing nodes other than the root are
+    /// not updated, i.e. the hints should be set before the `Tree` is searched.
+    pub fn with_memory_hints(mut self, memory_hints: MemoryHints) -> Self {
+        let root = self.root.read().unwrap();
+        root.refund(Node::node_bytes(&self.memory_hints, true));
+        root.set_memory_hints(&memory_hints);
+        root.charge(Node::node_bytes(&memory_hints, true));
+        drop(root);
+        self.memory_hints = memory_hints;
+        self
+    }
+
+    /// Skips children that are provably dominated by a sibling when selecting a node, and
+    /// releases their subtrees (the default is `false`).  A child is dominated if its upper bound
+    /// is below the lower bound of one of its siblings, where the bounds are given by
+    /// [`GameDynamics::score_bounds`]; such a child can never be the action selected by
+    /// [`SelectNodeState::Exploit`].
+    ///
+    /// Dominated children are disconnected from their parent by a later step (once no other
+    /// thread is in the middle of a step) or when an action is applied; nodes that are no longer
+    /// reachable are then removed from the registry.  The score of the parent is recomputed via
+    /// [`GameDynamics::backprop_scores`] when a child is disconnected, so the released child no
+    /// longer contributes to scores that aggregate all children (e.g. sums or means).
+    ///
+    /// The children of the root are only skipped, never disconnected, so that any legal action
+    /// (e.g. a losing move of an opponent) can still be applied with
+    /// [`SearchTree::apply_action`]; when the root moves to a node whose dominated children were
+    /// released, they are created again.
+    pub fn with_dominance_pruning(mut self, enabled: bool) -> Self {
+        self.prune_dominated = enabled;
+        self
+    }
+
+    /// Sets the maximum number of children of a leaf that a thread creates at once (the default
+    /// is `1`).  If more than one of these children is new, i.e. their states are not in the
+    /// registry, they are scored with a single call to [`GameDynamics::score_leaves`] instead of
+    /// one call to [`GameDynamics::score_leaf`] each, which allows an evaluator to fill its
+    /// batches (see [`Batcher`](crate::Batcher)) even if the `Tree` is searched by a single
+    /// thread.  Other threads can only join the expansion of a leaf for the children that have not
+    /// been claimed yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn with_leaf_batch_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "`size` must be positive");
+        self.leaf_batch_size = size;
+        self
+    }
+
     fn step(&self) -> Option<S> {
-        let _prune_rlk = self.prune_lock.read().unwrap();
-        let node = ArcNode::clone(&*self.root.read().unwrap());
-        let state = node.get_state();
-        self.step_into(state, node)
+        self.reg_info.steps.fetch_add(1, Ordering::Relaxed);
+        let state = {
+            let _prune_rlk = RegistryInfo::timed(&self.reg_info.prune_lock_wait_nanos, || {
+                self.prune_lock.read().unwrap()
+            });
+            let node = ArcNode::clone(&*self.root.read().unwrap());
+            let state = node.get_state();
+            self.step_into(state, node)
+        };
+        if self.prune_dominated && !self.dominated.lock().unwrap().is_empty() {
+            if let Ok(_prune_wlk) = self.prune_lock.try_write() {
+                self.release_dominated();
+            }
+        }
+        state
+    }
+
+    // Disconnects the queued dominated children from their parents; must be called while holding
+    // the write lock of the `prune_lock`.  The children of the root are kept (they are only
+    // skipped by `Tree::select_node`) so that every legal action can still be applied
+    fn release_dominated(&self) -> usize {
+        let dominated = std::mem::take(&mut *self.dominated.lock().unwrap());
+        let root = ArcNode::clone(&*self.root.read().unwrap());
+        let mut n_released = 0;
+        for (parent, action) in dominated {
+            // the parent may have been pruned in the meantime, or become the root
+            if let Some(parent) = parent.try_upgrade() {
+                if parent.as_ptr() == root.as_ptr() {
+                    continue;
+                }
+                if Node::drop_child(&parent, &action) {
+                    parent.released.store(true, Ordering::Relaxed);
+                    // the released child may have contributed to the score of the parent (e.g. if
+                    // `GD::backprop_scores` sums or averages the scores of the children)
+                    Node::backprop_scores(&parent, &self.reg_info);
+                    n_released += 1;
+                }
+            }
+        }
+        trace_event!(DEBUG, n_released, "released dominated children");
+        n_released
+    }
+
+    // Recreates the children of the root that were released as dominated while it was an inner
+    // node (see `Tree::release_dominated`), so that every legal action can be applied; the
+    // children that still exist are kept
+    fn restore_released(&self, root: &ArcNode<GD, S, P, A, Q, I, M>) {
+        if !root.released.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let state = root.get_state();
+        let players_actions = annotation::install(&root.annotation, || {
+            self.game_dynamics.available_actions(&root.player, &state)
+        });
+        let player_acts = match players_actions {
+            Some(player_acts) => player_acts,
+            None => return,
+        };
+        {
+            let mut children_wlk = root.children.write().unwrap();
+            let map = match std::mem::replace(&mut *children_wlk, Children::NewLeaf) {
+                Children::Branch(map) => map,
+                children => {
+                    *children_wlk = children;
+                    return;
+                }
+            };
+            let branch_wip = BranchWip::resume(player_acts.into_iter(), map);
+            *children_wlk = Children::BranchWip(branch_wip);
+            root.charge(self.memory_hints.action_iter);
+        }
+        trace_event!(DEBUG, hash = root.hash, "restoring released children");
+        self.make_branch(&state, root);
+        Node::backprop_scores(root, &self.reg_info);
     }
 
     fn step_into(&self, mut node_state: S, mut node: ArcNode<GD, S, P, A, Q, I, M>) -> Option<S> {
+        trace_span!("step_into", depth = node.depth.load(Ordering::Relaxed));
         loop {
             let children_rlk = node.children.read().unwrap();
             match *children_rlk {
                 Children::NewLeaf => {
                     drop(children_rlk);
+                    trace_event!(
+                        TRACE,
+                        hash = node.hash,
+                        depth = node.depth.load(Ordering::Relaxed),
+                        "expanding leaf"
+                    );
                     self.make_branch_wip(&node_state, &node);
                     self.make_branch(&node_state, &node);
-                    Node::backprop_scores(&node);
+                    Node::backprop_scores(&node, &self.reg_info);
                     return Some(node_state);
                 }
                 Children::BranchWip(_) => {
                     drop(children_rlk);
+                    trace_event!(
+                        TRACE,
+                        hash = node.hash,
+                        depth = node.depth.load(Ordering::Relaxed),
+                        "joining expansion"
+                    );
                     // `make_branch` returns when `Children::BranchWip` is converted to
                     // `Children::Branch`; so loop again
                     self.make_branch(&node_state, &node);
@@ -1461,7 +2439,9 @@ where
                         Self::select_node(&self, &node, &node_state, map, SelectNodeState::Explore);
 
                     // get the selected child node, calculate its state, and keep recursing
-                    let next_node = ArcNode::clone(&map.get(&action).unwrap());
+                    let edge = map.get(&action).unwrap();
+                    edge.visits.fetch_add(1, Ordering::Relaxed);
+                    let next_node = ArcNode::clone(edge);
 
                     drop(children_rlk);
                     node = next_node;
@@ -1472,6 +2452,12 @@ where
                     // Currently the implementation assumes that the score of a `Terminal` node is
                     // immutable even if the selection path leads there repeatedly
                     // Node::backprop_scores(&node);
+                    trace_event!(
+                        TRACE,
+                        hash = node.hash,
+                        depth = node.depth.load(Ordering::Relaxed),
+                        "reached terminal node"
+                    );
                     return None;
                 }
             }
@@ -1482,10 +2468,19 @@ where
         &self,
         parent_node: &ArcNode<GD, S, P, A, Q, I, M>,
         parent_node_state: &S,
-        children: &HashMap<A, ArcNode<GD, S, P, A, Q, I, M>>,
+        children: &ChildMap<A, Edge<ArcNode<GD, S, P, A, Q, I, M>>>,
         purpose: SelectNodeState,
     ) -> A {
-        let scores_and_actions = children.iter().map(|(a, child)| {
+        let dominated = if self.prune_dominated {
+            // only steps explore, so read-only callers (e.g. `Tree::best_action`) skip dominated
+            // children without queueing them for release
+            let queue = purpose == SelectNodeState::Explore;
+            self.find_dominated(parent_node, children, queue)
+        } else {
+            HashSet::new()
+        };
+        let children = children.iter().filter(|(a, _)| !dominated.contains(a));
+        let scores_actions_and_edges = children.map(|(a, child)| {
             // Taking a standard shared reference to the score will not compile because the
             // `Ref<'a,T>` would go out of scope at the end of the closure, and the lifetime of the
             // return value of `<Ref<'a,T> as Deref>::deref` is tied to the lifetime of the
@@ -1502,41 +2497,136 @@ where
             // each of the children's `scores` field in `GD::select_node`
             // let s = child.score.read().unwrap().as_ref().unwrap();
             let q = lockref::Ref::new(child.score.read().unwrap(), |q| &**q);
-            (q, a)
+            (q, a, child.stats())
         });
 
-        GD::select_node(
-            &*self.game_dynamics,
-            parent_node.score.read().unwrap().as_ref(),
-            &parent_node.player,
-            parent_node_state,
-            purpose,
-            scores_and_actions,
-        )
+        annotation::install(&parent_node.annotation, || {
+            GD::select_node_with_edges(
+                &*self.game_dynamics,
+                parent_node.score.read().unwrap().as_ref(),
+                &parent_node.player,
+                parent_node_state,
+                purpose,
+                scores_actions_and_edges,
+            )
+        })
+    }
+
+    // Children whose upper bound is below the greatest lower bound among their siblings; if `queue`
+    // is set, newly found children are flagged and queued so that their subtrees can be released
+    fn find_dominated<'a>(
+        &self,
+        parent_node: &ArcNode<GD, S, P, A, Q, I, M>,
+        children: &'a ChildMap<A, Edge<ArcNode<GD, S, P, A, Q, I, M>>>,
+        queue: bool,
+    ) -> HashSet<&'a A> {
+        let bounds = children
+            .iter()
+            .map(|(a, child)| {
+                let bounds =
+                    child.score.read().unwrap().as_ref().and_then(|q| {
+                        GD::score_bounds(&*self.game_dynamics, &parent_node.player, q)
+                    });
+                (a, child, bounds)
+            })
+            .collect::<Vec<_>>();
+        let max_lower = bounds
+            .iter()
+            .filter_map(|(_, _, bounds)| bounds.map(|(lower, _)| lower))
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        let dominated = bounds
+            .into_iter()
+            .filter(|(_, child, bounds)| {
+                let is_dominated = match bounds {
+                    Some((_, upper)) => *upper < max_lower,
+                    None => false,
+                };
+                is_dominated || child.dominated.load(Ordering::Relaxed)
+            })
+            .map(|(a, child, _)| (a, child))
+            .collect::<Vec<_>>();
+        // only possible if the bounds are not sound; rather than selecting from no children at
+        // all (or releasing all of them), none of them are skipped
+        if dominated.len() == children.len() {
+            return HashSet::new();
+        }
+        // the children of the root are never released (see `Tree::release_dominated`)
+        if queue && parent_node.as_ptr() != self.root.read().unwrap().as_ptr() {
+            for (a, child) in dominated.iter() {
+                if !child.dominated.swap(true, Ordering::Relaxed) {
+                    self.dominated
+                        .lock()
+                        .unwrap()
+                        .push((ArcNode::downgrade(parent_node), (*a).clone()));
+                }
+            }
+        }
+        dominated.into_iter().map(|(a, _)| a).collect()
     }
 
+    // `leaf_score` is the score of `node` if it was already computed by `GD::score_leaves`; it is
+    // discarded if `node` turns out to be a registry hit or a repetition
     fn create_scored_child(
         &self,
         parent_node: &ArcNode<GD, S, P, A, Q, I, M>,
-        player: P,
+        node: ArcNode<GD, S, P, A, Q, I, M>,
         action: A,
-        state: S,
+        prior: Option<f64>,
+        leaf_score: Option<Option<Q>>,
     ) {
-        let node = Node::new_child(parent_node, player, state);
+        trace_span!(
+            "create_scored_child",
+            parent = parent_node.hash,
+            hash = node.hash
+        );
 
         // check if node is in the registry, if not: add to registry, then calculate score, then
         // connect node to tree
         debug_assert!(node.state.read().unwrap().is_some());
-        let mut reg_wlk = self.registry.write().unwrap();
+        let mut reg_wlk = RegistryInfo::timed(&self.reg_info.registry_lock_wait_nanos, || {
+            self.registry.write().unwrap()
+        });
+
+        // a registry hit on `parent_node` or one of its (grand)*parents would close a cycle
+        if self.cycle_handling != CycleHandling::Acyclic {
+            while let Some(existing_node) = reg_wlk.get(&ArcNode::downgrade(&node)) {
+                if !Node::has_ancestor(parent_node, &WeakNode::upgrade(existing_node)) {
+                    break;
+                }
+                self.reg_info.cycles.fetch_add(1, Ordering::Relaxed);
+                if self.cycle_handling == CycleHandling::BreakEdge {
+                    drop(reg_wlk);
+                    let mut children_wlk = parent_node.children.write().unwrap();
+                    children_wlk.as_wip_mut().unwrap().decrease_scores_pending();
+                    return;
+                }
+                // look up the next repetition of the state instead; this terminates since each
+                // repetition that closes a cycle is a distinct (grand)*parent of `parent_node`
+                node.repetition.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let terminal_repetition = self.cycle_handling == CycleHandling::Draw
+            && node.repetition.load(Ordering::Relaxed) > 0;
+
         match reg_wlk.get(&ArcNode::downgrade(&node)) {
             Some(existing_node) => {
                 let node = WeakNode::upgrade(existing_node);
-                Node::connect_child(parent_node, action, &node);
+                Node::connect_child(parent_node, action.clone(), prior, &node);
                 drop(reg_wlk);
 
                 self.reg_info.hits.fetch_add(1, Ordering::Relaxed);
+                trace_event!(
+                    TRACE,
+                    depth = node.depth.load(Ordering::Relaxed),
+                    "registry hit"
+                );
+                node.charge(Node::edge_bytes(&self.memory_hints));
 
                 Node::set_min_depth(&node);
+                self.update_max_depth(&node);
+                node.observers
+                    .notify(TreeEvent::RegistryHit(&action), &node);
                 // `node.score` may be `None` but the score will be set before a read lock on
                 // `node.score` is available (see `None` arm below)
             }
@@ -1544,7 +2634,10 @@ where
                 // acquire a write lock on `node.score` before `reg_wlk` is released so that other
                 // threads block on trying to read `node.score` before it is calculated
                 let mut score_wlk = node.score.write().unwrap();
-                Node::connect_child(parent_node, action, &node);
+                if terminal_repetition {
+                    *node.children.write().unwrap() = Children::None;
+                }
+                Node::connect_child(parent_node, action.clone(), prior, &node);
                 Node::register(&node, Some(&mut reg_wlk));
                 drop(reg_wlk);
 
@@ -1556,48 +2649,95 @@ where
                 // `node` may actually be stale / incorrect, so we update the depth here while we
                 // have a write lock on `score`
                 Node::set_min_depth(&node);
+                self.update_max_depth(&node);
+                trace_event!(
+                    TRACE,
+                    depth = node.depth.load(Ordering::Relaxed),
+                    "registry miss"
+                );
 
                 // Only run `GD::score_leaf` for nodes that don't exist in the registry
                 // it's ok to hold the read lock on `node.state` for an extended period of time (if
                 // `GD::score_leaf` is slow) since no write lock is acquired on this field during
                 // expansion (a write lock is only acquired on this field during `move_root` /
                 // `Drop::drop` and `StateMemory::modify_state`)
-                *score_wlk = GD::score_leaf(
-                    &*self.game_dynamics,
-                    parent_node.score.read().unwrap().as_ref(),
-                    &parent_node.player,
-                    node.state.read().unwrap().as_ref().unwrap(),
-                );
+                *score_wlk = annotation::install(&node.annotation, || {
+                    if terminal_repetition {
+                        GD::score_repetition(
+                            &*self.game_dynamics,
+                            parent_node.score.read().unwrap().as_ref(),
+                            &parent_node.player,
+                            node.state.read().unwrap().as_ref().unwrap(),
+                        )
+                    } else if let Some(leaf_score) = leaf_score {
+                        leaf_score
+                    } else {
+                        self.reg_info
+                            .score_leaf_calls
+                            .fetch_add(1, Ordering::Relaxed);
+                        RegistryInfo::timed(&self.reg_info.score_leaf_nanos, || {
+                            GD::score_leaf(
+                                &*self.game_dynamics,
+                                parent_node.score.read().unwrap().as_ref(),
+                                &parent_node.player,
+                                node.state.read().unwrap().as_ref().unwrap(),
+                            )
+                        })
+                    }
+                });
                 drop(score_wlk);
 
                 <Node<GD, S, P, A, Q, I, M> as StateMemory>::modify_state(&node.state);
+                let stores_state = node.state.read().unwrap().is_some();
+                node.charge(
+                    Node::node_bytes(&self.memory_hints, stores_state)
+                        + Node::edge_bytes(&self.memory_hints),
+                );
+                node.observers
+                    .notify(TreeEvent::NodeCreated(&action), &node);
             }
         }
     }
 
+    fn update_max_depth(&self, node: &ArcNode<GD, S, P, A, Q, I, M>) {
+        let d = node.depth.load(Ordering::Relaxed);
+        self.reg_info.max_depth.fetch_max(d, Ordering::Relaxed);
+    }
+
     fn make_branch(&self, parent_state: &S, parent_node: &ArcNode<GD, S, P, A, Q, I, M>) {
+        trace_span!(
+            "make_branch",
+            hash = parent_node.hash,
+            depth = parent_node.depth.load(Ordering::Relaxed)
+        );
         // bracket needed for `debug_assertions` below so there is no deadlock on `children_wlk`
         {
             let mut children_wlk = parent_node.children.write().unwrap();
             // To allow other threads to steal work, we drop `children_wlk` as soon as we no longer
             // need `branch_wip`
             while let Children::BranchWip(ref mut branch_wip) = *children_wlk {
-                if let Some((p, a)) = branch_wip.next_unscored() {
-                    // a new player / action pair; `GD::apply_action` and
-                    // `Self::create_scored_child` could both be slow (depending on user
-                    // implementation of `GameDynamics` so we go ahead and drop the `children_wlk`
+                // claim up to `leaf_batch_size` new player / action pairs
+                let mut pairs = Vec::new();
+                while pairs.len() < self.leaf_batch_size {
+                    match branch_wip.next_unscored() {
+                        Some(pair) => pairs.push(pair),
+                        None => break,
+                    }
+                }
+                if !pairs.is_empty() {
+                    // `GD::apply_action` and `Self::create_scored_child` could both be slow
+                    // (depending on user implementation of `GameDynamics` so we go ahead and drop
+                    // the `children_wlk`
                     drop(children_wlk);
-                    if let Some(state) =
-                        GD::apply_action(&*parent_node.game_dynamics, parent_state.clone(), &a)
-                    {
-                        self.create_scored_child(parent_node, p, a, state);
-                        children_wlk = parent_node.children.write().unwrap();
-                    } else {
-                        // `BranchWip` keeps a counter to ensure all nodes have been created, since
-                        // this node won't be included in the `Branch` we need to let `BranchWip`
-                        // know
-                        children_wlk = parent_node.children.write().unwrap();
-                        children_wlk.as_wip_mut().unwrap().decrease_scores_pending();
+                    let n_dropped = self.create_children(parent_state, parent_node, pairs);
+                    children_wlk = parent_node.children.write().unwrap();
+                    // `BranchWip` keeps a counter to ensure all nodes have been created, since the
+                    // pairs for which `GD::apply_action` returned `None` won't be included in the
+                    // `Branch` we need to let `BranchWip` know (if there are no such pairs, another
+                    // thread may already have completed the branch)
+                    if n_dropped > 0 {
+                        let branch_wip = children_wlk.as_wip_mut().unwrap();
+                        (0..n_dropped).for_each(|_| branch_wip.decrease_scores_pending());
                     }
                 } else if branch_wip.finished() {
                     // no player / action pairs and ready to convert to `Branch`
@@ -1611,13 +2751,27 @@ where
                         *children_wlk = Children::Branch(map);
                     }
                     drop(children_wlk);
+                    parent_node.refund(self.memory_hints.action_iter);
+                    trace_event!(TRACE, "branch complete");
                     notifier.notify_all();
+                    parent_node
+                        .observers
+                        .notify(TreeEvent::BranchComplete, parent_node);
                     break;
                 } else {
                     // no more player / action pairs but another thread is still processing a pair
                     let notifier = branch_wip.get_notifier();
                     drop(children_wlk);
+                    self.reg_info.notifier_waits.fetch_add(1, Ordering::Relaxed);
+                    let t0 = Instant::now();
+                    trace_event!(DEBUG, "waiting for branch");
                     drop(notifier.wait().unwrap());
+                    trace_event!(
+                        DEBUG,
+                        waited_nanos = t0.elapsed().as_nanos() as u64,
+                        "branch complete after wait"
+                    );
+                    RegistryInfo::add_elapsed(&self.reg_info.notifier_wait_nanos, t0);
                     break;
                 }
             }
@@ -1633,16 +2787,115 @@ where
         }
     }
 
+    // Creates and scores the children for `pairs`, scoring the children that are not registry hits
+    // with a single call to `GD::score_leaves` if there is more than one of them; returns the
+    // number of pairs for which `GD::apply_action` returned `None`
+    fn create_children(
+        &self,
+        parent_state: &S,
+        parent_node: &ArcNode<GD, S, P, A, Q, I, M>,
+        pairs: Vec<(P, A)>,
+    ) -> usize {
+        let n_pairs = pairs.len();
+        let mut children = Vec::with_capacity(n_pairs);
+        for (p, a) in pairs {
+            if let Some(state) =
+                GD::apply_action(&*parent_node.game_dynamics, parent_state.clone(), &a)
+            {
+                let prior = annotation::install(&parent_node.annotation, || {
+                    GD::action_prior(
+                        &*parent_node.game_dynamics,
+                        parent_node.score.read().unwrap().as_ref(),
+                        &parent_node.player,
+                        parent_state,
+                        &a,
+                    )
+                });
+                children.push((Node::new_child(parent_node, p, state), a, prior));
+            }
+        }
+        let n_dropped = n_pairs - children.len();
+
+        // only the states that are not in the registry yet are scored in advance; if another
+        // thread registers the same state before the child is created, its score is discarded
+        let mut leaf_scores = Vec::new();
+        if children.len() > 1 {
+            // only the waits for the write lock are counted in `registry_lock_wait_nanos`
+            let reg_rlk = self.registry.read().unwrap();
+            let is_new = children
+                .iter()
+                .map(|(node, _, _)| !reg_rlk.contains(&ArcNode::downgrade(node)))
+                .collect::<Vec<_>>();
+            drop(reg_rlk);
+
+            let new_children = children
+                .iter()
+                .zip(&is_new)
+                .filter(|(_, &is_new)| is_new)
+                .map(|((node, _, _), _)| node)
+                .collect::<Vec<_>>();
+            if new_children.len() > 1 {
+                let state_rlks = new_children
+                    .iter()
+                    .map(|node| node.state.read().unwrap())
+                    .collect::<Vec<_>>();
+                let states = state_rlks
+                    .iter()
+                    .map(|state| state.as_ref().unwrap())
+                    .collect::<Vec<_>>();
+                self.reg_info
+                    .score_leaf_calls
+                    .fetch_add(states.len(), Ordering::Relaxed);
+                let annotations = new_children
+                    .iter()
+                    .map(|node| &node.annotation)
+                    .collect::<Vec<_>>();
+                let scores = RegistryInfo::timed(&self.reg_info.score_leaf_nanos, || {
+                    annotation::install_leaves(&annotations, || {
+                        GD::score_leaves(
+                            &*self.game_dynamics,
+                            parent_node.score.read().unwrap().as_ref(),
+                            &parent_node.player,
+                            &states,
+                        )
+                    })
+                });
+                assert_eq!(
+                    scores.len(),
+                    states.len(),
+                    "`GameDynamics::score_leaves` must return one score per state"
+                );
+                drop(states);
+                drop(state_rlks);
+                let mut scores = scores.into_iter();
+                leaf_scores = is_new
+                    .into_iter()
+                    .map(|is_new| if is_new { scores.next() } else { None })
+                    .collect();
+            }
+        }
+        leaf_scores.resize_with(children.len(), || None);
+
+        for ((node, a, prior), leaf_score) in children.into_iter().zip(leaf_scores) {
+            self.create_scored_child(parent_node, node, a, prior, leaf_score);
+        }
+        n_dropped
+    }
+
     fn make_branch_wip(&self, parent_state: &S, parent_node: &ArcNode<GD, S, P, A, Q, I, M>) {
         if let ref mut children @ Children::NewLeaf = *parent_node.children.write().unwrap() {
-            let players_actions = self
-                .game_dynamics
-                .available_actions(&parent_node.player, parent_state);
+            let players_actions = annotation::install(&parent_node.annotation, || {
+                self.game_dynamics
+                    .available_actions(&parent_node.player, parent_state)
+            });
+
+            self.reg_info.expansions.fetch_add(1, Ordering::Relaxed);
 
             match players_actions {
                 Some(player_acts) => {
                     let branch_wip = BranchWip::new(player_acts.into_iter());
                     *children = Children::BranchWip(branch_wip);
+                    parent_node.charge(self.memory_hints.action_iter);
                 }
                 None => {
                     *children = Children::None;
@@ -1679,10 +2932,111 @@ where
         })
     }
 
+    fn principal_variation(&self) -> Vec<A> {
+        let _prune_rlk = self.prune_lock.read().unwrap();
+        let mut node = ArcNode::clone(&*self.root.read().unwrap());
+        let mut state = node.get_state();
+        let mut pv = Vec::new();
+        loop {
+            let children = node.children.read().unwrap();
+            // the children of a `BranchWip` that have been scored are considered as in
+            // `best_action`
+            let map = match children.as_map() {
+                Some(map) if !map.is_empty() => map,
+                _ => break,
+            };
+            let action = Tree::select_node(self, &node, &state, map, SelectNodeState::Exploit);
+            let next_node = ArcNode::clone(map.get(&action).unwrap());
+            drop(children);
+            state = GD::apply_action(&*node.game_dynamics, state, &action).unwrap();
+            pv.push(action);
+            node = next_node;
+        }
+        pv
+    }
+
     fn apply_action(&self, a: &A) {
         let _prune_wlk = self.prune_lock.write().unwrap();
-        let root_new = self.root.read().unwrap().move_root(a);
-        *self.root.write().unwrap() = root_new;
+        {
+            let root = self.root.read().unwrap();
+            root.observers.notify(TreeEvent::RootMoving(a), &root);
+        }
+        let (root_new, detached) = self.root.read().unwrap().move_root(a);
+        let observers = Arc::clone(&root_new.observers);
+        let root_old = std::mem::replace(&mut *self.root.write().unwrap(), root_new);
+        if self.prune_dominated {
+            // the children that were dominated at the new root are kept from now on
+            self.release_dominated();
+            self.restore_released(&ArcNode::clone(&*self.root.read().unwrap()));
+        }
+        if self.max_history > 0 {
+            let detached = if self.keep_subtrees {
+                Some(detached)
+            } else {
+                drop(detached);
+                Node::drop_children(&root_old);
+                *root_old.children.write().unwrap() = Children::NewLeaf;
+                None
+            };
+            let mut history = self.history.lock().unwrap();
+            history.push_back(HistoryEntry {
+                root: root_old,
+                action: a.clone(),
+                detached,
+            });
+            if history.len() > self.max_history {
+                history.pop_front();
+            }
+        } else {
+            drop(detached);
+            drop(root_old);
+        }
+        observers.notify(TreeEvent::RootMoved(a), &self.root.read().unwrap());
+    }
+
+    fn undo(&self) -> bool {
+        let _prune_wlk = self.prune_lock.write().unwrap();
+        let entry = match self.history.lock().unwrap().pop_back() {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let HistoryEntry {
+            root,
+            action,
+            detached,
+        } = entry;
+        if let Some(detached) = detached {
+            debug_assert!(detached.edge.as_ptr() == self.root.read().unwrap().as_ptr());
+            Node::restore_root(&root, action.clone(), detached);
+            // the state of the root that is taken back can be recomputed from its parents unless
+            // the `StateMemory` requires it to be stored
+            let root_rlk = self.root.read().unwrap();
+            <Node<GD, S, P, A, Q, I, M> as StateMemory>::modify_state(&root_rlk.state);
+            if root_rlk.state.read().unwrap().is_none() {
+                root_rlk.refund(root_rlk.memory.state_bytes.load(Ordering::Relaxed));
+            }
+        }
+        let observers = Arc::clone(&root.observers);
+        let root_undone = std::mem::replace(&mut *self.root.write().unwrap(), root);
+        // without the subtrees, the root that is taken back is pruned here
+        drop(root_undone);
+        observers.notify(TreeEvent::RootUndone(&action), &self.root.read().unwrap());
+        true
+    }
+
+    fn history_len(&self) -> usize {
+        self.history.lock().unwrap().len()
+    }
+
+    fn add_observer(&self, observer: Arc<dyn TreeObserver<S, P, A, Q>>)
+    where
+        S: 'static,
+        P: 'static + Clone,
+        A: 'static,
+        Q: 'static + Clone,
+    {
+        let root = self.root.read().unwrap();
+        root.observers.push(Box::new(observer::Adapter(observer)));
     }
 
     fn apply_best_action(&self) -> Status<A> {
@@ -1721,6 +3075,231 @@ where
         Some(info)
     }
 
+    fn get_next_move_edges(&self) -> Option<Vec<(A, EdgeStats)>> {
+        let edges = self
+            .root
+            .read()
+            .unwrap()
+            .children
+            .read()
+            .unwrap()
+            .as_map()?
+            .iter()
+            .map(|(a, e)| (a.clone(), e.stats()))
+            .collect();
+
+        Some(edges)
+    }
+
+    fn add_root_noise(&self, alpha: f64, epsilon: f64, rng: &mut dyn RngCore) -> bool {
+        assert!(alpha > 0.0, "`alpha` must be positive");
+        assert!(
+            (0.0..=1.0).contains(&epsilon),
+            "`epsilon` must be in [0, 1]"
+        );
+        // `rand_distr::Dirichlet` requires at least two categories, so the Dirichlet sample is
+        // constructed from independent Gamma samples instead
+        let gamma = rand_distr::Gamma::new(alpha, 1.0).expect("`alpha` is positive");
+
+        let root = self.root.read().unwrap();
+        let mut children_wlk = root.children.write().unwrap();
+        let map = match *children_wlk {
+            Children::Branch(ref mut map) => map,
+            _ => return false,
+        };
+
+        let n = map.len() as f64;
+        let noise = map.iter().map(|_| rng.sample(gamma)).collect::<Vec<f64>>();
+        let total = noise.iter().sum::<f64>();
+        map.values_mut().zip(noise).for_each(|(e, eta)| {
+            let eta = if total > 0.0 { eta / total } else { 1.0 / n };
+            let p = e.prior.unwrap_or(1.0 / n);
+            e.prior = Some((1.0 - epsilon) * p + epsilon * eta);
+        });
+        true
+    }
+
+    fn set_root_priors(&self, priors: &[(A, f64)]) -> bool {
+        let root = self.root.read().unwrap();
+        let mut children_wlk = root.children.write().unwrap();
+        let map = match *children_wlk {
+            Children::Branch(ref mut map) => map,
+            _ => return false,
+        };
+
+        for (a, p) in priors {
+            if let Some(e) = map.get_mut(a) {
+                e.prior = Some(*p);
+            }
+        }
+        true
+    }
+
+    fn sample_action(&self, temperature: f64, rng: &mut dyn RngCore) -> Status<A> {
+        assert!(
+            temperature >= 0.0 && temperature.is_finite(),
+            "`temperature` must be non-negative and finite"
+        );
+        let node = self.root.read().unwrap();
+        let children = node.children.read().unwrap();
+        Status::from_children(&*children, |map| {
+            let visits = map
+                .iter()
+                .map(|(a, e)| (a, e.visits.load(Ordering::Relaxed)))
+                .collect::<Vec<_>>();
+            let max_visits = visits.iter().map(|(_, n)| *n).max().unwrap_or(0);
+
+            let sampled = if max_visits == 0 {
+                None
+            } else if temperature > 0.0 {
+                // normalizing by `max_visits` avoids overflowing for small temperatures
+                let weights = visits
+                    .iter()
+                    .map(|(_, n)| (*n as f64 / max_visits as f64).powf(1.0 / temperature));
+                let dist = WeightedIndex::new(weights).expect("some edge has been visited");
+                Some(visits[rng.sample(dist)].0)
+            } else {
+                visits
+                    .iter()
+                    .find(|(_, n)| *n == max_visits)
+                    .map(|(a, _)| *a)
+            };
+
+            match sampled {
+                Some(a) => a.clone(),
+                None => Tree::select_node(
+                    self,
+                    &node,
+                    node.state
+                        .read()
+                        .unwrap()
+                        .as_ref()
+                        .expect("the root always has a state"),
+                    map,
+                    SelectNodeState::Exploit,
+                ),
+            }
+        })
+    }
+
+    fn search_report(&self, include_nodes: bool) -> SearchReport<A, Q>
+    where
+        Q: Clone,
+    {
+        let _prune_rlk = self.prune_lock.read().unwrap();
+        let root = ArcNode::clone(&*self.root.read().unwrap());
+        let children = root
+            .children
+            .read()
+            .unwrap()
+            .as_map()
+            .map(|map| {
+                map.iter()
+                    .map(|(a, e)| ChildReport {
+                        action: a.clone(),
+                        hash: e.hash,
+                        visits: e.visits.load(Ordering::Relaxed),
+                        prior: e.prior,
+                        score: e.score.read().unwrap().clone(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let nodes = if include_nodes {
+            Node::find_reachable(&root)
+                .into_iter()
+                .map(|(node, depth, visits)| NodeReport {
+                    hash: node.hash,
+                    depth,
+                    visits,
+                    score: node.score.read().unwrap().clone(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        SearchReport {
+            root_hash: root.hash,
+            n_trees: 1,
+            children,
+            nodes,
+        }
+    }
+
+    fn rescore(&self, n_threads: usize) -> usize
+    where
+        Node<GD, S, P, A, Q, I, M>: Send + Sync,
+        GD: Sync,
+    {
+        assert!(n_threads > 0, "at least one thread is required");
+        let _prune_wlk = self.prune_lock.write().unwrap();
+        let sorted = self.find_children_sorted_with_depth();
+        // the root does not have a parent to score it with; it is only scored via backpropagation
+        let leaves = sorted
+            .iter()
+            .map(|(node, _)| node)
+            .filter(|node| match *node.children.read().unwrap() {
+                Children::NewLeaf | Children::None => !node.parents.read().unwrap().is_empty(),
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+
+        let game_dynamics = &*self.game_dynamics;
+        let reg_info = &self.reg_info;
+        let draw = self.cycle_handling == CycleHandling::Draw;
+        let chunk_size = std::cmp::max(1, leaves.len().div_ceil(n_threads));
+        std::thread::scope(|s| {
+            for chunk in leaves.chunks(chunk_size) {
+                s.spawn(move || {
+                    for node in chunk {
+                        Self::rescore_leaf(game_dynamics, reg_info, draw, node);
+                    }
+                });
+            }
+        });
+
+        for (node, _) in sorted.iter() {
+            if node.update_score(&self.reg_info) {
+                node.observers.notify(TreeEvent::ScoreUpdated, node);
+            }
+        }
+
+        leaves.len()
+    }
+
+    fn rescore_leaf(
+        game_dynamics: &GD,
+        reg_info: &RegistryInfo,
+        draw: bool,
+        node: &ArcNode<GD, S, P, A, Q, I, M>,
+    ) {
+        let parent = node
+            .parents
+            .read()
+            .unwrap()
+            .iter()
+            .next()
+            .map(|(_, p)| WeakNode::upgrade(p))
+            .expect("leaf without a parent");
+        let state = node.get_state();
+        let parent_score = parent.score.read().unwrap();
+        let score = annotation::install(&node.annotation, || {
+            if draw && node.repetition.load(Ordering::Relaxed) > 0 {
+                GD::score_repetition(game_dynamics, parent_score.as_ref(), &parent.player, &state)
+            } else {
+                reg_info.score_leaf_calls.fetch_add(1, Ordering::Relaxed);
+                RegistryInfo::timed(&reg_info.score_leaf_nanos, || {
+                    GD::score_leaf(game_dynamics, parent_score.as_ref(), &parent.player, &state)
+                })
+            }
+        });
+        drop(parent_score);
+        *node.score.write().unwrap() = score;
+        node.observers.notify(TreeEvent::ScoreUpdated, node);
+    }
+
     fn find_children_sorted_with_depth(&self) -> Vec<(ArcNode<GD, S, P, A, Q, I, M>, usize)> {
         let node = self.root.read().unwrap();
         let mut sorted = Vec::new();
@@ -1729,6 +3308,52 @@ where
         sorted
     }
 
+    fn root_cursor(&self) -> Cursor<'_, Node<GD, S, P, A, Q, I, M>, GD> {
+        Cursor::new(self, &self.root.read().unwrap())
+    }
+
+    fn find_by_path(&self, actions: &[A]) -> Option<Cursor<'_, Node<GD, S, P, A, Q, I, M>, GD>> {
+        actions
+            .iter()
+            .try_fold(self.root_cursor(), |cursor, a| cursor.child(a))
+    }
+
+    fn find_by_state(
+        &self,
+        player: &P,
+        state: &S,
+    ) -> Option<Cursor<'_, Node<GD, S, P, A, Q, I, M>, GD>> {
+        let _prune_rlk = self.prune_lock.read().unwrap();
+        let root = ArcNode::clone(&*self.root.read().unwrap());
+        let hash = hash_of(&(player, state));
+        Node::find_reachable(&root)
+            .into_iter()
+            .map(|(node, _, _)| node)
+            .find(|node| node.hash == hash && node.player == *player && node.get_state() == *state)
+            .map(|node| Cursor::new(self, &node))
+    }
+
+    fn find_nodes(&self, filter: &NodeFilter) -> Vec<Cursor<'_, Node<GD, S, P, A, Q, I, M>, GD>> {
+        let _prune_rlk = self.prune_lock.read().unwrap();
+        let root = ArcNode::clone(&*self.root.read().unwrap());
+        Node::find_reachable(&root)
+            .into_iter()
+            .filter(|(_, depth, visits)| {
+                filter.depth.contains(depth) && filter.visits.contains(visits)
+            })
+            .map(|(node, _, _)| Cursor::new(self, &node))
+            .collect()
+    }
+
+    fn count_transpositions(&self) -> usize {
+        let _prune_rlk = self.prune_lock.read().unwrap();
+        let root = ArcNode::clone(&*self.root.read().unwrap());
+        Node::find_reachable(&root)
+            .into_iter()
+            .filter(|(node, _, _)| node.parents.read().unwrap().len() > 1)
+            .count()
+    }
+
     fn get_registry_nodes(&self) -> HashSet<WeakNode<GD, S, P, A, Q, I, M>> {
         self.registry.read().unwrap().clone()
     }
@@ -1737,6 +3362,16 @@ where
         self.reg_info
             .len
             .store(self.registry.read().unwrap().len(), Ordering::Relaxed);
+        let memory_bytes = self
+            .root
+            .read()
+            .unwrap()
+            .memory
+            .bytes
+            .load(Ordering::Relaxed);
+        self.reg_info
+            .memory_bytes
+            .store(memory_bytes, Ordering::Relaxed);
         &self.reg_info
     }
 
@@ -1825,4 +3460,759 @@ pub(crate) mod test {
         });
         (children, visited)
     }
+
+    // the memory estimate of the `Tree` recomputed from the nodes in the registry and their parents
+    #[cfg(test)]
+    fn memory_bytes<GD, S, P, A, Q, I, M>(t: &Tree<Node<GD, S, P, A, Q, I, M>, GD>) -> usize
+    where
+        Node<GD, S, P, A, Q, I, M>: StateMemory + OnDrop,
+        GD: GameDynamics<Player = P, State = S, Action = A>,
+        A: Hash + Eq,
+        S: Hash + PartialEq<S> + Clone,
+        P: Hash + PartialEq<P>,
+    {
+        let hints = &t.memory_hints;
+        t.registry
+            .read()
+            .unwrap()
+            .iter()
+            .map(|wn| {
+                let n = WeakNode::upgrade(wn);
+                let stores_state = n.state.read().unwrap().is_some();
+                let n_parents = n.parents.read().unwrap().len();
+                Node::<GD, S, P, A, Q, I, M>::node_bytes(hints, stores_state)
+                    + Node::<GD, S, P, A, Q, I, M>::edge_bytes(hints) * n_parents
+            })
+            .sum()
+    }
+
+    #[cfg(test)]
+    mod cycle {
+        use super::*;
+
+        // a one player game on a ring of four states where every action moves one or two states
+        // forward, i.e. every state can be reached again; the score of a node is the number of nodes
+        // below it and `select_node` explores the least explored child
+        struct Ring;
+
+        impl GameDynamics for Ring {
+            type Player = ();
+            type State = usize;
+            type Action = usize;
+            type Score = f64;
+            type ActionIter = Vec<((), usize)>;
+
+            fn available_actions(&self, _: &(), _: &usize) -> Option<Self::ActionIter> {
+                Some(vec![((), 1), ((), 2)])
+            }
+
+            fn apply_action(&self, state: usize, action: &usize) -> Option<usize> {
+                Some((state + action) % 4)
+            }
+
+            fn select_node<II, Q, A>(
+                &self,
+                _: Option<&f64>,
+                _: &(),
+                _: &usize,
+                _: SelectNodeState,
+                scores_and_actions: II,
+            ) -> usize
+            where
+                II: Clone + IntoIterator<Item = (Q, A)>,
+                Q: Deref<Target = Option<f64>>,
+                A: Deref<Target = usize>,
+            {
+                let (_, a) = scores_and_actions
+                    .into_iter()
+                    .map(|(q, a)| (q.unwrap(), *a))
+                    .min_by(|x, y| x.partial_cmp(y).unwrap())
+                    .unwrap();
+                a
+            }
+
+            fn backprop_scores<II, Q>(
+                &self,
+                _: &(),
+                _: Option<&f64>,
+                child_scores: II,
+            ) -> Option<f64>
+            where
+                II: Clone + IntoIterator<Item = Q>,
+                Q: Deref<Target = f64>,
+            {
+                Some(1.0 + child_scores.into_iter().map(|q| *q).sum::<f64>())
+            }
+
+            fn score_leaf(&self, _: Option<&f64>, _: &(), _: &usize) -> Option<f64> {
+                Some(1.0)
+            }
+
+            fn score_repetition(&self, _: Option<&f64>, _: &(), _: &usize) -> Option<f64> {
+                Some(0.0)
+            }
+        }
+
+        fn ring_tree(cycle_handling: CycleHandling) -> TreeAlias<Ring, state_memory::GetState> {
+            let t =
+                Tree::new(Ring, state_memory::GetState, (), 0).with_cycle_handling(cycle_handling);
+            for _ in 0..50 {
+                t.step();
+            }
+            // panics (in debug builds) if the nodes do not form a DAG
+            t.find_children_sorted_with_depth();
+            assert!(t.get_registry_info().snapshot().cycles > 0);
+            t
+        }
+
+        #[test]
+        fn test_cycle_break_edge() {
+            let t = ring_tree(CycleHandling::BreakEdge);
+            assert_eq!(t.get_registry_nodes().len(), 4);
+            // every path ends once all actions lead back to a (grand)*parent
+            assert!(t.step().is_none());
+        }
+
+        #[test]
+        fn test_cycle_path_dependent() {
+            let t = ring_tree(CycleHandling::PathDependent);
+            let nodes = t.get_registry_nodes();
+            assert!(nodes.len() > 4);
+            assert!(nodes
+                .iter()
+                .any(|n| n.upgrade().repetition.load(Ordering::Relaxed) > 1));
+        }
+
+        #[test]
+        fn test_cycle_draw() {
+            let t = ring_tree(CycleHandling::Draw);
+            for n in t.get_registry_nodes().iter().map(WeakWrap::upgrade) {
+                let repetition = n.repetition.load(Ordering::Relaxed);
+                let terminal = matches!(*n.children.read().unwrap(), Children::None);
+                assert!(repetition <= 1);
+                assert_eq!(repetition == 1, terminal);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod transposition {
+        use super::*;
+
+        // a one player game that counts up to six where the actions `2 * k` and `2 * k + 1` both
+        // add `k + 1`, i.e. two actions of every node lead to the same child
+        struct Pairs;
+
+        impl GameDynamics for Pairs {
+            type Player = ();
+            type State = usize;
+            type Action = usize;
+            type Score = f64;
+            type ActionIter = Vec<((), usize)>;
+
+            fn available_actions(&self, _: &(), state: &usize) -> Option<Self::ActionIter> {
+                if *state < 6 {
+                    Some((0..4).map(|a| ((), a)).collect())
+                } else {
+                    None
+                }
+            }
+
+            fn apply_action(&self, state: usize, action: &usize) -> Option<usize> {
+                Some(state + action / 2 + 1)
+            }
+
+            fn select_node<II, Q, A>(
+                &self,
+                _: Option<&f64>,
+                _: &(),
+                _: &usize,
+                _: SelectNodeState,
+                scores_and_actions: II,
+            ) -> usize
+            where
+                II: Clone + IntoIterator<Item = (Q, A)>,
+                Q: Deref<Target = Option<f64>>,
+                A: Deref<Target = usize>,
+            {
+                scores_and_actions
+                    .into_iter()
+                    .map(|(_, a)| *a)
+                    .min()
+                    .unwrap()
+            }
+
+            fn backprop_scores<II, Q>(
+                &self,
+                _: &(),
+                _: Option<&f64>,
+                child_scores: II,
+            ) -> Option<f64>
+            where
+                II: Clone + IntoIterator<Item = Q>,
+                Q: Deref<Target = f64>,
+            {
+                Some(1.0 + child_scores.into_iter().map(|q| *q).sum::<f64>())
+            }
+
+            fn score_leaf(&self, _: Option<&f64>, _: &(), _: &usize) -> Option<f64> {
+                Some(1.0)
+            }
+        }
+
+        #[test]
+        fn test_move_root_to_transposition() {
+            for &(max_history, keep_subtrees) in &[(0, false), (1, false), (1, true)] {
+                let t = Tree::new(Pairs, state_memory::GetState, (), 0)
+                    .with_history(max_history, keep_subtrees);
+                for _ in 0..10 {
+                    t.step();
+                }
+                // the new root is also the child of the old root for the action 1
+                t.apply_action(&0);
+                assert_eq!(t.get_root_info().state, Some(1));
+                for _ in 0..10 {
+                    t.step();
+                }
+                if max_history > 0 {
+                    assert!(t.undo());
+                    assert_eq!(t.get_root_info().state, Some(0));
+                    if keep_subtrees {
+                        assert_eq!(t.get_next_move_edges().unwrap().len(), 4);
+                    }
+                    for _ in 0..10 {
+                        t.step();
+                    }
+                    t.apply_action(&1);
+                    assert_eq!(t.get_root_info().state, Some(1));
+                }
+                // panics (in debug builds) if parents and children are inconsistent
+                t.find_children_sorted_with_depth();
+            }
+        }
+
+        #[test]
+        fn test_memory_refunds() {
+            let hints = MemoryHints {
+                state: 7,
+                score: 11,
+                action: 13,
+                action_iter: 0,
+            };
+            for &(max_history, keep_subtrees) in &[(0, false), (1, false), (1, true)] {
+                let t = Tree::new(Pairs, state_memory::GetState, (), 0)
+                    .with_history(max_history, keep_subtrees)
+                    .with_memory_hints(hints.clone());
+                let check = || {
+                    let info = t.get_registry_info().snapshot();
+                    assert_eq!(info.memory_bytes, memory_bytes(&t));
+                    info.memory_bytes
+                };
+                for _ in 0..10 {
+                    t.step();
+                }
+                let before = check();
+                t.apply_action(&0);
+                check();
+                if max_history > 0 {
+                    assert!(t.undo());
+                    let after = check();
+                    if keep_subtrees {
+                        assert_eq!(after, before);
+                    }
+                }
+                for _ in 0..10 {
+                    t.step();
+                }
+                t.apply_action(&1);
+                check();
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod annotation {
+        use super::*;
+        use crate::annotation::with_annotation;
+
+        // a one player game that counts down from a number in steps of one or two; every callback
+        // checks that the annotation of the node it is evaluated for holds the node's state
+        struct Countdown;
+
+        fn check(state: &usize) {
+            with_annotation(|a| assert_eq!(a.unwrap().get_or_init(|| *state), state));
+        }
+
+        impl GameDynamics for Countdown {
+            type Player = ();
+            type State = usize;
+            type Action = usize;
+            type Score = f64;
+            type ActionIter = Vec<((), usize)>;
+
+            fn available_actions(&self, _: &(), state: &usize) -> Option<Self::ActionIter> {
+                check(state);
+                Some((1..=std::cmp::min(2, *state)).map(|a| ((), a)).collect())
+            }
+
+            fn apply_action(&self, state: usize, action: &usize) -> Option<usize> {
+                Some(state - action)
+            }
+
+            fn select_node<II, Q, A>(
+                &self,
+                _: Option<&f64>,
+                _: &(),
+                state: &usize,
+                _: SelectNodeState,
+                scores_and_actions: II,
+            ) -> usize
+            where
+                II: Clone + IntoIterator<Item = (Q, A)>,
+                Q: Deref<Target = Option<f64>>,
+                A: Deref<Target = usize>,
+            {
+                check(state);
+                *scores_and_actions.into_iter().next().unwrap().1
+            }
+
+            fn backprop_scores<II, Q>(
+                &self,
+                _: &(),
+                _: Option<&f64>,
+                child_scores: II,
+            ) -> Option<f64>
+            where
+                II: Clone + IntoIterator<Item = Q>,
+                Q: Deref<Target = f64>,
+            {
+                with_annotation(|a| assert!(a.unwrap().is_set()));
+                Some(child_scores.into_iter().map(|q| *q).sum::<f64>())
+            }
+
+            fn score_leaf(&self, _: Option<&f64>, _: &(), state: &usize) -> Option<f64> {
+                check(state);
+                Some(1.0)
+            }
+        }
+
+        #[test]
+        fn test_annotation() {
+            // with a batch size of two, the children of a leaf are scored via the default
+            // `GameDynamics::score_leaves`, which installs the annotation of each child
+            for leaf_batch_size in [1, 2] {
+                let t = Tree::new(Countdown, state_memory::GetState, (), 4)
+                    .with_leaf_batch_size(leaf_batch_size);
+                for _ in 0..20 {
+                    t.step();
+                }
+                assert!(with_annotation(|a| a.is_none()));
+                let nodes = t.find_nodes(&NodeFilter::default());
+                assert_eq!(nodes.len(), 5);
+                for node in nodes.iter() {
+                    let state = node.state().unwrap();
+                    assert_eq!(
+                        node.with_annotation(|a| a.get::<usize>().copied()),
+                        Some(Some(state))
+                    );
+                }
+
+                // the annotation is dropped with its node
+                let root = t.root_cursor();
+                t.apply_action(&1);
+                assert!(root.with_annotation(|_| ()).is_none());
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod dominance {
+        use super::*;
+
+        // a one player game of three moves with three actions each, where the path `[b, x, y]`
+        // ends with the value `b + 0.2 * x + 0.05 * y`; the score of a node is its value together
+        // with a lower and an upper bound, so that every action but the greatest is dominated; if
+        // `sum` is set, the value of a branch is the sum of the values of its children instead of
+        // their maximum (like a visit count)
+        struct Bands {
+            sum: bool,
+        }
+
+        fn bounds(path: &[usize]) -> (f64, f64) {
+            match *path {
+                [b] => (b as f64, b as f64 + 0.5),
+                [b, x] => (b as f64 + 0.2 * x as f64, b as f64 + 0.2 * x as f64 + 0.1),
+                [b, x, y] => {
+                    let v = b as f64 + 0.2 * x as f64 + 0.05 * y as f64;
+                    (v, v)
+                }
+                _ => (0.0, 2.5),
+            }
+        }
+
+        impl GameDynamics for Bands {
+            type Player = ();
+            type State = Vec<usize>;
+            type Action = usize;
+            type Score = (f64, f64, f64);
+            type ActionIter = Vec<((), usize)>;
+
+            fn available_actions(&self, _: &(), path: &Vec<usize>) -> Option<Self::ActionIter> {
+                if path.len() < 3 {
+                    Some((0..3).map(|a| ((), a)).collect())
+                } else {
+                    None
+                }
+            }
+
+            fn apply_action(&self, mut path: Vec<usize>, action: &usize) -> Option<Vec<usize>> {
+                path.push(*action);
+                Some(path)
+            }
+
+            fn select_node<II, Q, A>(
+                &self,
+                _: Option<&Self::Score>,
+                _: &(),
+                _: &Vec<usize>,
+                _: SelectNodeState,
+                scores_and_actions: II,
+            ) -> usize
+            where
+                II: Clone + IntoIterator<Item = (Q, A)>,
+                Q: Deref<Target = Option<Self::Score>>,
+                A: Deref<Target = usize>,
+            {
+                *scores_and_actions.into_iter().next().unwrap().1
+            }
+
+            // explores the least visited child and exploits the greatest value
+            fn select_node_with_edges<II, Q, A>(
+                &self,
+                _: Option<&Self::Score>,
+                _: &(),
+                _: &Vec<usize>,
+                purpose: SelectNodeState,
+                scores_actions_and_edges: II,
+            ) -> usize
+            where
+                II: Clone + IntoIterator<Item = (Q, A, EdgeStats)>,
+                Q: Deref<Target = Option<Self::Score>>,
+                A: Deref<Target = usize>,
+            {
+                let children = scores_actions_and_edges.into_iter();
+                match purpose {
+                    SelectNodeState::Explore => {
+                        children.map(|(_, a, e)| (e.visits, *a)).min().unwrap().1
+                    }
+                    SelectNodeState::Exploit => {
+                        let values = children.map(|(q, a, _)| (q.unwrap().0, *a));
+                        values.max_by(|x, y| x.partial_cmp(y).unwrap()).unwrap().1
+                    }
+                }
+            }
+
+            fn backprop_scores<II, Q>(
+                &self,
+                _: &(),
+                _: Option<&Self::Score>,
+                child_scores: II,
+            ) -> Option<Self::Score>
+            where
+                II: Clone + IntoIterator<Item = Q>,
+                Q: Deref<Target = Self::Score>,
+            {
+                child_scores
+                    .into_iter()
+                    .map(|q| *q)
+                    .fold(None, |m, q| match m {
+                        Some((v, lo, hi)) if self.sum => Some((q.0 + v, q.1.max(lo), q.2.max(hi))),
+                        Some((v, lo, hi)) => Some((q.0.max(v), q.1.max(lo), q.2.max(hi))),
+                        None => Some(q),
+                    })
+            }
+
+            fn score_leaf(
+                &self,
+                _: Option<&Self::Score>,
+                _: &(),
+                path: &Vec<usize>,
+            ) -> Option<Self::Score> {
+                let (lo, hi) = bounds(path);
+                Some(((lo + hi) / 2.0, lo, hi))
+            }
+
+            fn score_bounds(&self, _: &(), score: &Self::Score) -> Option<(f64, f64)> {
+                Some((score.1, score.2))
+            }
+        }
+
+        fn bands_tree(
+            prune_dominated: bool,
+            sum: bool,
+        ) -> TreeAlias<Bands, state_memory::GetState> {
+            let t = Tree::new(Bands { sum }, state_memory::GetState, (), Vec::new())
+                .with_dominance_pruning(prune_dominated);
+            for _ in 0..200 {
+                t.step();
+            }
+            t
+        }
+
+        #[test]
+        fn test_dominance_pruning() {
+            let t = bands_tree(false, false);
+            assert_eq!(t.get_registry_nodes().len(), 40);
+            assert_eq!(t.get_next_move_info().unwrap().len(), 3);
+
+            let t = bands_tree(true, false);
+            // only the children of the root and the path of the greatest value remain
+            assert_eq!(t.get_registry_nodes().len(), 6);
+            assert_eq!(t.get_next_move_info().unwrap().len(), 3);
+            assert!(matches!(t.best_action(), Status::Action(2)));
+            let score = t.get_root_info().score.unwrap();
+            assert!((score.0 - 2.5).abs() < 1e-9);
+        }
+
+        #[test]
+        fn test_read_only_selection() {
+            use rand::SeedableRng;
+
+            let t = Tree::new(Bands { sum: false }, state_memory::GetState, (), Vec::new())
+                .with_dominance_pruning(true);
+            // expands the root, whose children `0` and `1` are dominated by `2`
+            t.step();
+            let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+            assert!(matches!(t.best_action(), Status::Action(2)));
+            assert_eq!(t.principal_variation(), vec![2]);
+            assert!(matches!(t.sample_action(0.0, &mut rng), Status::Action(2)));
+            assert!(t.dominated.lock().unwrap().is_empty());
+            assert_eq!(t.get_next_move_info().unwrap().len(), 3);
+
+            // a step skips the dominated children of the root without queueing them
+            t.step();
+            assert!(t.dominated.lock().unwrap().is_empty());
+            assert_eq!(t.get_next_move_info().unwrap().len(), 3);
+            assert_eq!(t.get_registry_nodes().len(), 7);
+
+            // the next step expands `[2, 2]`, then releases the dominated children of `[2]`
+            t.step();
+            assert!(t.dominated.lock().unwrap().is_empty());
+            assert_eq!(t.get_registry_nodes().len(), 8);
+        }
+
+        #[test]
+        fn test_release_rescores_parent() {
+            // the values of the released children below `2` no longer count towards the sums,
+            // i.e. the root sums `0.25` and `1.25` (its kept children) and `2.5`
+            let t = bands_tree(true, true);
+            assert_eq!(t.get_registry_nodes().len(), 6);
+            let score = t.get_root_info().score.unwrap();
+            assert!((score.0 - 4.0).abs() < 1e-9, "stale score {:?}", score);
+        }
+
+        #[test]
+        fn test_release_on_apply_action() {
+            let t = Tree::new(Bands { sum: false }, state_memory::GetState, (), Vec::new())
+                .with_dominance_pruning(true)
+                .with_memory_hints(MemoryHints {
+                    state: 8,
+                    score: 0,
+                    action: 8,
+                    action_iter: 0,
+                });
+            {
+                // another thread holding the `prune_lock` keeps `step` from releasing the
+                // dominated children
+                let _prune_rlk = t.prune_lock.read().unwrap();
+                for _ in 0..200 {
+                    t.step();
+                }
+            }
+            assert!(!t.dominated.lock().unwrap().is_empty());
+            assert!(t.get_registry_nodes().len() > 4);
+            assert_eq!(t.get_next_move_info().unwrap().len(), 3);
+
+            t.apply_action(&2);
+            assert!(t.dominated.lock().unwrap().is_empty());
+            // the new root keeps its children, only the dominated children of `[2, 2]` are
+            // released
+            assert_eq!(t.get_next_move_info().unwrap().len(), 3);
+            let info = t.get_registry_info().snapshot();
+            assert_eq!(info.len, 5);
+            assert_eq!(info.memory_bytes, memory_bytes(&t));
+        }
+
+        #[test]
+        fn test_apply_dominated_action() {
+            let t = bands_tree(true, false);
+            // a dominated child of the root can be applied
+            t.apply_action(&0);
+            assert_eq!(t.get_root_info().state, Some(vec![0]));
+
+            let t = Tree::new(Bands { sum: false }, state_memory::GetState, (), Vec::new())
+                .with_dominance_pruning(true)
+                .with_memory_hints(MemoryHints {
+                    state: 8,
+                    score: 0,
+                    action: 8,
+                    action_iter: 0,
+                });
+            for _ in 0..200 {
+                t.step();
+            }
+            // the dominated children of `[2]` were released before it became the root and are
+            // created again
+            t.apply_action(&2);
+            let next_moves = t.get_next_move_info().unwrap();
+            assert_eq!(next_moves.len(), 3);
+            assert!(next_moves.iter().all(|(_, info)| info.score.is_some()));
+            let info = t.get_registry_info().snapshot();
+            assert_eq!(info.len, 5);
+            assert_eq!(info.memory_bytes, memory_bytes(&t));
+
+            t.apply_action(&0);
+            assert_eq!(t.get_root_info().state, Some(vec![2, 0]));
+        }
+
+        #[test]
+        #[cfg(feature = "testing")]
+        fn test_dominance_pruning_parallel() {
+            use crate::synthetic::{SyntheticConfig, SyntheticGame};
+
+            for seed in 0..5 {
+                let game = SyntheticGame::new(SyntheticConfig {
+                    branching: 4,
+                    max_depth: 6,
+                    terminal_rate: 0.3,
+                    seed,
+                    ..Default::default()
+                });
+                let (player, state) = game.initial();
+                let optimal = game.optimal_value(&state);
+                let n_states = game.reachable_states(&state);
+                let t = Tree::new(game, state_memory::GetState, player, state)
+                    .with_dominance_pruning(true);
+                std::thread::scope(|s| {
+                    for _ in 0..4 {
+                        s.spawn(|| {
+                            for _ in 0..3000 {
+                                t.step();
+                            }
+                        });
+                    }
+                });
+                // pruning keeps the minimax value of the root
+                assert_eq!(t.get_root_info().score, Some(optimal));
+                // every node in the registry is reachable from the root, i.e. the released
+                // subtrees were dropped, and children with another parent were kept
+                let n_reachable = t.find_children_sorted_with_depth().len();
+                let info = t.get_registry_info().snapshot();
+                assert_eq!(info.len, n_reachable);
+                assert!(info.len < n_states);
+                assert_eq!(info.memory_bytes, memory_bytes(&t));
+            }
+        }
+    }
+
+    #[cfg(all(test, feature = "tracing"))]
+    mod tracing_spans {
+        use super::*;
+        use crate::nim::{Nim, Player};
+        use crate::tree::state_memory::GetState;
+
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        // the names of the spans and the messages of the events
+        #[derive(Default)]
+        struct Records {
+            spans: Mutex<Vec<&'static str>>,
+            events: Mutex<Vec<String>>,
+        }
+
+        struct Recorder(Arc<Records>);
+
+        struct Message<'a>(&'a mut String);
+
+        impl Visit for Message<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    *self.0 = format!("{:?}", value);
+                }
+            }
+        }
+
+        impl tracing::Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut spans = self.0.spans.lock().unwrap();
+                spans.push(span.metadata().name());
+                Id::from_u64(spans.len() as u64)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut message = String::new();
+                event.record(&mut Message(&mut message));
+                self.0.events.lock().unwrap().push(message);
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        #[test]
+        fn test_spans_and_events() {
+            let records = Arc::new(Records::default());
+            tracing::subscriber::with_default(Recorder(Arc::clone(&records)), || {
+                let game = Nim {
+                    max_move: 3,
+                    rng: Mutex::new(StdRng::seed_from_u64(0)),
+                };
+                let t = Tree::new(game, GetState, Player::P1, 10);
+                for _ in 0..50 {
+                    t.step();
+                }
+                t.apply_best_action();
+            });
+
+            let spans = records.spans.lock().unwrap();
+            for name in [
+                "step_into",
+                "make_branch",
+                "create_scored_child",
+                "backprop_scores",
+                "move_root",
+                "drop_node",
+            ] {
+                assert!(spans.contains(&name), "missing span {}", name);
+            }
+            let events = records.events.lock().unwrap();
+            for message in [
+                "expanding leaf",
+                "registry hit",
+                "registry miss",
+                "branch complete",
+                "backpropagated scores",
+                "moving root",
+            ] {
+                assert!(
+                    events.iter().any(|e| e == message),
+                    "missing event {}",
+                    message
+                );
+            }
+        }
+    }
 }
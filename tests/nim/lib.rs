@@ -363,6 +363,89 @@ impl DynGD for Nim {
     }
 }
 
+// `Nim` with a uniform prior over the valid moves; the prior is not used by `select_node`, but
+// reported via `SearchTree::get_next_move_edges`
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct NimWithPrior(Nim);
+
+impl GameDynamics for NimWithPrior {
+    type Player = Player;
+    type State = usize;
+    type Action = usize;
+    type Score = Score;
+    type ActionIter = ActionIter;
+
+    fn available_actions(
+        &self,
+        player: &Self::Player,
+        state: &Self::State,
+    ) -> Option<Self::ActionIter> {
+        <Nim as GameDynamics>::available_actions(&self.0, player, state)
+    }
+
+    fn apply_action(&self, state: Self::State, action: &Self::Action) -> Option<Self::State> {
+        <Nim as GameDynamics>::apply_action(&self.0, state, action)
+    }
+
+    fn action_prior(
+        &self,
+        _parent_score: Option<&Self::Score>,
+        _parent_player: &Self::Player,
+        parent_state: &Self::State,
+        _action: &Self::Action,
+    ) -> Option<f64> {
+        Some(1.0 / std::cmp::min(self.0.max_move, *parent_state) as f64)
+    }
+
+    fn select_node<II, Q, A>(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        parent_state: &Self::State,
+        purpose: SelectNodeState,
+        scores_and_actions: II,
+    ) -> Self::Action
+    where
+        Self: Sized,
+        II: Clone + IntoIterator<Item = (Q, A)>,
+        Q: Deref<Target = Option<Self::Score>>,
+        A: Deref<Target = Self::Action>,
+    {
+        <Nim as GameDynamics>::select_node(
+            &self.0,
+            parent_score,
+            parent_player,
+            parent_state,
+            purpose,
+            scores_and_actions,
+        )
+    }
+
+    fn backprop_scores<II, Q>(
+        &self,
+        player: &Self::Player,
+        score_current: Option<&Self::Score>,
+        child_scores: II,
+    ) -> Option<Self::Score>
+    where
+        Self: Sized,
+        II: Clone + IntoIterator<Item = Q>,
+        Q: Deref<Target = Self::Score>,
+    {
+        <Nim as GameDynamics>::backprop_scores(&self.0, player, score_current, child_scores)
+    }
+
+    fn score_leaf(
+        &self,
+        parent_score: Option<&Self::Score>,
+        parent_player: &Self::Player,
+        state: &Self::State,
+    ) -> Option<Self::Score> {
+        <Nim as GameDynamics>::score_leaf(&self.0, parent_score, parent_player, state)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -387,6 +470,808 @@ mod test {
         }
     }
 
+    #[derive(Default)]
+    struct EventCounter {
+        created: AtomicUsize,
+        hits: AtomicUsize,
+        branches: AtomicUsize,
+        roots: AtomicUsize,
+        dropped: AtomicUsize,
+    }
+
+    impl TreeObserver<usize, Player, usize, Score> for EventCounter {
+        fn node_created(&self, _: &usize, _: &NodeInfo<usize, Player, Score>) {
+            self.created.fetch_add(1, Ordering::Relaxed);
+        }
+        fn registry_hit(&self, _: &usize, node: &NodeInfo<usize, Player, Score>) {
+            assert!(node.n_parents > 1);
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        fn branch_complete(&self, _: &NodeInfo<usize, Player, Score>) {
+            self.branches.fetch_add(1, Ordering::Relaxed);
+        }
+        fn root_moved(&self, _: &usize, node: &NodeInfo<usize, Player, Score>) {
+            assert!(node.state.is_some());
+            self.roots.fetch_add(1, Ordering::Relaxed);
+        }
+        fn node_dropped(&self, _: &NodeInfo<usize, Player, Score>) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let game = Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        };
+        let t = Tree::new(game, GetState, Player::P1, INIT);
+        let counter = Arc::new(EventCounter::default());
+        t.add_observer(Arc::clone(&counter) as _);
+
+        for _ in 0..3 {
+            for _ in 0..50 {
+                t.step();
+            }
+            t.apply_best_action();
+        }
+
+        let info = t.get_registry_info().snapshot();
+        assert_eq!(counter.created.load(Ordering::Relaxed), info.misses);
+        assert_eq!(counter.hits.load(Ordering::Relaxed), info.hits);
+        assert_eq!(counter.branches.load(Ordering::Relaxed), info.expansions);
+        assert_eq!(counter.roots.load(Ordering::Relaxed), 3);
+        // the initial root is never reported as created
+        assert_eq!(
+            counter.created.load(Ordering::Relaxed) + 1 - counter.dropped.load(Ordering::Relaxed),
+            info.len
+        );
+    }
+
+    #[test]
+    fn test_priors_and_visits() {
+        let game = NimWithPrior(Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        });
+        let t = Tree::new(game, GetState, Player::P1, INIT);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        assert!(t.get_next_move_edges().is_none());
+        assert!(!t.add_root_noise(0.3, 0.25, &mut rng));
+
+        let n_steps = 200;
+        for _ in 0..n_steps {
+            t.step();
+        }
+
+        let edges = t.get_next_move_edges().unwrap();
+        assert_eq!(edges.len(), MAX_MOVE);
+        assert!(edges
+            .iter()
+            .all(|(_, e)| e.prior == Some(1.0 / MAX_MOVE as f64)));
+        // every step except the one expanding the root traverses exactly one edge from the root
+        let visits = edges.iter().map(|(_, e)| e.visits).sum::<usize>();
+        assert_eq!(visits, n_steps - 1);
+
+        assert!(t.add_root_noise(0.3, 0.25, &mut rng));
+        let edges = t.get_next_move_edges().unwrap();
+        let total = edges.iter().map(|(_, e)| e.prior.unwrap()).sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!(edges
+            .iter()
+            .any(|(_, e)| e.prior != Some(1.0 / MAX_MOVE as f64)));
+
+        let visits_of = |a: usize| edges.iter().find(|(b, _)| *b == a).unwrap().1.visits;
+        let max_visits = edges.iter().map(|(_, e)| e.visits).max().unwrap();
+        match t.sample_action(0.0, &mut rng) {
+            Status::Action(a) => assert_eq!(visits_of(a), max_visits),
+            s => panic!("unexpected status {:?}", s),
+        }
+        for _ in 0..10 {
+            match t.sample_action(1.0, &mut rng) {
+                Status::Action(a) => assert!(visits_of(a) > 0),
+                s => panic!("unexpected status {:?}", s),
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "`epsilon` must be in [0, 1]")]
+    fn test_root_noise_epsilon() {
+        let game = NimWithPrior(Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        });
+        let t = Tree::new(game, GetState, Player::P1, INIT);
+        t.step();
+        t.add_root_noise(0.3, 1.5, &mut rand::rngs::StdRng::seed_from_u64(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "`alpha` must be positive")]
+    fn test_root_noise_alpha() {
+        let game = NimWithPrior(Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        });
+        let t = Tree::new(game, GetState, Player::P1, INIT);
+        t.step();
+        t.add_root_noise(0.0, 0.25, &mut rand::rngs::StdRng::seed_from_u64(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "`temperature` must be non-negative and finite")]
+    fn test_sample_action_temperature() {
+        let game = NimWithPrior(Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        });
+        let t = Tree::new(game, GetState, Player::P1, INIT);
+        t.step();
+        t.sample_action(-1.0, &mut rand::rngs::StdRng::seed_from_u64(1));
+    }
+
+    #[test]
+    fn test_reproducible() {
+        // unlike `Nim`, `SyntheticGame` draws from `with_search_rng` (to break ties between the
+        // least visited children), so the search depends on the seeded streams of the workers
+        let run = |seed| {
+            let game = SyntheticGame::new(SyntheticConfig {
+                branching: 6,
+                max_depth: 10,
+                transposition_pool: 64,
+                ..Default::default()
+            });
+            let (player, state) = game.initial();
+            let t = Tree::new(game, GetState, player, state);
+            let mut search = ReproducibleSearch::new(seed, 4);
+            let mut history = Vec::new();
+            for _ in 0..3 {
+                assert!(search.run(&t, 25) > 0);
+                let mut report = t.search_report(true);
+                report.children.sort_by_key(|c| c.action);
+                report.nodes.sort_by_key(|n| n.hash);
+                let a = match t.apply_best_action() {
+                    Status::Action(a) => a,
+                    s => panic!("unexpected status {:?}", s),
+                };
+                history.push((a, report));
+            }
+            history
+        };
+
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+    }
+
+    #[test]
+    fn test_undo() {
+        for &keep_subtrees in &[true, false] {
+            let game = Nim {
+                max_move: MAX_MOVE,
+                rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+            };
+            let t = Tree::new(game, GetState, Player::P1, INIT).with_history(2, keep_subtrees);
+            assert!(!t.undo());
+
+            for _ in 0..100 {
+                t.step();
+            }
+            let mut edges = t.get_next_move_edges().unwrap();
+            edges.sort_by_key(|(a, _)| *a);
+
+            let mut states = Vec::new();
+            for _ in 0..3 {
+                t.apply_best_action();
+                states.push(t.get_root_info().state.unwrap());
+                for _ in 0..20 {
+                    t.step();
+                }
+            }
+            assert_eq!(t.history_len(), 2);
+
+            // the oldest root was evicted from the history
+            assert!(t.undo());
+            assert_eq!(t.get_root_info().state, Some(states[1]));
+            assert!(t.undo());
+            assert_eq!(t.get_root_info().state, Some(states[0]));
+            assert!(!t.undo());
+            assert_eq!(t.history_len(), 0);
+
+            if keep_subtrees {
+                // the statistics of the subtree that was searched after the move are intact
+                assert!(t
+                    .get_next_move_edges()
+                    .unwrap()
+                    .iter()
+                    .any(|(_, e)| e.visits > 0));
+            } else {
+                assert!(t.get_next_move_edges().is_none());
+                assert_eq!(t.get_registry_info().snapshot().len, 1);
+            }
+
+            // search resumes from the restored root
+            assert!(t.step().is_some());
+            t.apply_best_action();
+            assert_eq!(t.history_len(), 1);
+        }
+
+        // undoing a move with the subtrees kept restores the edges of the previous root
+        let game = Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        };
+        let t = Tree::new(game, GetState, Player::P1, INIT).with_history(1, true);
+        for _ in 0..100 {
+            t.step();
+        }
+        let mut edges = t.get_next_move_edges().unwrap();
+        edges.sort_by_key(|(a, _)| *a);
+        let len = t.get_registry_info().snapshot().len;
+        t.apply_best_action();
+        assert!(t.undo());
+        let mut edges_undone = t.get_next_move_edges().unwrap();
+        edges_undone.sort_by_key(|(a, _)| *a);
+        assert_eq!(edges, edges_undone);
+        assert_eq!(t.get_root_info().state, Some(INIT));
+        assert_eq!(t.get_registry_info().snapshot().len, len);
+    }
+
+    #[test]
+    fn test_ponder() {
+        let game = Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        };
+        let t = Arc::new(Tree::new(game, GetState, Player::P1, INIT));
+        let wait_for_steps = |ponder: &Ponder<_>, n: usize| {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+            while ponder.n_steps() < n {
+                assert!(
+                    std::time::Instant::now() < deadline,
+                    "ponder stalled at {} of {} steps",
+                    ponder.n_steps(),
+                    n
+                );
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        };
+
+        let ponder = Ponder::start(Arc::clone(&t), 1);
+        wait_for_steps(&ponder, 50);
+        ponder.pause();
+        assert!(ponder.is_paused());
+        let n_steps = ponder.n_steps();
+        let n_nodes = t.get_registry_info().snapshot().len;
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert_eq!(ponder.n_steps(), n_steps);
+        assert_eq!(t.get_registry_info().snapshot().len, n_nodes);
+
+        // the opponent's move is applied while the search is running
+        ponder.resume();
+        let opponent_move = match ponder.best_action() {
+            Status::Action(a) => a,
+            s => panic!("unexpected status {:?}", s),
+        };
+        ponder.tree().apply_action(&opponent_move);
+        wait_for_steps(&ponder, n_steps + 50);
+        assert_eq!(t.get_root_info().state, Some(INIT - opponent_move));
+        assert!(matches!(ponder.best_action(), Status::Action(_)));
+
+        let tree = ponder.stop();
+        assert!(Arc::ptr_eq(&tree, &t));
+        assert!(tree
+            .get_next_move_edges()
+            .unwrap()
+            .iter()
+            .any(|(_, e)| e.visits > 0));
+
+        // once the tree is fully expanded, the workers stop counting (and spinning on) steps
+        let game = Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        };
+        let t = Arc::new(Tree::new(game, GetState, Player::P1, 3));
+        let ponder = Ponder::start(Arc::clone(&t), 2);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let n_steps = ponder.n_steps();
+        assert!(n_steps > 0);
+        assert!(n_steps <= t.get_registry_info().snapshot().len);
+        let tree = ponder.stop();
+        assert_eq!(tree.principal_variation(), vec![3]);
+
+        // a panicking evaluator is propagated when the search is stopped
+        let game = SyntheticGame::new(SyntheticConfig {
+            panic_rate: 0.2,
+            ..Default::default()
+        });
+        let (player, state) = game.initial();
+        let ponder = Ponder::start(Arc::new(Tree::new(game, GetState, player, state)), 2);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| ponder.stop()))
+            .expect_err("worker panic was swallowed");
+        // rather than the panic of a worker that ran into the poisoned locks of the tree
+        let message = payload.downcast_ref::<String>().unwrap();
+        assert!(
+            message.starts_with("synthetic evaluator failure"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_root_parallel() {
+        let n_steps = 100;
+        let make_tree = |i: usize| {
+            let game = Nim {
+                max_move: MAX_MOVE,
+                rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(i as u64)),
+            };
+            Tree::new(game, GetState, Player::P1, INIT)
+        };
+
+        let t = make_tree(0);
+        for _ in 0..n_steps {
+            t.step();
+        }
+        let report = t.search_report(true);
+        assert_eq!(report.children.len(), MAX_MOVE);
+        assert_eq!(report.nodes.len(), t.get_registry_info().snapshot().len);
+        assert_eq!(report.nodes[0].visits, n_steps - 1);
+
+        let config = RootParallelConfig {
+            n_trees: 3,
+            n_steps,
+            include_nodes: true,
+        };
+        let merged = search_root_parallel(&config, make_tree, &LocalTransport::new()).unwrap();
+        assert_eq!(merged.n_trees, 3);
+        assert_eq!(merged.root_hash, report.root_hash);
+        assert_eq!(merged.children.len(), MAX_MOVE);
+        // every step except the one expanding the root traverses exactly one edge from the root
+        let visits = merged.children.iter().map(|c| c.visits).sum::<usize>();
+        assert_eq!(visits, 3 * (n_steps - 1));
+        assert_eq!(merged.nodes[0].visits, visits);
+        assert!(merged.best_action().is_some());
+    }
+
+    // `Nim` with an adjustable score for the leaves that are not terminal (`Nim` scores them as a
+    // draw); used via the `GameDynamics` implementation for types dereferencing to a `DynGD`
+    struct Weighted {
+        nim: Nim,
+        player1: Mutex<f64>,
+    }
+
+    impl BaseGD for Weighted {
+        type Player = Player;
+        type State = usize;
+        type Action = usize;
+        type Score = Score;
+        type ActionIter = ActionIter;
+
+        fn available_actions(&self, player: &Player, state: &usize) -> Option<ActionIter> {
+            <Nim as BaseGD>::available_actions(&self.nim, player, state)
+        }
+
+        fn apply_action(&self, state: usize, action: &usize) -> Option<usize> {
+            <Nim as BaseGD>::apply_action(&self.nim, state, action)
+        }
+
+        fn score_leaf(
+            &self,
+            score: Option<&Score>,
+            player: &Player,
+            state: &usize,
+        ) -> Option<Score> {
+            if *state == 0 {
+                return <Nim as BaseGD>::score_leaf(&self.nim, score, player, state);
+            }
+            let player1 = *self.player1.lock().unwrap();
+            Some(Score {
+                player1,
+                player2: 1.0 - player1,
+                visits_direct: AtomicUsize::new(1),
+            })
+        }
+    }
+
+    impl DynGD for Weighted {
+        fn select_node(
+            &self,
+            parent_score: Option<&Score>,
+            parent_player: &Player,
+            parent_node_state: &usize,
+            purpose: SelectNodeState,
+            scores_and_actions: &mut dyn Iterator<Item = (Ref<'_, Option<Score>>, Ref<'_, usize>)>,
+        ) -> usize {
+            <Nim as DynGD>::select_node(
+                &self.nim,
+                parent_score,
+                parent_player,
+                parent_node_state,
+                purpose,
+                scores_and_actions,
+            )
+        }
+
+        fn backprop_scores(
+            &self,
+            player: &Player,
+            score_current: Option<&Score>,
+            child_scores: &mut dyn Iterator<Item = Ref<'_, Score>>,
+        ) -> Option<Score> {
+            <Nim as DynGD>::backprop_scores(&self.nim, player, score_current, child_scores)
+        }
+    }
+
+    #[test]
+    fn test_rescore() {
+        let game = Box::new(Weighted {
+            nim: Nim {
+                max_move: MAX_MOVE,
+                rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+            },
+            player1: Mutex::new(0.5),
+        });
+        let t = Tree::new(game, GetState, Player::P1, INIT);
+        for _ in 0..200 {
+            t.step();
+        }
+        let n_calls = t.get_registry_info().snapshot().score_leaf_calls;
+        let n_nodes = t.get_registry_info().snapshot().len;
+        let root_before = t.get_root_info().score.unwrap().player1;
+
+        // the evaluator now favours the first player
+        *t.get_game_dynamics().player1.lock().unwrap() = 0.9;
+        let n_leaves = t.rescore(4);
+        assert!(n_leaves > 0 && n_leaves < n_nodes);
+        assert_eq!(
+            t.get_registry_info().snapshot().score_leaf_calls,
+            n_calls + n_leaves
+        );
+        assert_eq!(t.get_registry_info().snapshot().len, n_nodes);
+
+        // leaves are scored by the new evaluator, and every other node has the best score of its
+        // children for the player taking an action at the node
+        for cursor in t.find_nodes(&NodeFilter::default()) {
+            let info = cursor.info().unwrap();
+            let score = info.score.unwrap().player1;
+            let children = cursor.children();
+            if children.is_empty() {
+                let expected = match (cursor.state().unwrap(), info.player) {
+                    (0, Player::P1) => 0.0,
+                    (0, Player::P2) => 1.0,
+                    _ => 0.9,
+                };
+                assert_eq!(score, expected);
+            } else {
+                let scores = children
+                    .iter()
+                    .map(|(_, _, c)| c.info().unwrap().score.unwrap().player1);
+                let best = match info.player {
+                    Player::P1 => scores.fold(f64::NEG_INFINITY, f64::max),
+                    Player::P2 => scores.fold(f64::INFINITY, f64::min),
+                };
+                assert_eq!(score, best);
+            }
+        }
+        let root_after = t.get_root_info().score.unwrap().player1;
+        assert_ne!(root_after, root_before);
+        assert!(t.step().is_some());
+    }
+
+    #[test]
+    fn test_cursor() {
+        let game = Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        };
+        let t = Tree::new(game, GetState, Player::P1, INIT);
+        for _ in 0..500 {
+            t.step();
+        }
+
+        let root = t.root_cursor();
+        assert_eq!(root.state(), Some(INIT));
+        assert!(root.parents().is_empty());
+        let children = root.children();
+        assert_eq!(children.len(), MAX_MOVE);
+        assert_eq!(
+            root.visits(),
+            children
+                .iter()
+                .map(|(_, stats, _)| stats.visits)
+                .sum::<usize>()
+        );
+        for (a, _, child) in children.iter() {
+            assert_eq!(child.state(), Some(INIT - a));
+            assert!(child.parents().iter().any(|(pa, _)| pa == a));
+        }
+
+        // two different paths to the same state
+        let c12 = t.find_by_path(&[1, 2]).unwrap();
+        let c21 = t.find_by_path(&[2, 1]).unwrap();
+        assert_eq!(c12.state(), Some(INIT - 3));
+        assert_eq!(c12.info().unwrap().player, Player::P1);
+        assert_eq!(c12.parents().len(), c21.parents().len());
+        assert!(c12.parents().len() > 1);
+        let c = t.find_by_state(&Player::P1, &(INIT - 3)).unwrap();
+        assert_eq!(c.visits(), c12.visits());
+        assert!(t.find_by_state(&Player::P1, &(INIT - 1)).is_none());
+        assert!(t.find_by_path(&[MAX_MOVE + 1]).is_none());
+        assert!(t.count_transpositions() > 0);
+
+        let depth1 = t.find_nodes(&NodeFilter {
+            depth: 1..=1,
+            ..Default::default()
+        });
+        assert_eq!(depth1.len(), MAX_MOVE);
+        let visited = t.find_nodes(&NodeFilter {
+            visits: 1..=usize::MAX,
+            ..Default::default()
+        });
+        assert!(visited.iter().all(|c| c.visits() > 0));
+        assert_eq!(
+            t.find_nodes(&NodeFilter::default()).len(),
+            t.get_registry_info().snapshot().len
+        );
+
+        // cursors do not keep nodes alive; the child reached by `2` can't be reached from the new
+        // root by the same player
+        let pruned = root.child(&2).unwrap();
+        t.apply_action(&1);
+        assert!(t.find_by_path(&[2]).is_some());
+        assert!(pruned.state().is_none());
+        assert!(pruned.children().is_empty());
+    }
+
+    #[test]
+    fn test_memory_hints() {
+        let tree = |memory_hints| {
+            let game = Nim {
+                max_move: MAX_MOVE,
+                rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+            };
+            let t = Tree::new(game, StoreState, Player::P1, INIT).with_memory_hints(memory_hints);
+            for _ in 0..100 {
+                t.step();
+            }
+            t
+        };
+        let memory = |t: &TreeAlias<Nim, StoreState>| {
+            let info = t.get_registry_info().snapshot();
+            (info.len, info.memory_bytes)
+        };
+
+        let t0 = tree(MemoryHints::default());
+        let t1 = tree(MemoryHints {
+            state: 100,
+            action_iter: 1000,
+            ..Default::default()
+        });
+        let (len, bytes0) = memory(&t0);
+        let (len1, bytes1) = memory(&t1);
+        assert_eq!(len, len1);
+        assert!(bytes0 > len * std::mem::size_of::<usize>());
+        // the iterators of the expanded nodes are no longer charged once all children are created
+        assert_eq!(bytes1, bytes0 + 100 * len);
+
+        t0.apply_action(&1);
+        t1.apply_action(&1);
+        let (len, bytes0) = memory(&t0);
+        let (_, bytes1) = memory(&t1);
+        assert!(bytes0 < memory(&tree(MemoryHints::default())).1);
+        assert_eq!(bytes1, bytes0 + 100 * len);
+    }
+
+    #[test]
+    fn test_trace() {
+        let game = Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        };
+        let t = Tree::new(game, GetState, Player::P1, INIT).with_history(1, true);
+        let recorder = Arc::new(TraceRecorder::new());
+        t.add_observer(Arc::clone(&recorder) as _);
+
+        let mut actions = Vec::new();
+        for _ in 0..3 {
+            for _ in 0..100 {
+                t.step();
+            }
+            let visits = t.root_cursor().visits();
+            match t.apply_best_action() {
+                Status::Action(a) => actions.push((a, visits)),
+                _ => panic!("the game is not over"),
+            }
+        }
+        assert!(t.undo());
+        actions.pop();
+
+        let records = recorder.records();
+        assert_eq!(records.len(), actions.len());
+        let mut state = INIT;
+        for (i, (record, (a, visits))) in records.iter().zip(actions.iter()).enumerate() {
+            assert_eq!(record.ply, i);
+            assert_eq!(record.action, *a);
+            assert_eq!(record.state, Some(state));
+            assert_eq!(record.children.len(), MAX_MOVE);
+            let n = record.children.iter().map(|c| c.visits).sum::<usize>();
+            assert_eq!(n, *visits);
+            state -= a;
+        }
+    }
+
+    #[test]
+    fn test_search_with_progress() {
+        let make_tree = || {
+            let game = Nim {
+                max_move: MAX_MOVE,
+                rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+            };
+            Tree::new(game, GetState, Player::P1, INIT)
+        };
+
+        let t = make_tree();
+        let mut snapshots = Vec::new();
+        let last = search_with_progress(
+            &t,
+            SearchBudget::Steps(1000),
+            2,
+            ProgressInterval::Steps(100),
+            |p| snapshots.push((p.n_steps, p.pv.clone())),
+        );
+        assert_eq!(last.n_steps, 1000);
+        assert!(last.steps_per_sec > 0.0);
+        assert_eq!(last.pv, t.principal_variation());
+        assert!(!last.pv.is_empty());
+        assert!(matches!(last.best_action, Status::Action(a) if a == last.pv[0]));
+        // a snapshot for every 100 steps (possibly fewer if the reporting thread falls behind)
+        assert!(snapshots.len() <= 10);
+        assert_eq!(snapshots.last().unwrap().0, 1000);
+        assert!(snapshots.windows(2).all(|w| w[0].0 < w[1].0));
+
+        let t = make_tree();
+        let mut n_snapshots = 0;
+        let last = search_with_progress(
+            &t,
+            SearchBudget::Time(std::time::Duration::from_millis(50)),
+            1,
+            ProgressInterval::Time(std::time::Duration::from_millis(5)),
+            |_| n_snapshots += 1,
+        );
+        assert!(last.elapsed >= std::time::Duration::from_millis(50));
+        assert!(n_snapshots > 1);
+    }
+
+    #[test]
+    fn test_conformance() {
+        let nim = Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        };
+        let config = ConformanceConfig {
+            n_walks: 20,
+            ..Default::default()
+        };
+        assert_game_dynamics(&nim, Player::P1, INIT, &config);
+        assert_game_dynamics(&nim, Player::P2, MAX_MOVE + 1, &config);
+    }
+
+    // minimal executor so that the tests do not depend on an async runtime
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        struct ThreadWaker(std::thread::Thread);
+        impl std::task::Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut f = Box::pin(f);
+        loop {
+            match f.as_mut().poll(&mut cx) {
+                std::task::Poll::Ready(out) => return out,
+                std::task::Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_async_search() {
+        let make_tree = || {
+            let game = Nim {
+                max_move: MAX_MOVE,
+                rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+            };
+            Arc::new(Tree::new(game, GetState, Player::P1, INIT))
+        };
+
+        let config = AsyncSearchConfig {
+            budget: SearchBudget::Steps(300),
+            n_threads: 2,
+            progress_interval: std::time::Duration::from_millis(1),
+        };
+        let task = SearchTask::spawn(make_tree(), config);
+        let mut progress = task.progress();
+        let snapshots = block_on(async {
+            let mut snapshots = Vec::new();
+            while let Some(snapshot) = progress.next_snapshot().await {
+                snapshots.push(snapshot);
+            }
+            snapshots
+        });
+        let last = snapshots.last().unwrap();
+        assert_eq!(last.n_steps, 300);
+        assert!(last.root_score.is_some());
+        assert!(snapshots.windows(2).all(|w| w[0].n_steps <= w[1].n_steps));
+
+        let outcome = block_on(task);
+        assert_eq!(outcome.n_steps, 300);
+        assert!(!outcome.cancelled);
+        assert!(matches!(outcome.best_action, Status::Action(_)));
+
+        // dropping the task cancels the search
+        let config = AsyncSearchConfig {
+            budget: SearchBudget::Time(std::time::Duration::from_secs(600)),
+            ..AsyncSearchConfig::default()
+        };
+        let task = SearchTask::spawn(make_tree(), config);
+        let mut progress = task.progress();
+        drop(task);
+        // wait for the end of the progress stream on another thread so that the test fails fast
+        // (rather than after the budget of ten minutes) if the search is not cancelled
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let last = block_on(async {
+                let mut last = None;
+                while let Some(snapshot) = progress.next_snapshot().await {
+                    last = Some(snapshot);
+                }
+                last
+            });
+            let _ = tx.send(last);
+        });
+        let timeout = std::time::Duration::from_secs(5);
+        let last = rx.recv_timeout(timeout).expect("search was not cancelled");
+        assert!(last.unwrap().elapsed < timeout);
+
+        // the search of a terminal root ends without waiting for the budget
+        let game = Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        };
+        let config = AsyncSearchConfig {
+            budget: SearchBudget::Time(std::time::Duration::from_secs(600)),
+            ..AsyncSearchConfig::default()
+        };
+        let task = SearchTask::spawn(Arc::new(Tree::new(game, GetState, Player::P1, 0)), config);
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(block_on(task));
+        });
+        let outcome = rx
+            .recv_timeout(timeout)
+            .expect("search of terminal root kept running");
+        assert!(matches!(outcome.best_action, Status::Terminal));
+
+        // the workers back off once the tree is fully expanded instead of spinning until the
+        // deadline
+        let game = Nim {
+            max_move: MAX_MOVE,
+            rng: Mutex::new(rand::rngs::StdRng::seed_from_u64(0)),
+        };
+        let config = AsyncSearchConfig {
+            budget: SearchBudget::Time(std::time::Duration::from_millis(200)),
+            n_threads: 2,
+            ..AsyncSearchConfig::default()
+        };
+        let t = Arc::new(Tree::new(game, GetState, Player::P1, 3));
+        let outcome = block_on(SearchTask::spawn(t, config));
+        assert!(outcome.n_steps < 1000, "{} steps", outcome.n_steps);
+    }
+
     #[test]
     #[allow(clippy::type_complexity)]
     fn test_tree_dynamic() {